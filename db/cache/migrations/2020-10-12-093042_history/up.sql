@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+create table sql_history(
+    id INTEGER PRIMARY key not null,
+    sql_asset_id integer not null,
+    node_id text not null,
+    direction integer not null,
+    seal text,
+    amount bigint not null,
+    witness_txid text,
+    timestamp datetime not null
+);
@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+
+create table sql_allocations_backup(
+    id INTEGER PRIMARY key not null,
+    sql_allocation_utxo_id integer not null,
+    node_id text not null,
+    assignment_index integer not null,
+    amount bigint not null,
+    blinding text not null
+);
+insert into sql_allocations_backup
+    select id, sql_allocation_utxo_id, node_id, assignment_index, amount, blinding
+    from sql_allocations;
+drop table sql_allocations;
+alter table sql_allocations_backup rename to sql_allocations;
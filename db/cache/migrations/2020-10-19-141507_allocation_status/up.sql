@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+alter table sql_allocations add column witness_txid text;
+alter table sql_allocations add column status integer not null default 0;
+alter table sql_allocations add column height integer not null default 0;
+alter table sql_allocations add column confirmations integer not null default 0;
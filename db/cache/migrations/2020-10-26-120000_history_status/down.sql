@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+
+create table sql_history_backup(
+    id INTEGER PRIMARY key not null,
+    sql_asset_id integer not null,
+    node_id text not null,
+    direction integer not null,
+    seal text,
+    amount bigint not null,
+    witness_txid text,
+    timestamp datetime not null
+);
+insert into sql_history_backup
+    select id, sql_asset_id, node_id, direction, seal, amount, witness_txid,
+        timestamp
+    from sql_history;
+drop table sql_history;
+alter table sql_history_backup rename to sql_history;
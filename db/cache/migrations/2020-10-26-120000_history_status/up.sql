@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+alter table sql_history add column status integer not null default 0;
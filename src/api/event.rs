@@ -0,0 +1,232 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::collections::BTreeSet;
+
+use lnpbp::bitcoin::{OutPoint, Txid};
+use lnpbp::rgb::{AtomicValue, ContractId, NodeId};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::api::ValidationStatus;
+use crate::auth::Role;
+use crate::util::chain::TxStatus;
+
+/// Events published by stash and contract daemons over their
+/// publish-subscribe sockets
+#[derive(Clone, Debug, Display, LnpApi)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(
+        crate = "serde_crate",
+        tag = "event",
+        content = "data",
+        rename_all = "snake_case"
+    )
+)]
+#[lnp_api(encoding = "strict")]
+#[display(Debug)]
+#[non_exhaustive]
+pub enum Event {
+    /// Schema was added to the stash
+    #[lnp_api(type = 0x0A01)]
+    SchemaAdded(::lnpbp::rgb::SchemaId),
+
+    /// Asset was issued or imported into the asset cache
+    #[lnp_api(type = 0x0A03)]
+    AssetImported(::lnpbp::rgb::ContractId),
+
+    /// New allocation was added to the asset cache
+    #[lnp_api(type = 0x0A05)]
+    AllocationAdded(crate::api::event::AllocationEvent),
+
+    /// Allocation was removed from the asset cache
+    #[lnp_api(type = 0x0A07)]
+    AllocationRemoved(crate::api::event::AllocationEvent),
+
+    /// Consignment was merged into the stash
+    #[lnp_api(type = 0x0A09)]
+    ConsignmentMerged(crate::api::event::MergeEvent),
+
+    /// Stash was requested to forget the listed state assignments. Stash
+    /// does not prune its data yet, so only the subscribers forget them.
+    #[lnp_api(type = 0x0A0B)]
+    ForgetRequested(Vec<(::lnpbp::rgb::NodeId, u16)>),
+
+    /// Consignment validation was completed
+    #[lnp_api(type = 0x0A0D)]
+    ValidationFinished(crate::api::event::ValidationEvent),
+
+    /// Witness transaction of merged consignments has reached the required
+    /// number of confirmations
+    #[lnp_api(type = 0x0A0F)]
+    WitnessConfirmed(crate::api::event::WitnessEvent),
+
+    /// Outpoint holding an allocation was spent, so the allocation was
+    /// removed from the asset cache
+    #[lnp_api(type = 0x0A11)]
+    AllocationSpent(crate::api::event::SpendEvent),
+
+    /// Mined witness transaction of merged consignments was reorged out of
+    /// the chain, or an unmined one was dropped from the mempool; it may need
+    /// to be broadcasted again
+    #[lnp_api(type = 0x0A13)]
+    WitnessReorged(crate::api::event::WitnessEvent),
+
+    /// Witness transaction of merged consignments was double-spent, so the
+    /// transfer has to be negotiated again
+    #[lnp_api(type = 0x0A15)]
+    WitnessConflicted(crate::api::event::WitnessEvent),
+
+    /// Allocation was rolled back since its witness transaction was reorged
+    /// out of the chain or double-spent
+    #[lnp_api(type = 0x0A17)]
+    AllocationRolledBack(crate::api::event::RollbackEvent),
+}
+
+impl Event {
+    /// Minimal role a client must have to receive the event; it is the same
+    /// as the role of the requests which the event tells about
+    pub fn required_role(&self) -> Role {
+        match self {
+            Event::ForgetRequested(_) => Role::Admin,
+            Event::ConsignmentMerged(_)
+            | Event::WitnessConfirmed(_)
+            | Event::WitnessReorged(_)
+            | Event::WitnessConflicted(_)
+            | Event::AllocationRolledBack(_) => Role::Transfer,
+            Event::SchemaAdded(_)
+            | Event::AssetImported(_)
+            | Event::AllocationAdded(_)
+            | Event::AllocationRemoved(_)
+            | Event::ValidationFinished(_)
+            | Event::AllocationSpent(_) => Role::ReadOnly,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct AllocationEvent {
+    pub contract_id: ContractId,
+    pub outpoint: OutPoint,
+    pub node_id: NodeId,
+    pub index: u16,
+    pub amount: AtomicValue,
+}
+
+impl AllocationEvent {
+    pub fn with(
+        contract_id: ContractId,
+        allocation: &crate::fungible::Allocation,
+    ) -> Self {
+        Self {
+            contract_id,
+            outpoint: *allocation.outpoint(),
+            node_id: *allocation.node_id(),
+            index: *allocation.index(),
+            amount: allocation.value().value,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct ValidationEvent {
+    pub contract_id: ContractId,
+    pub status: ValidationStatus,
+}
+
+/// Merged consignment, identified without disclosing the revealed seals.
+/// Stash clients with the transfer role may read the merged data with
+/// [`crate::api::stash::Request::ReadMerge`].
+#[derive(
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    StrictEncode,
+    StrictDecode,
+    Debug,
+    Display,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct MergeEvent {
+    pub contract_id: ContractId,
+    /// State transitions and extensions of the consignment
+    pub node_ids: BTreeSet<NodeId>,
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct WitnessEvent {
+    pub txid: Txid,
+    /// Contracts whose state transitions are anchored in the transaction
+    pub contract_ids: BTreeSet<ContractId>,
+    pub status: TxStatus,
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct SpendEvent {
+    pub allocation: AllocationEvent,
+    /// Spending transaction, which has the number of confirmations required
+    /// by the asset cache
+    pub spending_txid: Txid,
+    /// Whether the spending transaction is a witness of a state transition
+    /// known to the asset cache; unknown spends may mean loss of the assets
+    pub known: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct RollbackEvent {
+    pub allocation: AllocationEvent,
+    /// Transaction which status determines the allocation status
+    pub txid: Txid,
+    /// New transaction status: conflicted for double-spent transactions,
+    /// otherwise the transaction was reorged out
+    pub status: TxStatus,
+}
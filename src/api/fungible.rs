@@ -19,6 +19,7 @@ use lnpbp::bitcoin::OutPoint;
 use lnpbp::bp::blind::OutpointReveal;
 use lnpbp::rgb::{Consignment, ContractId};
 
+use crate::auth::Role;
 use crate::fungible::{Outcoincealed, Outcoins};
 use crate::util::SealSpec;
 use crate::DataFormat;
@@ -28,6 +29,15 @@ use crate::DataFormat;
 #[display(Debug)]
 #[non_exhaustive]
 pub enum Request {
+    #[lnp_api(type = 0x0001)]
+    Hello(crate::api::hello::Hello),
+
+    #[lnp_api(type = 0x0003)]
+    Shutdown(),
+
+    #[lnp_api(type = 0x0005)]
+    Status(),
+
     #[lnp_api(type = 0x0101)]
     Issue(crate::api::fungible::Issue),
 
@@ -35,7 +45,7 @@ pub enum Request {
     Transfer(crate::api::fungible::TransferApi),
 
     #[lnp_api(type = 0x0105)]
-    Validate(::lnpbp::rgb::Consignment),
+    Validate(crate::api::stash::ValidateRequest),
 
     #[lnp_api(type = 0x0107)]
     Accept(crate::api::fungible::AcceptApi),
@@ -57,6 +67,74 @@ pub enum Request {
 
     #[lnp_api(type = 0xFF03)]
     Allocations(ContractId),
+
+    #[lnp_api(type = 0xFF04)]
+    History(crate::api::fungible::HistoryApi),
+
+    #[lnp_api(type = 0xFF05)]
+    Balance(ContractId),
+}
+
+impl Request {
+    /// Names of all requests, as reported by [`Request::name`]
+    pub const NAMES: &'static [&'static str] = &[
+        "hello",
+        "shutdown",
+        "status",
+        "issue",
+        "transfer",
+        "validate",
+        "accept",
+        "import_asset",
+        "export_asset",
+        "forget",
+        "sync",
+        "assets",
+        "allocations",
+        "history",
+        "balance",
+    ];
+
+    /// Request name used in logs and error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Hello(_) => "hello",
+            Request::Shutdown() => "shutdown",
+            Request::Status() => "status",
+            Request::Issue(_) => "issue",
+            Request::Transfer(_) => "transfer",
+            Request::Validate(_) => "validate",
+            Request::Accept(_) => "accept",
+            Request::ImportAsset(_) => "import_asset",
+            Request::ExportAsset(_) => "export_asset",
+            Request::Forget(_) => "forget",
+            Request::Sync(_) => "sync",
+            Request::Assets(_) => "assets",
+            Request::Allocations(_) => "allocations",
+            Request::History(_) => "history",
+            Request::Balance(_) => "balance",
+        }
+    }
+
+    /// Minimal client role required to call the request
+    pub fn required_role(&self) -> Role {
+        match self {
+            Request::Shutdown()
+            | Request::Issue(_)
+            | Request::ImportAsset(_)
+            | Request::Forget(_) => Role::Admin,
+            Request::Transfer(_) | Request::Accept(_) => Role::Transfer,
+            Request::Hello(_)
+            | Request::Status()
+            | Request::Validate(_)
+            | Request::ExportAsset(_)
+            | Request::Sync(_)
+            | Request::Assets(_)
+            | Request::Allocations(_)
+            | Request::History(_)
+            | Request::Balance(_) => Role::ReadOnly,
+        }
+    }
 }
 
 #[derive(Clap, Clone, PartialEq, StrictEncode, StrictDecode, Debug, Display)]
@@ -98,11 +176,20 @@ pub struct Issue {
 
 #[derive(Clone, PartialEq, StrictEncode, StrictDecode, Debug, Display)]
 #[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
 pub struct TransferApi {
     /// Asset contract id
     pub contract_id: ContractId,
 
     /// Base layer transaction structure to use
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::psbt_base64")
+    )]
     pub psbt: PartiallySignedTransaction,
 
     /// Asset input: unspent transaction outputs
@@ -123,14 +210,41 @@ pub struct TransferApi {
 
 #[derive(Clone, StrictEncode, StrictDecode, Debug, Display)]
 #[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
 pub struct AcceptApi {
     /// Raw consignment data
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::strict_base64")
+    )]
     pub consignment: Consignment,
 
     /// Reveal outpoints data used during invoice creation
     pub reveal_outpoints: Vec<OutpointReveal>,
 }
 
+#[derive(Clone, PartialEq, StrictEncode, StrictDecode, Debug, Display)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct HistoryApi {
+    /// Asset contract id
+    pub contract_id: ContractId,
+
+    /// Number of the history entries to skip
+    pub offset: u32,
+
+    /// Maximum number of the history entries to return
+    pub limit: u32,
+}
+
 fn ticker_validator(name: &str) -> Result<(), String> {
     let re = Regex::new(r"^[A-Z]{3,8}$").expect("Regex parse failure");
     if !re.is_match(&name) {
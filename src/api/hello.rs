@@ -0,0 +1,175 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Handshake between clients and daemons. Before issuing any other request
+//! client sends [`Hello`] and daemon replies with its [`Capabilities`], so
+//! the client can detect protocol version mismatch and avoid requests which
+//! the daemon does not support.
+
+use lnpbp::bp;
+use lnpbp::rgb::SchemaId;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::api::Reply;
+use crate::constants::RGB_PROTOCOL_VERSION;
+use crate::error::{ApiErrorType, ErrorCode, ServiceErrorDomain};
+
+/// Returns agent string for the given program name, which includes the
+/// version of this library
+pub fn agent(name: &str) -> String {
+    format!("{}/{}", name, env!("CARGO_PKG_VERSION"))
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[display(Debug)]
+pub struct Hello {
+    /// Version of the API protocol used by the client
+    pub protocol_version: u16,
+    /// Client name and version; used for logging only
+    pub agent: String,
+}
+
+impl Hello {
+    pub fn with(name: &str) -> Self {
+        Self {
+            protocol_version: RGB_PROTOCOL_VERSION,
+            agent: agent(name),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, StrictEncode, StrictDecode, Debug, Display)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct Capabilities {
+    /// Version of the API protocol used by the daemon
+    pub protocol_version: u16,
+    /// Daemon name and version
+    pub agent: String,
+    /// Names of the requests supported by the daemon
+    pub requests: Vec<String>,
+    /// Schemata of the contracts handled by the daemon
+    pub schemata: Vec<SchemaId>,
+    /// Bitcoin network used by the daemon
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::rust::display_fromstr")
+    )]
+    pub network: bp::Chain,
+    /// Storage backend used by the daemon
+    pub storage: String,
+    /// Cache or index backend used by the daemon
+    pub cache: String,
+}
+
+impl Capabilities {
+    /// Checks whether daemon protocol version matches the client one
+    pub fn check_version(&self) -> Result<(), ServiceErrorDomain> {
+        if self.protocol_version != RGB_PROTOCOL_VERSION {
+            Err(ServiceErrorDomain::Api(ApiErrorType::IncompatibleVersion {
+                client: RGB_PROTOCOL_VERSION,
+                daemon: self.protocol_version,
+            }))?
+        }
+        Ok(())
+    }
+
+    /// Checks whether the request with the given name is supported by the
+    /// daemon
+    pub fn supports(&self, request: &str) -> bool {
+        self.requests.iter().any(|name| name == request)
+    }
+}
+
+/// State of the handshake performed by a client with one of the daemons
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub enum Handshake {
+    /// Handshake was not performed yet
+    Pending,
+
+    /// Daemon predates the handshake and does not recognize [`Hello`]
+    /// request; its capabilities are unknown, so all requests are sent to it
+    /// as is
+    Legacy,
+
+    /// Daemon has reported its capabilities
+    Completed(Capabilities),
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Handshake::Pending
+    }
+}
+
+impl Handshake {
+    /// Processes daemon reply to [`Hello`] request. Fails if the daemon uses
+    /// incompatible protocol version.
+    pub fn with_reply(reply: &Reply) -> Result<Self, ServiceErrorDomain> {
+        match reply {
+            Reply::Capabilities(capabilities) => {
+                capabilities.check_version()?;
+                debug!(
+                    "Connected to {} using protocol version {}",
+                    capabilities.agent, capabilities.protocol_version
+                );
+                Ok(Handshake::Completed(capabilities.clone()))
+            }
+            Reply::Failure(failure)
+                if failure.error_code() == ErrorCode::LnpRpc =>
+            {
+                warn!(
+                    "Daemon does not support API handshake; probably it \
+                     runs an outdated version"
+                );
+                Ok(Handshake::Legacy)
+            }
+            _ => Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply)),
+        }
+    }
+
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        *self == Handshake::Pending
+    }
+
+    /// Returns daemon capabilities, if they are known
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        match self {
+            Handshake::Completed(capabilities) => Some(capabilities),
+            _ => None,
+        }
+    }
+
+    /// Checks that the request is supported by the daemon. Legacy daemons
+    /// are assumed to support all requests.
+    pub fn check_request(
+        &self,
+        request: &str,
+    ) -> Result<(), ServiceErrorDomain> {
+        match self.capabilities() {
+            Some(capabilities) if !capabilities.supports(request) => {
+                Err(ServiceErrorDomain::Api(ApiErrorType::UnknownCommand {
+                    command: request.to_string(),
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -11,8 +11,17 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+pub mod event;
 pub mod fungible;
+pub mod hello;
 pub mod reply;
+pub mod rgbd;
 pub mod stash;
+pub mod status;
+pub mod validation;
 
+pub use event::Event;
+pub use hello::{Capabilities, Handshake, Hello};
 pub use reply::Reply;
+pub use status::Status;
+pub use validation::ValidationStatus;
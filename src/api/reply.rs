@@ -18,6 +18,10 @@ use lnpbp::bp::Psbt;
 use lnpbp::lnp;
 use lnpbp::rgb::{AtomicValue, Consignment, ContractId};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::ErrorCode;
 use crate::DataFormat;
 
 #[cfg(feature = "node")]
@@ -64,10 +68,26 @@ pub enum Reply {
     #[lnp_api(type = 0xFF0A)]
     Transitions(Vec<::lnpbp::rgb::Transition>),
 
+    #[lnp_api(type = 0xFF0B)]
+    ValidationStatus(crate::api::validation::ValidationStatus),
+
     #[lnp_api(type = 0xFF0C)]
     Transfer(crate::api::reply::Transfer),
-    /* #[lnp_api(type = 0xFF0B)]
-    ValidationStatus(::lnpbp::rgb::validation::Status), */
+
+    #[lnp_api(type = 0xFF0D)]
+    History(Vec<crate::fungible::HistoryEntry>),
+
+    #[lnp_api(type = 0xFF0E)]
+    Balance(crate::fungible::Balance),
+
+    #[lnp_api(type = 0xFF0F)]
+    Capabilities(crate::api::hello::Capabilities),
+
+    #[lnp_api(type = 0xFF10)]
+    Status(crate::api::status::Status),
+
+    #[lnp_api(type = 0xFF11)]
+    Merge(crate::api::stash::MergeRequest),
 }
 
 impl From<lnp::presentation::Error> for Reply {
@@ -102,61 +122,81 @@ pub struct SyncFormat(pub DataFormat, pub Vec<u8>);
 
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Error)]
 #[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct Transfer {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::strict_base64")
+    )]
     pub consignment: Consignment,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::psbt_base64")
+    )]
     pub psbt: Psbt,
 }
 
+/// Error reply. The `code` is one of the codes registered in
+/// [`ErrorCode`]; it is kept as a raw number so the clients can process
+/// replies from newer daemon versions with codes unknown to them.
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Error)]
 #[display(Debug)]
 #[non_exhaustive]
 pub struct Failure {
     pub code: u16,
     pub info: String,
+    /// Structured error parameters, which may be used to compose localized
+    /// error messages
+    pub details: BTreeMap<String, String>,
 }
 
-impl From<lnp::presentation::Error> for Failure {
-    fn from(err: lnp::presentation::Error) -> Self {
-        // TODO: Save error code taken from `Error::to_value()` after
-        //       implementation of `ToValue` trait and derive macro for enums
+impl Failure {
+    pub fn with_code(code: ErrorCode, info: String) -> Self {
         Failure {
-            code: 0,
-            info: format!("{}", err),
+            code: code.as_u16(),
+            info,
+            details: BTreeMap::new(),
         }
     }
+
+    /// Returns registered error code for the failure
+    pub fn error_code(&self) -> ErrorCode {
+        ErrorCode::from_u16(self.code)
+    }
+}
+
+impl From<lnp::presentation::Error> for Failure {
+    fn from(err: lnp::presentation::Error) -> Self {
+        Failure::with_code(ErrorCode::Presentation, format!("{}", err))
+    }
 }
 
 impl From<lnp::transport::Error> for Failure {
     fn from(err: lnp::transport::Error) -> Self {
-        // TODO: Save error code taken from `Error::to_value()` after
-        //       implementation of `ToValue` trait and derive macro for enums
-        Failure {
-            code: 1,
-            info: format!("{}", err),
-        }
+        Failure::with_code(ErrorCode::Transport, format!("{}", err))
     }
 }
 
 #[cfg(feature = "node")]
 impl From<RuntimeError> for Failure {
     fn from(err: RuntimeError) -> Self {
-        // TODO: Save error code taken from `Error::to_value()` after
-        //       implementation of `ToValue` trait and derive macro for enums
-        Failure {
-            code: 2,
-            info: format!("{}", err),
-        }
+        Failure::with_code(ErrorCode::Runtime, format!("{}", err))
     }
 }
 
 #[cfg(any(feature = "node", feature = "client"))]
 impl From<ServiceError> for Failure {
     fn from(err: ServiceError) -> Self {
-        // TODO: Save error code taken from `Error::to_value()` after
-        //       implementation of `ToValue` trait and derive macro for enums
+        let mut details = err.domain.details();
+        details.insert("service".to_string(), err.service.to_string());
         Failure {
-            code: 3,
+            code: err.domain.code().as_u16(),
             info: format!("{}", err),
+            details,
         }
     }
 }
@@ -0,0 +1,47 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use crate::auth::Role;
+
+/// Requests served by rgbd, which supervises the rest of the daemons
+#[derive(Clone, Debug, Display, LnpApi)]
+#[lnp_api(encoding = "strict")]
+#[display(Debug)]
+#[non_exhaustive]
+pub enum Request {
+    #[lnp_api(type = 0x0001)]
+    Hello(crate::api::hello::Hello),
+
+    #[lnp_api(type = 0x0005)]
+    Status(),
+}
+
+impl Request {
+    /// Names of all requests, as reported by [`Request::name`]
+    pub const NAMES: &'static [&'static str] = &["hello", "status"];
+
+    /// Request name used in logs and error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Hello(_) => "hello",
+            Request::Status() => "status",
+        }
+    }
+
+    /// Minimal client role required to call the request
+    pub fn required_role(&self) -> Role {
+        match self {
+            Request::Hello(_) | Request::Status() => Role::ReadOnly,
+        }
+    }
+}
@@ -17,12 +17,25 @@ use lnpbp::bitcoin::util::psbt::PartiallySignedTransaction as Psbt;
 use lnpbp::bitcoin::OutPoint;
 use lnpbp::bp::blind::{OutpointHash, OutpointReveal};
 use lnpbp::rgb::{Consignment, ContractId, NodeId, Transition};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Role;
 
 #[derive(Clone, Debug, Display, LnpApi)]
 #[lnp_api(encoding = "strict")]
 #[display(Debug)]
 #[non_exhaustive]
 pub enum Request {
+    #[lnp_api(type = 0x0001)]
+    Hello(crate::api::hello::Hello),
+
+    #[lnp_api(type = 0x0003)]
+    Shutdown(),
+
+    #[lnp_api(type = 0x0005)]
+    Status(),
+
     #[lnp_api(type = 0x0101)]
     AddSchema(::lnpbp::rgb::Schema),
 
@@ -48,29 +61,142 @@ pub enum Request {
     Consign(crate::api::stash::ConsignRequest),
 
     #[lnp_api(type = 0x0403)]
-    Validate(::lnpbp::rgb::Consignment),
+    Validate(crate::api::stash::ValidateRequest),
 
     #[lnp_api(type = 0x0405)]
     Merge(crate::api::stash::MergeRequest),
 
     #[lnp_api(type = 0x0407)]
     Forget(Vec<(::lnpbp::rgb::NodeId, u16)>),
+
+    #[lnp_api(type = 0x0409)]
+    ReadMerge(crate::api::event::MergeEvent),
+}
+
+impl Request {
+    /// Names of all requests, as reported by [`Request::name`]
+    pub const NAMES: &'static [&'static str] = &[
+        "hello",
+        "shutdown",
+        "status",
+        "add_schema",
+        "list_schemata",
+        "read_schema",
+        "add_genesis",
+        "list_geneses",
+        "read_genesis",
+        "read_transitions",
+        "consign",
+        "validate",
+        "merge",
+        "forget",
+        "read_merge",
+    ];
+
+    /// Request name used in logs and error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Hello(_) => "hello",
+            Request::Shutdown() => "shutdown",
+            Request::Status() => "status",
+            Request::AddSchema(_) => "add_schema",
+            Request::ListSchemata() => "list_schemata",
+            Request::ReadSchema(_) => "read_schema",
+            Request::AddGenesis(_) => "add_genesis",
+            Request::ListGeneses() => "list_geneses",
+            Request::ReadGenesis(_) => "read_genesis",
+            Request::ReadTransitions(_) => "read_transitions",
+            Request::Consign(_) => "consign",
+            Request::Validate(_) => "validate",
+            Request::Merge(_) => "merge",
+            Request::Forget(_) => "forget",
+            Request::ReadMerge(_) => "read_merge",
+        }
+    }
+
+    /// Minimal client role required to call the request
+    pub fn required_role(&self) -> Role {
+        match self {
+            Request::Shutdown()
+            | Request::AddSchema(_)
+            | Request::AddGenesis(_)
+            | Request::Forget(_) => Role::Admin,
+            // Merged data contains revealed seals of the recipient
+            Request::Consign(_)
+            | Request::Merge(_)
+            | Request::ReadMerge(_)
+            | Request::ReadTransitions(_) => Role::Transfer,
+            Request::Hello(_)
+            | Request::Status()
+            | Request::ListSchemata()
+            | Request::ReadSchema(_)
+            | Request::ListGeneses()
+            | Request::ReadGenesis(_)
+            | Request::Validate(_) => Role::ReadOnly,
+        }
+    }
 }
 
 #[derive(Clone, StrictEncode, StrictDecode, Debug, Display)]
 #[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
 pub struct ConsignRequest {
     pub contract_id: ContractId,
     pub inputs: Vec<OutPoint>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::rust::display_fromstr")
+    )]
     pub transition: Transition,
     pub other_transition_ids: BTreeMap<ContractId, NodeId>,
     pub outpoints: Vec<OutpointHash>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::psbt_base64")
+    )]
     pub psbt: Psbt,
 }
 
 #[derive(Clone, StrictEncode, StrictDecode, Debug, Display)]
 #[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct ValidateRequest {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::strict_base64")
+    )]
+    pub consignment: Consignment,
+    /// Number of confirmations witness transactions must have for the
+    /// consignment not to be reported as awaiting confirmation; stash
+    /// configuration is used if not given
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub min_confirmations: Option<u32>,
+}
+
+#[derive(Clone, StrictEncode, StrictDecode, Debug, Display)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
 pub struct MergeRequest {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::util::encoding::strict_base64")
+    )]
     pub consignment: Consignment,
     pub reveal_outpoints: Vec<OutpointReveal>,
+    /// Number of confirmations after which merged witness transactions are
+    /// reported as confirmed; stash configuration is used if not given
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub min_confirmations: Option<u32>,
 }
@@ -0,0 +1,319 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Daemon status and metrics, reported in reply to `status` request and
+//! exported in Prometheus text format.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::util::chain::Connectivity;
+
+/// Statistics of processing requests of a single type
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Display,
+    Default,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct RequestStats {
+    /// Number of processed requests
+    pub count: u64,
+    /// Number of requests which have failed
+    pub failures: u64,
+    /// Total time spent processing the requests, in microseconds
+    pub total_latency: u64,
+    /// Maximal time spent processing a single request, in microseconds
+    pub max_latency: u64,
+}
+
+impl RequestStats {
+    /// Accounts for the processed request
+    pub fn record(&mut self, latency: Duration, success: bool) {
+        let latency = latency.as_micros() as u64;
+        self.count += 1;
+        if !success {
+            self.failures += 1;
+        }
+        self.total_latency = self.total_latency.saturating_add(latency);
+        self.max_latency = self.max_latency.max(latency);
+    }
+}
+
+/// Life-cycle status of a daemon supervised by rgbd
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+pub enum DaemonStatus {
+    /// Daemon is launched, but is not yet ready to serve requests
+    #[display("starting")]
+    Starting,
+
+    /// Daemon is running
+    #[display("running")]
+    Running,
+
+    /// Daemon has exited and waits to be restarted
+    #[display("restarting")]
+    Restarting,
+
+    /// Daemon has exited and will not be restarted
+    #[display("stopped")]
+    Stopped,
+
+    /// Daemon has exceeded restart limit or failed to start
+    #[display("failed")]
+    Failed,
+}
+
+/// Health of a daemon supervised by rgbd
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct DaemonHealth {
+    pub status: DaemonStatus,
+    /// Number of restarts since the daemon was first launched
+    pub restarts: u32,
+    /// Error with which the daemon has failed last time
+    pub last_error: Option<String>,
+}
+
+impl Default for DaemonHealth {
+    fn default() -> Self {
+        Self {
+            status: DaemonStatus::Starting,
+            restarts: 0,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(
+    Clone, PartialEq, Eq, Debug, Display, Default, StrictEncode, StrictDecode,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct Status {
+    /// Daemon name and version
+    pub agent: String,
+    /// Time since the daemon has started, in seconds
+    pub uptime: u64,
+    /// Statistics of processed requests by request name
+    pub requests: BTreeMap<String, RequestStats>,
+    /// Number of objects of each kind kept in the storage
+    pub storage: BTreeMap<String, u64>,
+    /// Number of entries in the daemon cache or index
+    pub cache_size: u64,
+    /// Last error which has happened during request processing
+    pub last_error: Option<String>,
+    /// Connectivity of the chain data source (the configured transaction
+    /// resolvers) as of their last request, if the daemon uses one
+    pub chain: Option<Connectivity>,
+    /// Health of the daemons supervised by rgbd; empty for other daemons
+    pub daemons: BTreeMap<String, DaemonHealth>,
+}
+
+impl Status {
+    /// Renders status in Prometheus text exposition format, labelling all
+    /// metrics with the given daemon name
+    pub fn to_prometheus(&self, daemon: &str) -> String {
+        let mut out = String::new();
+        // Writing to string never fails
+        let _ = self.write_prometheus(daemon, &mut out);
+        out
+    }
+
+    fn write_prometheus(
+        &self,
+        daemon: &str,
+        out: &mut String,
+    ) -> std::fmt::Result {
+        let label = format!("daemon=\"{}\"", daemon);
+
+        metric_header(out, "rgb_uptime_seconds", "gauge", "Daemon uptime")?;
+        writeln!(out, "rgb_uptime_seconds{{{}}} {}", label, self.uptime)?;
+
+        metric_header(
+            out,
+            "rgb_requests_total",
+            "counter",
+            "Number of processed requests",
+        )?;
+        for (name, stats) in &self.requests {
+            writeln!(
+                out,
+                "rgb_requests_total{{{},request=\"{}\"}} {}",
+                label, name, stats.count
+            )?;
+        }
+        metric_header(
+            out,
+            "rgb_request_failures_total",
+            "counter",
+            "Number of failed requests",
+        )?;
+        for (name, stats) in &self.requests {
+            writeln!(
+                out,
+                "rgb_request_failures_total{{{},request=\"{}\"}} {}",
+                label, name, stats.failures
+            )?;
+        }
+        metric_header(
+            out,
+            "rgb_request_latency_seconds_total",
+            "counter",
+            "Total time spent processing requests",
+        )?;
+        for (name, stats) in &self.requests {
+            writeln!(
+                out,
+                "rgb_request_latency_seconds_total{{{},request=\"{}\"}} {}",
+                label,
+                name,
+                stats.total_latency as f64 / 1_000_000.0
+            )?;
+        }
+        metric_header(
+            out,
+            "rgb_request_latency_seconds_max",
+            "gauge",
+            "Maximal time spent processing a single request",
+        )?;
+        for (name, stats) in &self.requests {
+            writeln!(
+                out,
+                "rgb_request_latency_seconds_max{{{},request=\"{}\"}} {}",
+                label,
+                name,
+                stats.max_latency as f64 / 1_000_000.0
+            )?;
+        }
+
+        if !self.storage.is_empty() {
+            metric_header(
+                out,
+                "rgb_storage_objects",
+                "gauge",
+                "Number of objects in the storage",
+            )?;
+            for (kind, count) in &self.storage {
+                writeln!(
+                    out,
+                    "rgb_storage_objects{{{},kind=\"{}\"}} {}",
+                    label, kind, count
+                )?;
+            }
+        }
+
+        metric_header(
+            out,
+            "rgb_cache_entries",
+            "gauge",
+            "Number of entries in the cache",
+        )?;
+        writeln!(out, "rgb_cache_entries{{{}}} {}", label, self.cache_size)?;
+
+        if let Some(ref chain) = self.chain {
+            metric_header(
+                out,
+                "rgb_chain_connected",
+                "gauge",
+                "Whether chain data source is reachable",
+            )?;
+            writeln!(
+                out,
+                "rgb_chain_connected{{{}}} {}",
+                label,
+                (*chain == Connectivity::Connected) as u8
+            )?;
+        }
+
+        if !self.daemons.is_empty() {
+            metric_header(
+                out,
+                "rgb_daemon_up",
+                "gauge",
+                "Whether supervised daemon is running",
+            )?;
+            for (name, health) in &self.daemons {
+                writeln!(
+                    out,
+                    "rgb_daemon_up{{{},child=\"{}\"}} {}",
+                    label,
+                    name,
+                    (health.status == DaemonStatus::Running) as u8
+                )?;
+            }
+            metric_header(
+                out,
+                "rgb_daemon_restarts_total",
+                "counter",
+                "Number of supervised daemon restarts",
+            )?;
+            for (name, health) in &self.daemons {
+                writeln!(
+                    out,
+                    "rgb_daemon_restarts_total{{{},child=\"{}\"}} {}",
+                    label, name, health.restarts
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn metric_header(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+) -> std::fmt::Result {
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} {}", name, kind)
+}
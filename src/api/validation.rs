@@ -0,0 +1,386 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Wire representation of the consignment validation status.
+//!
+//! LNP/BP library does not provide strict encoding for
+//! [`validation::Status`], so we mirror it here with the types which can be
+//! transferred over RPC without losing the information required to tell
+//! one kind of validation problem from another.
+
+use lnpbp::bitcoin::Txid;
+use lnpbp::rgb::validation::{self, Validity};
+use lnpbp::rgb::{seal, NodeId};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::util::chain::TxStatus;
+
+/// Category of the validation failure
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    FromPrimitive,
+    ToPrimitive,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+#[repr(u8)]
+pub enum FailureKind {
+    /// Consignment data does not match the schema
+    Schema = 0,
+
+    /// State transition is absent, not anchored or does not close the seals
+    /// of its parents
+    Transition = 1,
+
+    /// State extension is absent or does not match its parent valencies
+    Extension = 2,
+
+    /// Witness transaction is not found or does not contain the commitment
+    Witness = 3,
+
+    /// Validation script has failed or is not supported
+    Script = 4,
+}
+impl_enum_strict_encoding!(FailureKind);
+
+/// Single validation failure which makes the consignment invalid
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// Node (genesis, transition or extension) where the failure has occurred,
+    /// if applicable
+    pub node_id: Option<NodeId>,
+    /// Witness transaction related to the failure, if applicable
+    pub txid: Option<Txid>,
+    /// Original failure details as reported by the validator
+    pub details: String,
+}
+
+impl From<validation::Failure> for Failure {
+    fn from(failure: validation::Failure) -> Self {
+        use validation::Failure::*;
+
+        let (kind, node_id, txid) = match failure {
+            TransitionAbsent(node_id)
+            | TransitionNotAnchored(node_id)
+            | TransitionNotInAnchor(node_id, _)
+            | TransitionParentWrongSealType { node_id, .. }
+            | TransitionParentWrongSeal { node_id, .. }
+            | TransitionParentConfidentialSeal { node_id, .. }
+            | TransitionParentIsNotWitnessInput { node_id, .. } => {
+                (FailureKind::Transition, Some(node_id), None)
+            }
+            ExtensionAbsent(node_id)
+            | ExtensionParentWrongValenciesType { node_id, .. } => {
+                (FailureKind::Extension, Some(node_id), None)
+            }
+            WitnessTransactionMissed(txid) => {
+                (FailureKind::Witness, None, Some(txid))
+            }
+            WitnessNoCommitment(node_id, _, txid) => {
+                (FailureKind::Witness, Some(node_id), Some(txid))
+            }
+            SimplicityIsNotSupportedYet => (FailureKind::Script, None, None),
+            ScriptFailure(node_id, _) => {
+                (FailureKind::Script, Some(node_id), None)
+            }
+            SchemaUnknownExtensionType(node_id, _)
+            | SchemaUnknownTransitionType(node_id, _)
+            | SchemaUnknownFieldType(node_id, _)
+            | SchemaUnknownOwnedRightType(node_id, _)
+            | SchemaUnknownPublicRightType(node_id, _)
+            | SchemaDeniedScriptExtension(node_id)
+            | SchemaMetaOccurencesError(node_id, ..)
+            | SchemaParentOwnedRightOccurencesError(node_id, ..)
+            | SchemaOwnedRightOccurencesError(node_id, ..) => {
+                (FailureKind::Schema, Some(node_id), None)
+            }
+            _ => (FailureKind::Schema, None, None),
+        };
+
+        Self {
+            kind,
+            node_id,
+            txid,
+            details: format!("{:?}", failure),
+        }
+    }
+}
+
+/// Validation warning, which does not make consignment invalid
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub enum Warning {
+    EndpointTransitionNotFound(NodeId),
+    EndpointDuplication(NodeId, seal::Confidential),
+    EndpointTransitionSealNotFound(NodeId, seal::Confidential),
+    ExcessiveTransition(NodeId),
+}
+
+impl From<validation::Warning> for Warning {
+    fn from(warning: validation::Warning) -> Self {
+        match warning {
+            validation::Warning::EndpointTransitionNotFound(node_id) => {
+                Warning::EndpointTransitionNotFound(node_id)
+            }
+            validation::Warning::EndpointDuplication(node_id, seal) => {
+                Warning::EndpointDuplication(node_id, seal)
+            }
+            validation::Warning::EndpointTransitionSealNotFound(
+                node_id,
+                seal,
+            ) => Warning::EndpointTransitionSealNotFound(node_id, seal),
+            validation::Warning::ExcessiveTransition(node_id) => {
+                Warning::ExcessiveTransition(node_id)
+            }
+        }
+    }
+}
+
+/// Additional information reported by the validator
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub enum Info {
+    /// State data of the node with the given index can't be checked since it
+    /// is concealed
+    UncheckableConfidentialStateData(NodeId, u64),
+}
+
+impl From<validation::Info> for Info {
+    fn from(info: validation::Info) -> Self {
+        match info {
+            validation::Info::UncheckableConfidentialStateData(
+                node_id,
+                index,
+            ) => Info::UncheckableConfidentialStateData(node_id, index as u64),
+        }
+    }
+}
+
+/// Witness transaction which has not yet reached the required number of
+/// confirmations
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct WitnessStatus {
+    pub txid: Txid,
+    pub status: TxStatus,
+}
+
+/// Full status of the consignment validation
+#[derive(
+    Clone, PartialEq, Eq, Debug, Display, Default, StrictEncode, StrictDecode,
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct ValidationStatus {
+    pub unresolved_txids: Vec<Txid>,
+    pub failures: Vec<Failure>,
+    pub warnings: Vec<Warning>,
+    pub info: Vec<Info>,
+    /// Witness transactions below the minimal confirmations policy; filled
+    /// by stash after the validation itself
+    pub awaiting_confirmation: Vec<WitnessStatus>,
+}
+
+impl ValidationStatus {
+    /// Returns the same validity as [`validation::Status::validity`] would
+    /// return for the original status
+    pub fn validity(&self) -> Validity {
+        if !self.failures.is_empty() {
+            Validity::Invalid
+        } else if !self.unresolved_txids.is_empty() {
+            Validity::UnresolvedTransactions
+        } else {
+            Validity::Valid
+        }
+    }
+
+    /// Checks whether all witness transactions have reached the minimal
+    /// confirmations policy the consignment was validated with
+    pub fn is_confirmed(&self) -> bool {
+        self.awaiting_confirmation.is_empty()
+    }
+}
+
+impl From<validation::Status> for ValidationStatus {
+    fn from(status: validation::Status) -> Self {
+        Self {
+            unresolved_txids: status.unresolved_txids,
+            failures: status.failures.into_iter().map(Failure::from).collect(),
+            warnings: status.warnings.into_iter().map(Warning::from).collect(),
+            info: status.info.into_iter().map(Info::from).collect(),
+            awaiting_confirmation: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lnpbp::bitcoin::hashes::Hash;
+    use lnpbp::rgb::AnchorId;
+
+    fn node_id(tag: &[u8]) -> NodeId {
+        NodeId::hash(tag)
+    }
+
+    fn txid(tag: &[u8]) -> Txid {
+        Txid::hash(tag)
+    }
+
+    #[test]
+    fn test_failure_kind_mapping() {
+        let node = node_id(b"node");
+        let ancestor = node_id(b"ancestor");
+        let witness = txid(b"witness");
+        let anchor = AnchorId::hash(b"anchor");
+
+        let cases = vec![
+            (
+                validation::Failure::TransitionAbsent(node),
+                FailureKind::Transition,
+                Some(node),
+                None,
+            ),
+            (
+                validation::Failure::TransitionNotInAnchor(node, anchor),
+                FailureKind::Transition,
+                Some(node),
+                None,
+            ),
+            (
+                validation::Failure::TransitionParentConfidentialSeal {
+                    node_id: node,
+                    ancestor_id: ancestor,
+                    assignment_type: 1,
+                    seal_index: 0,
+                },
+                FailureKind::Transition,
+                Some(node),
+                None,
+            ),
+            (
+                validation::Failure::ExtensionParentWrongValenciesType {
+                    node_id: node,
+                    ancestor_id: ancestor,
+                    valencies_type: 1,
+                },
+                FailureKind::Extension,
+                Some(node),
+                None,
+            ),
+            (
+                validation::Failure::WitnessTransactionMissed(witness),
+                FailureKind::Witness,
+                None,
+                Some(witness),
+            ),
+            (
+                validation::Failure::WitnessNoCommitment(node, anchor, witness),
+                FailureKind::Witness,
+                Some(node),
+                Some(witness),
+            ),
+            (
+                validation::Failure::SimplicityIsNotSupportedYet,
+                FailureKind::Script,
+                None,
+                None,
+            ),
+            (
+                validation::Failure::ScriptFailure(node, 1),
+                FailureKind::Script,
+                Some(node),
+                None,
+            ),
+            (
+                validation::Failure::SchemaUnknownFieldType(node, 1),
+                FailureKind::Schema,
+                Some(node),
+                None,
+            ),
+            (
+                validation::Failure::SchemaMismatchedDataType(1),
+                FailureKind::Schema,
+                None,
+                None,
+            ),
+        ];
+
+        for (original, kind, node_id, txid) in cases {
+            let details = format!("{:?}", original);
+            let failure = Failure::from(original);
+            assert_eq!(failure.kind, kind, "{}", details);
+            assert_eq!(failure.node_id, node_id, "{}", details);
+            assert_eq!(failure.txid, txid, "{}", details);
+            assert_eq!(failure.details, details);
+        }
+    }
+
+    #[test]
+    fn test_validity() {
+        let mut status = ValidationStatus::default();
+        assert_eq!(status.validity(), Validity::Valid);
+        assert!(status.is_confirmed());
+
+        status.unresolved_txids.push(txid(b"unresolved"));
+        assert_eq!(status.validity(), Validity::UnresolvedTransactions);
+
+        status.failures.push(Failure::from(
+            validation::Failure::WitnessTransactionMissed(txid(b"missed")),
+        ));
+        assert_eq!(status.validity(), Validity::Invalid);
+
+        status.awaiting_confirmation.push(WitnessStatus {
+            txid: txid(b"mempool"),
+            status: TxStatus::Mempool,
+        });
+        assert!(!status.is_confirmed());
+    }
+}
@@ -0,0 +1,668 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Encryption and authentication of the RPC and PUB/SUB connections with
+//! CurveZMQ.
+//!
+//! Services of a single node share the same Curve key pair, which is used both
+//! to encrypt the sockets they bind and to authenticate them when they connect
+//! to each other. Client public keys are checked against the allowlist of the
+//! service by the ZAP handler running in the background thread, so the
+//! allowlist must be given whenever the node keys are set.
+//!
+//! Each allowed client is assigned a [`Role`], limiting the set of the API
+//! requests it may call. Services check the role of the client sending the
+//! request before dispatching it.
+//!
+//! Allowlist entry may also bind the client to a single wallet; services of
+//! other wallets then do not accept its connections.
+
+use core::fmt::{self, Debug, Display, Formatter};
+use core::str::FromStr;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::thread;
+
+use lazy_static::lazy_static;
+use lnpbp::lnp::transport::zmqsocket::{ZmqSocketAddr, ZmqType};
+use lnpbp::lnp::{session, transport, PlainTranscoder, ZMQ_CONTEXT};
+
+use crate::error::{ApiErrorType, ServiceErrorDomain};
+use crate::util::WalletId;
+
+/// Endpoint at which ZMQ looks for the authentication handler
+const ZAP_ENDPOINT: &'static str = "inproc://zeromq.zap.01";
+
+/// Session over ZMQ socket, which may be encrypted with CurveZMQ on the socket
+/// level
+pub type ZmqSession =
+    session::Raw<PlainTranscoder, transport::zmqsocket::Connection>;
+
+type ZapAllowlists = HashMap<String, BTreeSet<CurvePublicKey>>;
+
+lazy_static! {
+    /// Client allowlists for each of the ZAP domains of the services running
+    /// within the process; `None` until the ZAP handler is started
+    static ref ZAP_ALLOWLISTS: Mutex<Option<ZapAllowlists>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum KeyParseError {
+    /// Curve key must be a valid Z85-encoded string
+    Encoding,
+
+    /// Curve key must be exactly 32 bytes (40 characters in Z85 encoding)
+    Length,
+
+    /// Both public and secret Curve keys must be provided
+    IncompleteKeyPair,
+
+    /// Unknown client role; must be one of `read-only`, `transfer` or `admin`
+    UnknownRole,
+
+    /// Invalid wallet identifier in the client allowlist entry
+    InvalidWallet,
+
+    /// Client allowlist (`--curve-clients`) must be given when the node Curve
+    /// keys are set
+    EmptyAllowlist,
+}
+
+fn decode_key(s: &str) -> Result<[u8; 32], KeyParseError> {
+    let data = zmq::z85_decode(s).map_err(|_| KeyParseError::Encoding)?;
+    if data.len() != 32 {
+        Err(KeyParseError::Length)?
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&data);
+    Ok(key)
+}
+
+fn encode_key(key: &[u8; 32]) -> String {
+    zmq::z85_encode(key).expect("32-byte keys are always Z85-encodable")
+}
+
+/// Client role defining which API requests the client is allowed to call.
+/// Roles are ordered, and each role permits all the requests permitted by the
+/// previous roles.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum Role {
+    /// May only read the data and validate consignments
+    #[display("read-only")]
+    ReadOnly,
+
+    /// May also prepare and accept transfers
+    #[display("transfer")]
+    Transfer,
+
+    /// May call any request, including issuing, importing and forgetting
+    /// assets
+    #[display("admin")]
+    Admin,
+}
+
+impl Role {
+    /// Checks that the role permits requests requiring `required` role
+    pub fn permits(&self, required: Role) -> bool {
+        *self >= required
+    }
+
+    /// Returns error if the role does not permit requests requiring
+    /// `required` role
+    pub fn check(
+        &self,
+        required: Role,
+        request: &str,
+    ) -> Result<(), ServiceErrorDomain> {
+        if self.permits(required) {
+            return Ok(());
+        }
+        Err(ServiceErrorDomain::Api(ApiErrorType::PermissionDenied {
+            request: request.to_string(),
+            role: self.to_string(),
+        }))
+    }
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::Admin
+    }
+}
+
+impl FromStr for Role {
+    type Err = KeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "read-only" | "readonly" | "read" => Role::ReadOnly,
+            "transfer" => Role::Transfer,
+            "admin" => Role::Admin,
+            _ => Err(KeyParseError::UnknownRole)?,
+        })
+    }
+}
+
+/// Curve25519 public key, which identifies service or client
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CurvePublicKey([u8; 32]);
+
+impl CurvePublicKey {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl FromStr for CurvePublicKey {
+    type Err = KeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(decode_key(s)?))
+    }
+}
+
+impl Display for CurvePublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_key(&self.0))
+    }
+}
+
+impl Debug for CurvePublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "CurvePublicKey({})", self)
+    }
+}
+
+/// Curve25519 secret key. Its debug representation does not reveal the key
+/// data, so configurations containing it can be logged safely.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CurveSecretKey([u8; 32]);
+
+impl CurveSecretKey {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl FromStr for CurveSecretKey {
+    type Err = KeyParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(decode_key(s)?))
+    }
+}
+
+impl Display for CurveSecretKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_key(&self.0))
+    }
+}
+
+impl Debug for CurveSecretKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("CurveSecretKey(..)")
+    }
+}
+
+/// Curve key pair used to encrypt and authenticate connections
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct CurveKeys {
+    pub public: CurvePublicKey,
+    pub secret: CurveSecretKey,
+}
+
+impl CurveKeys {
+    /// Generates new random key pair
+    pub fn generate() -> Result<Self, transport::Error> {
+        let pair = zmq::CurveKeyPair::new()?;
+        Ok(Self {
+            public: CurvePublicKey(pair.public_key),
+            secret: CurveSecretKey(pair.secret_key),
+        })
+    }
+
+    /// Parses optional pair of Z85-encoded keys as they are provided in
+    /// command-line arguments or environment. Returns `None` if neither of the
+    /// keys is given.
+    pub fn from_opts(
+        public: Option<String>,
+        secret: Option<String>,
+    ) -> Result<Option<Self>, KeyParseError> {
+        match (public, secret) {
+            (None, None) => Ok(None),
+            (Some(public), Some(secret)) => Ok(Some(Self {
+                public: public.parse()?,
+                secret: secret.parse()?,
+            })),
+            _ => Err(KeyParseError::IncompleteKeyPair),
+        }
+    }
+}
+
+/// Authentication settings for the sockets bound by a service
+#[derive(Clone, PartialEq, Eq, Debug, Display, Default)]
+#[display(Debug)]
+pub struct ServerAuth {
+    /// Node keys; if absent, connections are not encrypted
+    pub keys: Option<CurveKeys>,
+
+    /// Public keys of the clients allowed to connect with their roles. Other
+    /// services of the node, which use the node keys, are always accepted.
+    pub allowed_clients: BTreeMap<CurvePublicKey, Role>,
+}
+
+impl ServerAuth {
+    /// Constructs settings from Z85-encoded node keys and client allowlist
+    /// for the services of the given wallet. Allowlist entries are
+    /// Z85-encoded client public keys optionally followed by a colon and
+    /// client role, and by `@` and wallet identifier, like
+    /// `<key>:read-only@alice`; clients without explicit role are given admin
+    /// rights, and clients without explicit wallet may access any wallet.
+    /// Allowlist must not be empty if the node keys are given.
+    pub fn from_opts(
+        public: Option<String>,
+        secret: Option<String>,
+        allowed_clients: Vec<String>,
+        wallet: &WalletId,
+    ) -> Result<Self, KeyParseError> {
+        let keys = CurveKeys::from_opts(public, secret)?;
+        if keys.is_some() && allowed_clients.is_empty() {
+            Err(KeyParseError::EmptyAllowlist)?
+        }
+        let allowed_clients = allowed_clients
+            .iter()
+            .map(|entry| parse_client_entry(entry))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|(_, _, bound)| {
+                bound.as_ref().map(|bound| bound == wallet).unwrap_or(true)
+            })
+            .map(|(key, role, _)| (key, role))
+            .collect();
+        Ok(Self {
+            keys,
+            allowed_clients,
+        })
+    }
+
+    /// Constructs settings from Z85-encoded node keys, accepting connections
+    /// only from the other services of the same node
+    pub fn node_only(
+        public: Option<String>,
+        secret: Option<String>,
+    ) -> Result<Self, KeyParseError> {
+        Ok(Self {
+            keys: CurveKeys::from_opts(public, secret)?,
+            allowed_clients: bmap! {},
+        })
+    }
+
+    /// Binds socket of the given type to the endpoint, enabling CurveZMQ if
+    /// the node keys are configured. The `domain` is used to tell the
+    /// allowlists of different services running within the same process.
+    pub fn bind(
+        &self,
+        zmq_type: ZmqType,
+        endpoint: &ZmqSocketAddr,
+        domain: &str,
+    ) -> Result<ZmqSession, transport::Error> {
+        let keys = match self.keys {
+            None => {
+                return session::Raw::with_zmq_unencrypted(
+                    zmq_type, endpoint, None, None,
+                )
+            }
+            Some(keys) => keys,
+        };
+
+        let mut allowlist: BTreeSet<_> =
+            self.allowed_clients.keys().copied().collect();
+        // Other services of the same node connect with the node keys
+        allowlist.insert(keys.public);
+        register_zap_domain(domain, allowlist)?;
+
+        let socket = ZMQ_CONTEXT.socket(zmq_type.socket_type())?;
+        socket.set_zap_domain(domain)?;
+        socket.set_curve_server(true)?;
+        socket.set_curve_secretkey(keys.secret.as_bytes())?;
+        open_socket(zmq_type, socket, endpoint)
+    }
+
+    /// Returns role of the client with the given key. Connections which are
+    /// not encrypted, as well as other services of the same node are not
+    /// restricted.
+    pub fn role(&self, client: Option<&CurvePublicKey>) -> Role {
+        let (keys, client) = match (self.keys, client) {
+            (Some(keys), Some(client)) => (keys, client),
+            _ => return Role::Admin,
+        };
+        if *client == keys.public {
+            return Role::Admin;
+        }
+        self.allowed_clients
+            .get(client)
+            .copied()
+            // ZAP handler does not accept clients outside of the allowlist, so
+            // this should never happen
+            .unwrap_or(Role::ReadOnly)
+    }
+
+    /// Returns client settings which can be used by the service to connect to
+    /// the other services of the same node
+    pub fn client(&self) -> ClientAuth {
+        ClientAuth {
+            node_key: self.keys.map(|keys| keys.public),
+            keys: self.keys,
+        }
+    }
+}
+
+/// Authentication settings for the sockets connecting to node services
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display, Default)]
+#[display(Debug)]
+pub struct ClientAuth {
+    /// Public key of the node; if absent, connections are not encrypted
+    pub node_key: Option<CurvePublicKey>,
+
+    /// Client keys; if absent while the node key is known, ephemeral keys are
+    /// generated for each connection
+    pub keys: Option<CurveKeys>,
+}
+
+impl ClientAuth {
+    /// Constructs settings from Z85-encoded node public key and client keys
+    pub fn from_opts(
+        node_key: Option<String>,
+        public: Option<String>,
+        secret: Option<String>,
+    ) -> Result<Self, KeyParseError> {
+        Ok(Self {
+            node_key: node_key.map(|key| key.parse()).transpose()?,
+            keys: CurveKeys::from_opts(public, secret)?,
+        })
+    }
+
+    /// Connects socket of the given type to the endpoint, enabling CurveZMQ
+    /// if the node key is configured
+    pub fn connect(
+        &self,
+        zmq_type: ZmqType,
+        endpoint: &ZmqSocketAddr,
+    ) -> Result<ZmqSession, transport::Error> {
+        let node_key = match self.node_key {
+            None => {
+                return session::Raw::with_zmq_unencrypted(
+                    zmq_type, endpoint, None, None,
+                )
+            }
+            Some(node_key) => node_key,
+        };
+        let keys = match self.keys {
+            Some(keys) => keys,
+            None => CurveKeys::generate()?,
+        };
+
+        let socket = ZMQ_CONTEXT.socket(zmq_type.socket_type())?;
+        socket.set_curve_serverkey(node_key.as_bytes())?;
+        socket.set_curve_publickey(keys.public.as_bytes())?;
+        socket.set_curve_secretkey(keys.secret.as_bytes())?;
+        open_socket(zmq_type, socket, endpoint)
+    }
+}
+
+/// Receives raw message from the session together with the public key of the
+/// client which has sent it, which is known only for sessions encrypted with
+/// CurveZMQ
+pub fn recv_authenticated(
+    session: &mut ZmqSession,
+) -> Result<(Vec<u8>, Option<CurvePublicKey>), transport::Error> {
+    let mut msg = session.as_socket().recv_msg(0)?;
+    let client = msg
+        .gets("User-Id")
+        .and_then(|user_id| CurvePublicKey::from_str(user_id).ok());
+    Ok((msg.to_vec(), client))
+}
+
+fn parse_client_entry(
+    entry: &str,
+) -> Result<(CurvePublicKey, Role, Option<WalletId>), KeyParseError> {
+    // Z85 alphabet contains colon and `@`, so we rely on the fixed key length
+    // to separate the key from the role and wallet
+    if entry.len() <= 40 {
+        return Ok((entry.parse()?, Role::Admin, None));
+    }
+    if !entry.is_char_boundary(40) {
+        Err(KeyParseError::Length)?
+    }
+    let (key, rest) = entry.split_at(40);
+    let (role, wallet) = match rest.find('@') {
+        Some(pos) => (&rest[..pos], Some(&rest[pos + 1..])),
+        None => (rest, None),
+    };
+    let role = match role {
+        "" => Role::Admin,
+        role if role.starts_with(':') => role[1..].parse()?,
+        _ => Err(KeyParseError::Length)?,
+    };
+    let wallet = wallet
+        .map(|wallet| wallet.parse())
+        .transpose()
+        .map_err(|_| KeyParseError::InvalidWallet)?;
+    Ok((key.parse()?, role, wallet))
+}
+
+fn open_socket(
+    zmq_type: ZmqType,
+    socket: zmq::Socket,
+    endpoint: &ZmqSocketAddr,
+) -> Result<ZmqSession, transport::Error> {
+    let endpoint = endpoint.zmq_socket_string();
+    match zmq_type {
+        ZmqType::Pull | ZmqType::Rep | ZmqType::Pub | ZmqType::RouterBind => {
+            socket.bind(&endpoint)?
+        }
+        _ => socket.connect(&endpoint)?,
+    }
+    Ok(session::Raw::from_zmq_socket_unencrypted(zmq_type, socket))
+}
+
+fn register_zap_domain(
+    domain: &str,
+    allowlist: BTreeSet<CurvePublicKey>,
+) -> Result<(), transport::Error> {
+    let mut allowlists = ZAP_ALLOWLISTS
+        .lock()
+        .expect("ZAP allowlist lock is poisoned");
+    if allowlists.is_none() {
+        // ZAP handler must be bound before any of the Curve server sockets
+        let socket = ZMQ_CONTEXT.socket(zmq::REP)?;
+        socket.bind(ZAP_ENDPOINT)?;
+        thread::spawn(move || run_zap_handler(socket));
+        *allowlists = Some(HashMap::new());
+    }
+    allowlists
+        .as_mut()
+        .expect("ZAP allowlists are initialized above")
+        .insert(domain.to_string(), allowlist);
+    Ok(())
+}
+
+fn run_zap_handler(socket: zmq::Socket) {
+    loop {
+        let request = match socket.recv_multipart(0) {
+            Ok(request) => request,
+            Err(err) => {
+                error!("Error receiving ZAP request: {}", err);
+                continue;
+            }
+        };
+        if let Err(err) = socket.send_multipart(zap_reply(&request), 0) {
+            error!("Error sending ZAP reply: {}", err);
+        }
+    }
+}
+
+/// Processes ZAP request, which consists of version, request id, domain,
+/// address, identity, mechanism and credentials frames. For the accepted
+/// clients the reply carries client public key as the user id, which is
+/// available in the metadata of all messages received from the client.
+fn zap_reply(request: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let request_id = request.get(1).cloned().unwrap_or_default();
+    let (code, text, user_id) =
+        match (request.get(2), request.get(5), request.get(6)) {
+            (Some(domain), Some(mechanism), Some(credentials))
+                if mechanism.as_slice() == b"CURVE"
+                    && credentials.len() == 32 =>
+            {
+                let mut key = [0u8; 32];
+                key.copy_from_slice(credentials);
+                let key = CurvePublicKey(key);
+                let domain = String::from_utf8_lossy(domain);
+                let allowed = ZAP_ALLOWLISTS
+                    .lock()
+                    .expect("ZAP allowlist lock is poisoned")
+                    .as_ref()
+                    .and_then(|allowlists| allowlists.get(domain.as_ref()))
+                    .map(|allowlist| allowlist.contains(&key))
+                    .unwrap_or(false);
+                if allowed {
+                    debug!("Client {} is authenticated for {}", key, domain);
+                    ("200", "OK", key.to_string())
+                } else {
+                    warn!("Client {} is not allowed for {}", key, domain);
+                    ("400", "Client key is not allowed", s!(""))
+                }
+            }
+            _ => ("400", "Unsupported authentication mechanism", s!("")),
+        };
+    vec![
+        b"1.0".to_vec(),
+        request_id,
+        code.as_bytes().to_vec(),
+        text.as_bytes().to_vec(),
+        user_id.into_bytes(),
+        vec![],
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        encode_key(&[byte; 32])
+    }
+
+    #[test]
+    fn test_parse_client_entry() {
+        let plain = key(1);
+        // Z85 alphabet contains both separators, so keys may consist of them
+        let colons = ":".repeat(40);
+        let ats = "@".repeat(40);
+        let alice: WalletId = "alice".parse().unwrap();
+
+        assert_eq!(
+            parse_client_entry(&plain),
+            Ok((plain.parse().unwrap(), Role::Admin, None))
+        );
+        assert_eq!(
+            parse_client_entry(&colons),
+            Ok((colons.parse().unwrap(), Role::Admin, None))
+        );
+        assert_eq!(
+            parse_client_entry(&ats),
+            Ok((ats.parse().unwrap(), Role::Admin, None))
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}:transfer", colons)),
+            Ok((colons.parse().unwrap(), Role::Transfer, None))
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}@alice", ats)),
+            Ok((ats.parse().unwrap(), Role::Admin, Some(alice.clone())))
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}:read-only@alice", ats)),
+            Ok((ats.parse().unwrap(), Role::ReadOnly, Some(alice)))
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}:admin", plain)),
+            Ok((plain.parse().unwrap(), Role::Admin, None))
+        );
+
+        assert_eq!(
+            parse_client_entry(&format!("{}:owner", plain)),
+            Err(KeyParseError::UnknownRole)
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}transfer", plain)),
+            Err(KeyParseError::Length)
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}:transfer@", plain)),
+            Err(KeyParseError::InvalidWallet)
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}@al@ice", plain)),
+            Err(KeyParseError::InvalidWallet)
+        );
+        assert_eq!(
+            parse_client_entry(&format!("{}é:admin", &plain[..39])),
+            Err(KeyParseError::Length)
+        );
+        assert!(parse_client_entry(&plain[..35]).is_err());
+        assert!(parse_client_entry("").is_err());
+    }
+
+    #[test]
+    fn test_allowlist_required() {
+        assert_eq!(
+            ServerAuth::from_opts(
+                Some(key(1)),
+                Some(key(2)),
+                vec![],
+                &WalletId::default()
+            ),
+            Err(KeyParseError::EmptyAllowlist)
+        );
+        let auth =
+            ServerAuth::from_opts(None, None, vec![], &WalletId::default())
+                .unwrap();
+        assert_eq!(auth.keys, None);
+        assert_eq!(auth.role(None), Role::Admin);
+
+        let auth = ServerAuth::node_only(Some(key(1)), Some(key(2))).unwrap();
+        let node: CurvePublicKey = key(1).parse().unwrap();
+        let client: CurvePublicKey = key(3).parse().unwrap();
+        assert_eq!(auth.role(Some(&node)), Role::Admin);
+        assert_eq!(auth.role(Some(&client)), Role::ReadOnly);
+    }
+
+    #[test]
+    fn test_allowlist_wallet_binding() {
+        let alice: WalletId = "alice".parse().unwrap();
+        let auth = ServerAuth::from_opts(
+            Some(key(1)),
+            Some(key(2)),
+            vec![format!("{}:transfer@bob", key(3))],
+            &alice,
+        )
+        .unwrap();
+        // Allowlist given for another wallet accepts only the node services
+        assert!(auth.allowed_clients.is_empty());
+        let client: CurvePublicKey = key(3).parse().unwrap();
+        assert_eq!(auth.role(Some(&client)), Role::ReadOnly);
+    }
+}
@@ -11,18 +11,24 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
-use clap::Clap;
+use core::convert::TryFrom;
 use log::*;
-use std::env;
+use std::{env, process};
 
 use rgb::cli::{Config, Opts, Runtime};
 use rgb::error::BootstrapError;
+use rgb::util::config::LayeredOpts;
 
 #[tokio::main]
 async fn main() -> Result<(), BootstrapError> {
-    // TODO: Parse config file as well
-    let opts: Opts = Opts::parse();
-    let config: Config = opts.clone().into();
+    let (opts, config) = Opts::parse_layered()
+        .and_then(|opts| {
+            Config::try_from(opts.clone()).map(|config| (opts, config))
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Configuration error: {}", err);
+            process::exit(1)
+        });
 
     if env::var("RUST_LOG").is_err() {
         env::set_var(
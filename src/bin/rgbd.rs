@@ -11,17 +11,22 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
-use clap::Clap;
+use core::convert::TryFrom;
 use log::LevelFilter;
-use std::env;
+use std::{env, process};
 
 use rgb::error::BootstrapError;
 use rgb::rgbd::{main_with_config, Config, Opts};
+use rgb::util::config::LayeredOpts;
 
 #[tokio::main]
 async fn main() -> Result<(), BootstrapError> {
-    let opts: Opts = Opts::parse();
-    let config: Config = opts.into();
+    let config = Opts::parse_layered()
+        .and_then(Config::try_from)
+        .unwrap_or_else(|err| {
+            eprintln!("Configuration error: {}", err);
+            process::exit(1)
+        });
 
     if env::var("RUST_LOG").is_err() {
         env::set_var(
@@ -11,6 +11,7 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+use core::convert::TryFrom;
 use core::fmt::Display;
 use core::str::FromStr;
 use std::path::PathBuf;
@@ -18,8 +19,14 @@ use std::path::PathBuf;
 use lnpbp::bp;
 use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
 
-use super::{fungible, stash, Error, Runtime};
+use super::{event, fungible, stash, Error, Runtime};
+use crate::api::Reply;
+use crate::auth::{ClientAuth, CurveKeys};
 use crate::constants::*;
+use crate::error::{ConfigError, ServiceErrorDomain};
+use crate::util::config::LayeredOpts;
+use crate::util::file::utf8_path;
+use crate::util::WalletId;
 
 #[derive(Clap, Clone, Debug, Display)]
 #[display(Debug)]
@@ -38,21 +45,68 @@ pub struct Opts {
     #[clap(short, long, default_value = RGB_DATA_DIR, env = "RGB_DATA_DIR")]
     pub data_dir: String,
 
+    /// Path to the configuration file; if not given, `rgb.toml` from the data
+    /// directory is used when present
+    #[clap(long, env = "RGB_CONFIG")]
+    pub config: Option<String>,
+
     /// RPC endpoint of contracts service
-    #[clap(short, long, default_value = FUNGIBLED_RPC_ENDPOINT)]
+    #[clap(
+        short,
+        long,
+        default_value = FUNGIBLED_RPC_ENDPOINT,
+        env = "RGB_FUNGIBLED_RPC"
+    )]
     pub fungible_endpoint: String,
 
     /// RPC endpoint of contracts service
-    #[clap(short, long, default_value = STASHD_RPC_ENDPOINT)]
+    #[clap(
+        short,
+        long,
+        default_value = STASHD_RPC_ENDPOINT,
+        env = "RGB_STASHD_RPC"
+    )]
     pub stash_endpoint: String,
 
+    /// Publish-subscribe endpoint of contracts service
+    #[clap(
+        long,
+        default_value = FUNGIBLED_PUB_ENDPOINT,
+        env = "RGB_FUNGIBLED_PUB"
+    )]
+    pub fungible_pub: String,
+
+    /// Publish-subscribe endpoint of stash service
+    #[clap(long, default_value = STASHD_PUB_ENDPOINT, env = "RGB_STASHD_PUB")]
+    pub stash_pub: String,
+
+    /// Z85-encoded public key of the node; if given, connections are
+    /// encrypted with CurveZMQ
+    #[clap(long, env = "RGB_NODE_KEY")]
+    pub node_key: Option<String>,
+
+    /// Z85-encoded public key of the client; if not given while the node key
+    /// is known, ephemeral client keys are used
+    #[clap(long, env = "RGB_CLI_CURVE_PUBLIC")]
+    pub curve_public: Option<String>,
+
+    /// Z85-encoded secret key of the client matching `--curve-public`
+    #[clap(long, env = "RGB_CLI_CURVE_SECRET", hide_env_values = true)]
+    pub curve_secret: Option<String>,
+
     /// Command to execute
     #[clap(subcommand)]
     pub command: Command,
 
-    /// Bitcoin network to use
+    /// Bitcoin network to use; selects the daemons serving it if rgbd runs
+    /// several networks
     #[clap(short, long, default_value = RGB_NETWORK, env = "RGB_NETWORK")]
     pub network: bp::Chain,
+
+    /// Wallet to work with; selects the daemons serving it if rgbd runs
+    /// several wallets
+    #[clap(long, default_value = RGB_WALLET, env = "RGB_WALLET")]
+    pub wallet: WalletId,
 }
 
 #[derive(Clap, Clone, Debug, Display)]
@@ -76,6 +130,16 @@ pub enum Command {
         #[clap(subcommand)]
         subcommand: fungible::Command,
     },
+
+    /// Prints events published by stash and contracts services as they
+    /// happen
+    Watch,
+
+    /// Generates new key pair for CurveZMQ encryption of node connections
+    Keygen,
+
+    /// Shuts down node daemons, letting them flush their state to disk
+    Shutdown,
 }
 
 // We need config structure since not all of the parameters can be specified
@@ -88,20 +152,59 @@ pub struct Config {
     pub data_dir: PathBuf,
     pub fungible_endpoint: ZmqSocketAddr,
     pub stash_endpoint: ZmqSocketAddr,
+    pub fungible_pub: ZmqSocketAddr,
+    pub stash_pub: ZmqSocketAddr,
     pub network: bp::Chain,
+    pub wallet: WalletId,
+    pub auth: ClientAuth,
 }
 
-impl From<Opts> for Config {
-    fn from(opts: Opts) -> Self {
+impl LayeredOpts for Opts {
+    const FILE_PARAMS: &'static [(&'static str, &'static str)] = &[
+        ("data-dir", "data-dir"),
+        ("network", "network"),
+        ("wallet", "wallet"),
+        ("node-key", "node-key"),
+        ("cli.curve-public", "curve-public"),
+        ("cli.curve-secret", "curve-secret"),
+        ("fungibled.rpc", "fungible-endpoint"),
+        ("stashd.rpc", "stash-endpoint"),
+        ("fungibled.pub", "fungible-pub"),
+        ("stashd.pub", "stash-pub"),
+    ];
+
+    fn config_file(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    fn data_dir(&self) -> &str {
+        &self.data_dir
+    }
+}
+
+impl TryFrom<Opts> for Config {
+    type Error = ConfigError;
+
+    fn try_from(opts: Opts) -> Result<Self, Self::Error> {
         let mut me = Self {
             verbose: opts.verbose,
             network: opts.network,
+            wallet: opts.wallet,
             ..Config::default()
         };
-        me.data_dir = me.parse_param(opts.data_dir);
-        me.fungible_endpoint = me.parse_param(opts.fungible_endpoint);
-        me.stash_endpoint = me.parse_param(opts.stash_endpoint);
-        me
+        me.data_dir = me.parse_param("data-dir", opts.data_dir)?;
+        me.fungible_endpoint =
+            me.parse_param("fungible-endpoint", opts.fungible_endpoint)?;
+        me.stash_endpoint =
+            me.parse_param("stash-endpoint", opts.stash_endpoint)?;
+        me.fungible_pub = me.parse_param("fungible-pub", opts.fungible_pub)?;
+        me.stash_pub = me.parse_param("stash-pub", opts.stash_pub)?;
+        me.auth = ClientAuth::from_opts(
+            opts.node_key,
+            opts.curve_public,
+            opts.curve_secret,
+        )?;
+        Ok(me)
     }
 }
 
@@ -118,9 +221,17 @@ impl Default for Config {
             stash_endpoint: STASHD_RPC_ENDPOINT
                 .parse()
                 .expect("Broken STASHD_RPC_ENDPOINT value"),
+            fungible_pub: FUNGIBLED_PUB_ENDPOINT
+                .parse()
+                .expect("Broken FUNGIBLED_PUB_ENDPOINT value"),
+            stash_pub: STASHD_PUB_ENDPOINT
+                .parse()
+                .expect("Broken STASHD_PUB_ENDPOINT value"),
             network: RGB_NETWORK
                 .parse()
                 .expect("Error in RGB_NETWORK constant value"),
+            wallet: WalletId::default(),
+            auth: ClientAuth::default(),
         }
     }
 }
@@ -131,22 +242,55 @@ impl Command {
             Command::Fungible { subcommand } => subcommand.exec(runtime),
             Command::Schema { subcommand } => subcommand.exec(runtime),
             Command::Genesis { subcommand } => subcommand.exec(runtime),
+            Command::Watch => event::exec_watch(runtime),
+            Command::Keygen => exec_keygen(),
+            Command::Shutdown => exec_shutdown(runtime),
         }
     }
 }
 
 impl Config {
-    pub fn parse_param<T>(&self, param: String) -> T
+    /// Parses value of the parameter with a given name, replacing
+    /// placeholders with the actual values
+    pub fn parse_param<T>(
+        &self,
+        name: &str,
+        param: String,
+    ) -> Result<T, ConfigError>
     where
         T: FromStr,
         T::Err: Display,
     {
         param
+            .replace("{id}", self.wallet.as_str())
             .replace("{network}", &self.network.to_string())
-            .replace("{data_dir}", self.data_dir.to_str().unwrap())
+            .replace("{data_dir}", utf8_path("data-dir", &self.data_dir)?)
             .parse()
-            .unwrap_or_else(|err| {
-                panic!("Error parsing parameter `{}`: {}", param, err)
+            .map_err(|err: T::Err| ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: param.clone(),
+                details: err.to_string(),
             })
     }
 }
+
+fn exec_keygen() -> Result<(), Error> {
+    let keys = CurveKeys::generate().map_err(ServiceErrorDomain::from)?;
+    println!("Public key: {}", keys.public);
+    println!("Secret key: {}", keys.secret);
+    Ok(())
+}
+
+fn exec_shutdown(mut runtime: Runtime) -> Result<(), Error> {
+    for (daemon, reply) in runtime.shutdown()? {
+        match reply.as_deref() {
+            Some(Reply::Success) => println!("{} is shutting down", daemon),
+            Some(Reply::Failure(failure)) => {
+                eprintln!("{} returned error: {}", daemon, failure)
+            }
+            Some(_) => Err(Error::DataInconsistency)?,
+            None => println!("{} is not running", daemon),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use super::{Error, Runtime};
+use crate::api::Event;
+
+pub fn exec_watch(mut runtime: Runtime) -> Result<(), Error> {
+    info!("Watching for stash and contract events...");
+
+    runtime.subscribe()?;
+    loop {
+        match &*runtime.next_event()? {
+            Event::SchemaAdded(schema_id) => {
+                println!("Schema added: {}", schema_id)
+            }
+            Event::AssetImported(contract_id) => {
+                println!("Asset imported: {}", contract_id)
+            }
+            Event::AllocationAdded(allocation) => println!(
+                "Allocation added: {} of asset {} at {} (assignment {}:{})",
+                allocation.amount,
+                allocation.contract_id,
+                allocation.outpoint,
+                allocation.node_id,
+                allocation.index
+            ),
+            Event::AllocationRemoved(allocation) => println!(
+                "Allocation removed: {} of asset {} at {} (assignment {}:{})",
+                allocation.amount,
+                allocation.contract_id,
+                allocation.outpoint,
+                allocation.node_id,
+                allocation.index
+            ),
+            Event::ConsignmentMerged(merge) => println!(
+                "Consignment merged: contract {}, {} state node(s)",
+                merge.contract_id,
+                merge.node_ids.len()
+            ),
+            Event::ForgetRequested(assignments) => println!(
+                "Requested to forget {} assignment(s)",
+                assignments.len()
+            ),
+            Event::ValidationFinished(validation) => println!(
+                "Validation finished: contract {} is {}",
+                validation.contract_id,
+                validation.status.validity()
+            ),
+            Event::WitnessConfirmed(witness) => println!(
+                "Witness transaction {} confirmed: {}, {} contract(s)",
+                witness.txid,
+                witness.status,
+                witness.contract_ids.len()
+            ),
+            Event::AllocationSpent(spend) => println!(
+                "{} spent: contract {}, outpoint {}, amount {} by {}",
+                if spend.known {
+                    "Allocation"
+                } else {
+                    "WARNING: allocation unexpectedly"
+                },
+                spend.allocation.contract_id,
+                spend.allocation.outpoint,
+                spend.allocation.amount,
+                spend.spending_txid
+            ),
+            Event::WitnessReorged(witness) => println!(
+                "Witness transaction {} reorged out: {}, {} contract(s)",
+                witness.txid,
+                witness.status,
+                witness.contract_ids.len()
+            ),
+            Event::WitnessConflicted(witness) => println!(
+                "Witness transaction {} double-spent, {} contract(s)",
+                witness.txid,
+                witness.contract_ids.len()
+            ),
+            Event::AllocationRolledBack(rollback) => println!(
+                "Allocation rolled back: contract {}, outpoint {}, amount {}, \
+                 witness {} is {}",
+                rollback.allocation.contract_id,
+                rollback.allocation.outpoint,
+                rollback.allocation.amount,
+                rollback.txid,
+                rollback.status
+            ),
+        }
+    }
+}
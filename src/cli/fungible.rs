@@ -27,6 +27,7 @@ use lnpbp::strict_encoding::strict_encode;
 use super::{Error, OutputFormat, Runtime};
 use crate::api::fungible::{AcceptApi, Issue, TransferApi};
 use crate::api::{reply, Reply};
+use crate::error::ServiceErrorDomain;
 use crate::fungible::{Asset, Invoice, Outcoincealed, Outcoins, Outpoint};
 use crate::util::file::ReadWrite;
 use crate::DataFormat;
@@ -90,6 +91,34 @@ pub enum Command {
         /// has to be forgotten
         outpoint: OutPoint,
     },
+
+    /// Shows asset balance split into spendable and unconfirmed parts
+    Balance {
+        /// Bech32 representation of the asset ID (contract id of the asset
+        /// genesis)
+        #[clap(parse(try_from_str = ContractId::from_bech32_str))]
+        asset: ContractId,
+    },
+
+    /// Lists history of asset transfers
+    History {
+        /// Bech32 representation of the asset ID (contract id of the asset
+        /// genesis)
+        #[clap(parse(try_from_str = ContractId::from_bech32_str))]
+        asset: ContractId,
+
+        /// Number of history entries to skip
+        #[clap(short, long, default_value = "0")]
+        offset: u32,
+
+        /// Maximum number of history entries to show
+        #[clap(short, long, default_value = "100")]
+        limit: u32,
+
+        /// Format for information output
+        #[clap(short, long, arg_enum, default_value = "yaml")]
+        format: OutputFormat,
+    },
 }
 
 #[derive(Clap, Clone, PartialEq, Debug, Display)]
@@ -158,6 +187,13 @@ impl Command {
                 blinding_factor,
             ),
             Command::Forget { outpoint } => self.exec_forget(runtime, outpoint),
+            Command::Balance { asset } => self.exec_balance(runtime, asset),
+            Command::History {
+                asset,
+                offset,
+                limit,
+                format,
+            } => self.exec_history(runtime, asset, offset, limit, format),
         }
     }
 
@@ -288,8 +324,37 @@ impl Command {
             Reply::Failure(failure) => {
                 eprintln!("Server returned error: {}", failure);
             }
-            Reply::Success => {
-                eprintln!("Asset transfer successfully validated.");
+            Reply::ValidationStatus(status) => {
+                match status.validity() {
+                    Validity::Valid => {
+                        eprintln!("Asset transfer successfully validated.")
+                    }
+                    Validity::UnresolvedTransactions => eprintln!(
+                        "Asset transfer can't be validated: some of the witness transactions are not found"
+                    ),
+                    Validity::Invalid => {
+                        eprintln!("Asset transfer is invalid.")
+                    }
+                }
+                for txid in &status.unresolved_txids {
+                    println!("Unresolved transaction: {}", txid);
+                }
+                for failure in &status.failures {
+                    print!("{} failure", failure.kind);
+                    if let Some(node_id) = failure.node_id {
+                        print!(" in node {}", node_id);
+                    }
+                    if let Some(txid) = failure.txid {
+                        print!(" for transaction {}", txid);
+                    }
+                    println!(": {}", failure.details);
+                }
+                for warning in &status.warnings {
+                    println!("Warning: {}", warning);
+                }
+                for info in &status.info {
+                    println!("Info: {}", info);
+                }
             }
             _ => {
                 eprintln!(
@@ -381,6 +446,67 @@ impl Command {
 
         Ok(())
     }
+
+    fn exec_balance(
+        &self,
+        mut runtime: Runtime,
+        asset_id: ContractId,
+    ) -> Result<(), Error> {
+        info!("Requesting asset balance...");
+
+        match &*runtime.balance(asset_id)? {
+            Reply::Failure(failure) => {
+                eprintln!("Server returned error: {}", failure);
+            }
+            Reply::Balance(balance) => {
+                println!("Spendable: {}", balance.spendable);
+                println!("Unconfirmed: {}", balance.unconfirmed);
+                if balance.failed > 0 {
+                    println!("Failed: {}", balance.failed);
+                }
+            }
+            _ => {
+                eprintln!(
+                    "Unexpected server error; probably you connecting with outdated client version"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn exec_history(
+        &self,
+        mut runtime: Runtime,
+        asset_id: ContractId,
+        offset: u32,
+        limit: u32,
+        output_format: OutputFormat,
+    ) -> Result<(), Error> {
+        info!("Reading asset transfer history...");
+
+        match &*runtime.history(asset_id, offset, limit)? {
+            Reply::Failure(failure) => {
+                eprintln!("Server returned error: {}", failure);
+            }
+            Reply::History(history) => {
+                let output = match output_format {
+                    OutputFormat::Yaml => serde_yaml::to_string(&history)?,
+                    OutputFormat::Json => serde_json::to_string(&history)?,
+                    OutputFormat::Toml => toml::to_string(&history)?,
+                    _ => Err(Error::FormatNotSupported)?,
+                };
+                println!("{}", output);
+            }
+            _ => {
+                eprintln!(
+                    "Unexpected server error; probably you connecting with outdated client version"
+                );
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Issue {
@@ -433,6 +559,10 @@ impl TransferCli {
         info!("Transferring asset ...");
         debug!("{}", self.clone());
 
+        self.invoice
+            .check_network(runtime.network())
+            .map_err(ServiceErrorDomain::from)?;
+
         let seal_confidential = match self.invoice.outpoint {
             Outpoint::BlindedUtxo(outpoint_hash) => outpoint_hash,
             Outpoint::Address(_address) => {
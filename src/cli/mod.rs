@@ -13,6 +13,7 @@
 
 mod config;
 mod error;
+mod event;
 pub mod fungible;
 mod runtime;
 pub mod stash;
@@ -14,6 +14,7 @@
 use std::sync::Arc;
 
 use lnpbp::bitcoin::OutPoint;
+use lnpbp::bp;
 use lnpbp::lnp::presentation::Encode;
 use lnpbp::lnp::transport::zmqsocket::ZmqType;
 use lnpbp::lnp::{
@@ -23,63 +24,168 @@ use lnpbp::lnp::{
 use lnpbp::rgb::{Consignment, ContractId, Genesis, SchemaId};
 
 use super::{Config, Error};
-use crate::api::fungible::{self, AcceptApi, Issue, TransferApi};
+use crate::api::fungible::{self, AcceptApi, HistoryApi, Issue, TransferApi};
 use crate::api::stash;
-use crate::api::Reply;
+use crate::api::{Event, Handshake, Hello, Reply};
 use crate::cli::OutputFormat;
-use crate::error::{BootstrapError, ServiceErrorDomain};
+use crate::error::{BootstrapError, NetworkMismatch, ServiceErrorDomain};
 use crate::DataFormat;
 
+/// Time to wait for a daemon to confirm shutdown, in milliseconds
+const SHUTDOWN_TIMEOUT: i32 = 5000;
+
 pub struct Runtime {
+    network: bp::Chain,
     stash_rpc: session::Raw<PlainTranscoder, transport::zmqsocket::Connection>,
     fungible_rpc:
         session::Raw<PlainTranscoder, transport::zmqsocket::Connection>,
+    stash_sub: session::Raw<PlainTranscoder, transport::zmqsocket::Connection>,
+    fungible_sub:
+        session::Raw<PlainTranscoder, transport::zmqsocket::Connection>,
     unmarshaller: Unmarshaller<Reply>,
+    event_unmarshaller: Unmarshaller<Event>,
+    stash_handshake: Handshake,
+    fungible_handshake: Handshake,
 }
 
 impl Runtime {
     pub async fn init(config: Config) -> Result<Self, BootstrapError> {
-        let fungible_rpc = session::Raw::with_zmq_unencrypted(
-            ZmqType::Req,
-            &config.fungible_endpoint,
-            None,
-            None,
-        )?;
-        let stash_rpc = session::Raw::with_zmq_unencrypted(
-            ZmqType::Req,
-            &config.stash_endpoint,
-            None,
-            None,
-        )?;
+        let fungible_rpc = config
+            .auth
+            .connect(ZmqType::Req, &config.fungible_endpoint)?;
+        let stash_rpc =
+            config.auth.connect(ZmqType::Req, &config.stash_endpoint)?;
+        // Subscriber sockets do not receive anything until we subscribe them
+        // to some topic in `Runtime::subscribe`
+        let fungible_sub =
+            config.auth.connect(ZmqType::Sub, &config.fungible_pub)?;
+        let stash_sub = config.auth.connect(ZmqType::Sub, &config.stash_pub)?;
         Ok(Self {
+            network: config.network.clone(),
             stash_rpc,
             fungible_rpc,
+            stash_sub,
+            fungible_sub,
             unmarshaller: Reply::create_unmarshaller(),
+            event_unmarshaller: Event::create_unmarshaller(),
+            stash_handshake: Handshake::Pending,
+            fungible_handshake: Handshake::Pending,
         })
     }
 
-    fn stash_command(
+    /// Bitcoin network used by the client
+    #[inline]
+    pub fn network(&self) -> &bp::Chain {
+        &self.network
+    }
+
+    /// Subscribes to all events published by stash and contracts services
+    pub fn subscribe(&mut self) -> Result<(), Error> {
+        for session in &[&self.stash_sub, &self.fungible_sub] {
+            session.as_socket().set_subscribe(&[]).map_err(|err| {
+                ServiceErrorDomain::from(transport::Error::from(err))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Blocks until an event is published by any of the services
+    pub fn next_event(&mut self) -> Result<Arc<Event>, Error> {
+        let mut items = [
+            self.stash_sub.as_socket().as_poll_item(zmq::POLLIN),
+            self.fungible_sub.as_socket().as_poll_item(zmq::POLLIN),
+        ];
+        zmq::poll(&mut items, -1).map_err(|err| {
+            ServiceErrorDomain::from(transport::Error::from(err))
+        })?;
+        let from_stash = items[0].is_readable();
+        let session = if from_stash {
+            &mut self.stash_sub
+        } else {
+            &mut self.fungible_sub
+        };
+        let raw = session
+            .recv_raw_message()
+            .map_err(ServiceErrorDomain::from)?;
+        Ok(self
+            .event_unmarshaller
+            .unmarshall(&raw)
+            .map_err(ServiceErrorDomain::from)?)
+    }
+
+    fn stash_request(
         &mut self,
-        command: stash::Request,
+        request: stash::Request,
     ) -> Result<Arc<Reply>, ServiceErrorDomain> {
-        let data = command.encode()?;
+        let data = request.encode()?;
         self.stash_rpc.send_raw_message(&data)?;
         let raw = self.stash_rpc.recv_raw_message()?;
         let reply = self.unmarshaller.unmarshall(&raw)?;
         Ok(reply)
     }
 
-    fn fungible_command(
+    fn fungible_request(
         &mut self,
-        command: fungible::Request,
+        request: fungible::Request,
     ) -> Result<Arc<Reply>, ServiceErrorDomain> {
-        let data = command.encode()?;
+        let data = request.encode()?;
         self.fungible_rpc.send_raw_message(&data)?;
         let raw = self.fungible_rpc.recv_raw_message()?;
         let reply = self.unmarshaller.unmarshall(&raw)?;
         Ok(reply)
     }
 
+    fn stash_command(
+        &mut self,
+        command: stash::Request,
+    ) -> Result<Arc<Reply>, ServiceErrorDomain> {
+        if self.stash_handshake.is_pending() {
+            let reply = self
+                .stash_request(stash::Request::Hello(Hello::with("rgb-cli")))?;
+            self.stash_handshake = Handshake::with_reply(&reply)?;
+            if let Some(capabilities) = self.stash_handshake.capabilities() {
+                NetworkMismatch::check(&self.network, &capabilities.network)?;
+            }
+        }
+        self.stash_handshake.check_request(command.name())?;
+        self.stash_request(command)
+    }
+
+    fn fungible_command(
+        &mut self,
+        command: fungible::Request,
+    ) -> Result<Arc<Reply>, ServiceErrorDomain> {
+        if self.fungible_handshake.is_pending() {
+            let reply = self.fungible_request(fungible::Request::Hello(
+                Hello::with("rgb-cli"),
+            ))?;
+            self.fungible_handshake = Handshake::with_reply(&reply)?;
+            if let Some(capabilities) = self.fungible_handshake.capabilities() {
+                NetworkMismatch::check(&self.network, &capabilities.network)?;
+            }
+        }
+        self.fungible_handshake.check_request(command.name())?;
+        self.fungible_request(command)
+    }
+
+    /// Asks fungibled and then stashd to shut down. When the daemons are
+    /// supervised by rgbd, shutdown of one of them makes rgbd stop the rest,
+    /// so a daemon which has not replied in time is reported with `None`
+    pub fn shutdown(
+        &mut self,
+    ) -> Result<Vec<(&'static str, Option<Arc<Reply>>)>, Error> {
+        for session in &mut [&mut self.fungible_rpc, &mut self.stash_rpc] {
+            session
+                .as_socket()
+                .set_rcvtimeo(SHUTDOWN_TIMEOUT)
+                .map_err(transport::Error::from)
+                .map_err(ServiceErrorDomain::from)?;
+        }
+        let fungible = self.fungible_request(fungible::Request::Shutdown());
+        let stash = self.stash_request(stash::Request::Shutdown());
+        Ok(vec![("fungibled", fungible.ok()), ("stashd", stash.ok())])
+    }
+
     #[inline]
     pub fn list_schemata(&mut self) -> Result<Arc<Reply>, Error> {
         Ok(self.stash_command(stash::Request::ListSchemata())?)
@@ -120,6 +226,8 @@ impl Runtime {
 
     #[inline]
     pub fn import(&mut self, genesis: Genesis) -> Result<Arc<Reply>, Error> {
+        NetworkMismatch::check(&self.network, genesis.chain())
+            .map_err(ServiceErrorDomain::from)?;
         Ok(self.fungible_command(fungible::Request::ImportAsset(genesis))?)
     }
 
@@ -149,11 +257,23 @@ impl Runtime {
         &mut self,
         consignment: Consignment,
     ) -> Result<Arc<Reply>, Error> {
-        Ok(self.fungible_command(fungible::Request::Validate(consignment))?)
+        NetworkMismatch::check(&self.network, consignment.genesis.chain())
+            .map_err(ServiceErrorDomain::from)?;
+        Ok(self.fungible_command(fungible::Request::Validate(
+            stash::ValidateRequest {
+                consignment,
+                min_confirmations: None,
+            },
+        ))?)
     }
 
     #[inline]
     pub fn accept(&mut self, accept: AcceptApi) -> Result<Arc<Reply>, Error> {
+        NetworkMismatch::check(
+            &self.network,
+            accept.consignment.genesis.chain(),
+        )
+        .map_err(ServiceErrorDomain::from)?;
         Ok(self.fungible_command(fungible::Request::Accept(accept))?)
     }
 
@@ -161,4 +281,27 @@ impl Runtime {
     pub fn forget(&mut self, outpoint: OutPoint) -> Result<Arc<Reply>, Error> {
         Ok(self.fungible_command(fungible::Request::Forget(outpoint))?)
     }
+
+    #[inline]
+    pub fn balance(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<Arc<Reply>, Error> {
+        Ok(self.fungible_command(fungible::Request::Balance(contract_id))?)
+    }
+
+    #[inline]
+    pub fn history(
+        &mut self,
+        contract_id: ContractId,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Arc<Reply>, Error> {
+        let request = fungible::Request::History(HistoryApi {
+            contract_id,
+            offset,
+            limit,
+        });
+        Ok(self.fungible_command(request)?)
+    }
 }
@@ -13,27 +13,43 @@
 
 //! Shared constants, including configuration parameters etc
 
+/// Version of the API protocol spoken between clients and daemons; must be
+/// increased on any incompatible change to the API requests or replies
+pub const RGB_PROTOCOL_VERSION: u16 = 2;
+
 pub const RGB20_BECH32_HRP_INVOICE: &'static str = "rgb20:";
 
 pub const RGB_DATA_DIR: &'static str = "/var/lib/rgb";
 pub const RGB_BIN_DIR: &'static str = "/usr/local/bin";
 pub const RGB_CONTRACTS: &'static str = "fungible";
 pub const RGB_NETWORK: &'static str = "testnet";
+pub const RGB_WALLET: &'static str = "default";
+pub const RGB_CONFIG_FILE: &'static str = "{data_dir}/rgb.toml";
+
+pub const RGBD_RESTART_POLICY: &'static str = "on-failure";
+pub const RGBD_MAX_RESTARTS: &'static str = "10";
+pub const RGBD_STARTUP_TIMEOUT: &'static str = "30";
+pub const RGBD_HEALTH_FILE: &'static str = "{data_dir}/rgbd.health";
+pub const RGBD_RPC_ENDPOINT: &'static str = "lnpz:{data_dir}/rgbd.rpc";
 
 pub const STASHD_STASH: &'static str = "{data_dir}/{network}/stash/{id}/";
 pub const STASHD_INDEX: &'static str =
     "{data_dir}/{network}/stash/{id}/index.dat";
+pub const STASHD_TX_CACHE: &'static str = "{data_dir}/{network}/cache/{id}/txs";
 pub const STASHD_P2P_ENDPOINT: &'static str = "lnp://{node_id}@0.0.0.0:13000";
 pub const STASHD_RPC_ENDPOINT: &'static str =
-    "lnpz:{data_dir}/{network}/stashd.rpc";
+    "lnpz:{data_dir}/{network}/stashd-{id}.rpc";
 pub const STASHD_PUB_ENDPOINT: &'static str =
-    "lnpz:{data_dir}/{network}/stashd.pub";
+    "lnpz:{data_dir}/{network}/stashd-{id}.pub";
 
-pub const FUNGIBLED_CACHE: &'static str = "{data_dir}/{network}/cache/fungible";
+pub const FUNGIBLED_CACHE: &'static str =
+    "{data_dir}/{network}/cache/{id}/fungible";
 pub const FUNGIBLED_RPC_ENDPOINT: &'static str =
-    "lnpz:{data_dir}/{network}/fungibled.rpc";
+    "lnpz:{data_dir}/{network}/fungibled-{id}.rpc";
 pub const FUNGIBLED_PUB_ENDPOINT: &'static str =
-    "lnpz:{data_dir}/{network}/fungibled.pub";
+    "lnpz:{data_dir}/{network}/fungibled-{id}.pub";
+
+pub const GATEWAY_HTTP_ENDPOINT: &'static str = "127.0.0.1:13080";
 
 pub const DEFAULT_ELECTRUM_ENDPOINT: &'static str =
     "electrum.blockstream.info:60001";
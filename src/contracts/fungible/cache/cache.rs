@@ -17,8 +17,8 @@ use std::collections::BTreeMap;
 
 use super::sql::SqlCacheError;
 use super::FileCacheError;
-use crate::error::{BootstrapError, ServiceErrorDomain};
-use crate::fungible::Asset;
+use crate::error::{BootstrapError, ErrorCode, ServiceErrorDomain};
+use crate::fungible::{Asset, HistoryEntry};
 use crate::util::file::FileMode;
 
 pub trait Cache {
@@ -36,6 +36,14 @@ pub trait Cache {
         contract_id: ContractId,
     ) -> Result<BTreeMap<bitcoin::OutPoint, Vec<AtomicValue>>, Self::Error>;
 
+    /// Returns a page of the transfer history for a given asset
+    fn asset_history(
+        &self,
+        contract_id: ContractId,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<HistoryEntry>, Self::Error>;
+
     /// Returns the map of Asset-Allocation_amount for a given Outpoint
     fn outpoint_assets(
         &self,
@@ -61,8 +69,20 @@ pub enum CacheError {
 }
 
 impl From<CacheError> for ServiceErrorDomain {
-    fn from(_: CacheError) -> Self {
-        ServiceErrorDomain::Cache
+    fn from(err: CacheError) -> Self {
+        ServiceErrorDomain::Cache(err)
+    }
+}
+
+impl From<&CacheError> for ErrorCode {
+    fn from(err: &CacheError) -> Self {
+        match err {
+            CacheError::Io(_) => ErrorCode::CacheIo,
+            CacheError::NotFound { .. } => ErrorCode::CacheNotFound,
+            CacheError::DataAccessError { .. } => ErrorCode::CacheDataAccess,
+            CacheError::DataIntegrityError(_) => ErrorCode::CacheDataIntegrity,
+            CacheError::Sqlite(_) => ErrorCode::CacheSqlite,
+        }
     }
 }
 
@@ -97,6 +117,12 @@ impl From<FileCacheError> for CacheError {
             FileCacheError::NotFound => {
                 Self::DataIntegrityError("Data file is not found".to_string())
             }
+            FileCacheError::UnsupportedVersion(version) => {
+                Self::DataIntegrityError(format!(
+                    "Unsupported assets file format version {}",
+                    version
+                ))
+            }
         }
     }
 }
@@ -18,11 +18,14 @@ use std::{fs, io, io::Read, io::Write};
 
 use lnpbp::bitcoin;
 use lnpbp::rgb::prelude::*;
-use lnpbp::strict_encoding::{strict_encode, StrictDecode, StrictEncode};
+use lnpbp::strict_encoding::{
+    strict_decode, strict_encode, StrictDecode, StrictEncode,
+};
 
 use super::Cache;
+use crate::contracts::fungible::data::AssetV0;
 use crate::fungible::cache::CacheError;
-use crate::fungible::Asset;
+use crate::fungible::{Asset, HistoryEntry};
 use crate::util::file::*;
 use crate::DataFormat;
 
@@ -52,8 +55,18 @@ pub enum FileCacheError {
     SerdeToml,
 
     NotFound,
+
+    /// Assets file has a format version unknown to this node
+    UnsupportedVersion(u16),
 }
 
+/// Magic bytes starting strict-encoded assets files of version 1 and above.
+/// Files of version 0 had no header and started with the number of assets.
+const ASSETS_FILE_MAGIC: [u8; 4] = *b"RGBA";
+
+/// Current version of the strict-encoded assets file format
+const ASSETS_FILE_VERSION: u16 = 1;
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Display)]
 #[display(Debug)]
 pub struct FileCacheConfig {
@@ -131,11 +144,35 @@ impl FileCache {
                 f.read_to_string(&mut data)?;
                 toml::from_str(&data)?
             }
-            DataFormat::StrictEncode => StrictDecode::strict_decode(f)?,
+            DataFormat::StrictEncode => {
+                let mut data = vec![];
+                f.read_to_end(&mut data)?;
+                Self::decode_assets(&data)?
+            }
         };
         Ok(())
     }
 
+    /// Decodes strict-encoded assets file, migrating it from the older
+    /// format versions
+    fn decode_assets(
+        data: &[u8],
+    ) -> Result<BTreeMap<ContractId, Asset>, FileCacheError> {
+        if !data.starts_with(&ASSETS_FILE_MAGIC) {
+            debug!("Migrating assets from format version 0 ...");
+            let assets: BTreeMap<ContractId, AssetV0> = strict_decode(&data)?;
+            return Ok(assets
+                .into_iter()
+                .map(|(id, asset)| (id, Asset::from(asset)))
+                .collect());
+        }
+        let mut data = &data[ASSETS_FILE_MAGIC.len()..];
+        match u16::strict_decode(&mut data)? {
+            ASSETS_FILE_VERSION => Ok(strict_decode(&data)?),
+            version => Err(FileCacheError::UnsupportedVersion(version)),
+        }
+    }
+
     pub fn save(&self) -> Result<(), FileCacheError> {
         trace!("Saving assets information ...");
         let filename = self.config.assets_filename();
@@ -146,6 +183,8 @@ impl FileCache {
             DataFormat::Json => serde_json::to_writer(&f, &self.assets)?,
             DataFormat::Toml => f.write_all(&toml::to_vec(&self.assets)?)?,
             DataFormat::StrictEncode => {
+                f.write_all(&ASSETS_FILE_MAGIC)?;
+                ASSETS_FILE_VERSION.strict_encode(&mut f)?;
                 self.assets.strict_encode(f)?;
             }
         }
@@ -219,6 +258,16 @@ impl Cache for FileCache {
         Ok(result)
     }
 
+    #[inline]
+    fn asset_history(
+        &self,
+        contract_id: ContractId,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<HistoryEntry>, CacheError> {
+        Ok(self.asset(contract_id)?.history_page(offset, limit))
+    }
+
     fn outpoint_assets(
         &self,
         outpoint: bitcoin::OutPoint,
@@ -247,6 +296,7 @@ impl Cache for FileCache {
 mod test {
     use super::super::sql::{SqlCache, SqlCacheConfig};
     use super::*;
+    use crate::fungible::{Direction, EntryStatus};
     use lnpbp::hex::FromHex;
     use std::env;
 
@@ -365,4 +415,107 @@ mod test {
         // Assert caclulation meets expectation
         assert_eq!(expected_map, allocation_map_calculated);
     }
+
+    #[test]
+    #[ignore]
+    fn test_filecache_history() {
+        // As the test above, this one copies test assets from the sqlite
+        // database, so it should be run after running tests in sql.rs module.
+        let database_url = env::var("DATABASE_URL").expect(
+            "Environment Variable 'DATABASE_URL' must be set to run this test",
+        );
+        let filepath = PathBuf::from(&database_url[..]);
+        let config = SqlCacheConfig {
+            data_dir: filepath.clone(),
+        };
+        let sql_cache = SqlCache::new(&config).unwrap();
+
+        let filecache_config = FileCacheConfig {
+            data_dir: filepath.join("history"),
+            data_format: DataFormat::StrictEncode,
+        };
+        let _ = fs::remove_file(filecache_config.assets_filename());
+
+        let node_id = NodeId::from_hex(
+            "d47df6cf7a0eff79d3afeab7614404e43a0fa4498ff081918a2e75d7366cd730",
+        )
+        .unwrap();
+        let txid = bitcoin::Txid::from_hex(
+            "db2f3035e05795d72e2744dc0e88b2f72acbed97ee9a54c2c7f52d426ae05627",
+        )
+        .unwrap();
+
+        // -------------------------------------------------
+        // TEST HISTORY ROUND-TRIP
+
+        let mut filecache = FileCache::new(filecache_config.clone()).unwrap();
+        let mut assets = vec![];
+        for asset in sql_cache.assets().unwrap() {
+            let mut asset = asset.clone();
+            asset.add_history(HistoryEntry::new(
+                Direction::Received,
+                node_id,
+                7,
+                None,
+                Some(txid),
+            ));
+            let mut invalid =
+                HistoryEntry::new(Direction::Sent, node_id, 3, None, None);
+            invalid.set_status(EntryStatus::Invalid);
+            asset.add_history(invalid);
+            filecache.add_asset(asset.clone()).unwrap();
+            assets.push(asset);
+        }
+
+        let filecache = FileCache::new(filecache_config.clone()).unwrap();
+        for asset in &assets {
+            assert_eq!(filecache.asset(*asset.id()).unwrap(), asset);
+        }
+
+        // -------------------------------------------------
+        // TEST MIGRATION FROM THE UNVERSIONED FORMAT
+
+        let legacy = assets
+            .iter()
+            .map(|asset| (*asset.id(), AssetV0::from(asset)))
+            .collect::<BTreeMap<_, _>>();
+        fs::write(
+            filecache_config.assets_filename(),
+            strict_encode(&legacy).unwrap(),
+        )
+        .unwrap();
+
+        let filecache = FileCache::new(filecache_config.clone()).unwrap();
+        for asset in &assets {
+            let migrated = filecache.asset(*asset.id()).unwrap();
+            assert_eq!(migrated.name(), asset.name());
+            assert!(migrated.history().is_empty());
+            assert_eq!(
+                filecache.asset_allocations(*asset.id()).unwrap(),
+                sql_cache.asset_allocations(*asset.id()).unwrap()
+            );
+        }
+
+        // Saving writes the migrated data in the current format
+        filecache.save().unwrap();
+        let data = fs::read(filecache_config.assets_filename()).unwrap();
+        assert!(data.starts_with(&ASSETS_FILE_MAGIC));
+        assert_eq!(
+            FileCache::new(filecache_config.clone()).unwrap().assets,
+            filecache.assets
+        );
+
+        // -------------------------------------------------
+        // TEST UNKNOWN FORMAT VERSION
+
+        let mut data = ASSETS_FILE_MAGIC.to_vec();
+        data.extend(strict_encode(&(ASSETS_FILE_VERSION + 1)).unwrap());
+        fs::write(filecache_config.assets_filename(), data).unwrap();
+        match FileCache::new(filecache_config) {
+            Err(FileCacheError::UnsupportedVersion(version)) => {
+                assert_eq!(version, ASSETS_FILE_VERSION + 1)
+            }
+            _ => panic!("Unknown assets file version must not be accepted"),
+        }
+    }
 }
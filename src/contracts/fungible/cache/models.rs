@@ -4,20 +4,22 @@ use crate::contracts::fungible::cache::schema as cache_schema;
 use cache_schema::sql_allocation_utxo::dsl::sql_allocation_utxo as sql_allocation_utxo_table;
 use cache_schema::sql_allocations::dsl::sql_allocations as sql_allocation_table;
 use cache_schema::sql_assets::dsl::sql_assets as sql_asset_table;
+use cache_schema::sql_history::dsl::sql_history as sql_history_table;
 use cache_schema::sql_inflation::dsl::sql_inflation as sql_inflation_table;
 use cache_schema::sql_issues::dsl::sql_issues as sql_issue_table;
 use cache_schema::*;
 
 use super::sql::SqlCacheError;
 use crate::contracts::fungible::data::{
-    AccountingAmount, AccountingValue, Allocation, Asset,
+    AccountingAmount, AccountingValue, Allocation, AllocationStatus, Asset,
+    HistoryEntry,
 };
 use diesel::prelude::*;
 use lnpbp::bitcoin::{OutPoint, Txid};
 use lnpbp::hex::{FromHex, ToHex};
 /// All the sqlite table structures are defined here.
-/// There are 5 tables namely Asset, Issue, Inflation, AllocationUtxo
-/// and Allocation. The Asset is the major table, and all other tables
+/// There are 6 tables namely Asset, Issue, Inflation, AllocationUtxo,
+/// Allocation and History. The Asset is the major table, and all other tables
 /// are associated with Asset by sql_asset_id field.
 
 #[derive(Queryable, Insertable, Identifiable, Clone, Debug)]
@@ -283,6 +285,11 @@ pub struct SqlAllocation {
     pub assignment_index: i32,
     pub amount: i64,
     pub blinding: String,
+    pub witness_txid: Option<String>,
+    /// Allocation status: 0 for pending, 1 for mined and 2 for failed
+    pub status: i32,
+    pub height: i32,
+    pub confirmations: i32,
 }
 
 /// Create a list of AllocationUtxo and Allocation table entry
@@ -326,6 +333,14 @@ pub fn create_allocation_from_asset(
             vout: item.0.vout as i32,
         });
         for (index, alloc) in item.1.into_iter().enumerate() {
+            let (status, height, confirmations) = match alloc.status() {
+                AllocationStatus::Pending => (0, 0, 0),
+                AllocationStatus::Mined {
+                    height,
+                    confirmations,
+                } => (1, *height as i32, *confirmations as i32),
+                AllocationStatus::Failed => (2, 0, 0),
+            };
             allocation_vec.push(SqlAllocation {
                 id: match last_alloc.clone() {
                     Some(alloc) => {
@@ -338,6 +353,13 @@ pub fn create_allocation_from_asset(
                 assignment_index: alloc.index().clone() as i32,
                 amount: alloc.value().value as i64,
                 blinding: alloc.value().blinding.0.to_vec().to_hex(),
+                witness_txid: alloc
+                    .witness_txid()
+                    .as_ref()
+                    .map(|txid| txid.to_hex()),
+                status,
+                height,
+                confirmations,
             });
         }
         added_allocations += item.1.len() as i32;
@@ -394,3 +416,74 @@ pub fn read_allocations(
 
     Ok(known_allocation)
 }
+
+#[derive(Queryable, Insertable, Identifiable, Associations, Clone, Debug)]
+#[table_name = "sql_history"]
+#[belongs_to(SqlAsset)]
+pub struct SqlHistory {
+    pub id: i32,
+    pub sql_asset_id: i32,
+    pub node_id: String,
+    pub direction: i32,
+    pub seal: Option<String>,
+    pub amount: i64,
+    pub witness_txid: Option<String>,
+    pub timestamp: chrono::NaiveDateTime,
+    /// Entry status: 0 for active, 1 for reorged and 2 for invalid
+    pub status: i32,
+}
+
+impl SqlHistory {
+    /// Create vector of History table entries from a given Asset data
+    pub fn from_asset(
+        asset: &Asset,
+        table_asset: &SqlAsset,
+        connection: &SqliteConnection,
+    ) -> Result<Vec<Self>, SqlCacheError> {
+        // get the largest history entry id and increase id from there
+        let next_id = sql_history_table
+            .select(diesel::dsl::max(sql_history::id))
+            .first::<Option<i32>>(connection)?
+            .map(|id| id + 1)
+            .unwrap_or(0);
+
+        let mut result = vec![];
+
+        for (index, entry) in asset.history().into_iter().enumerate() {
+            result.push(Self {
+                id: next_id + index as i32,
+                sql_asset_id: table_asset.id,
+                node_id: entry.node_id().to_hex(),
+                direction: *entry.direction() as i32,
+                seal: entry.seal().as_ref().map(|seal| seal.to_hex()),
+                amount: *entry.amount() as i64,
+                witness_txid: entry
+                    .witness_txid()
+                    .as_ref()
+                    .map(|txid| txid.to_hex()),
+                timestamp: entry.timestamp().clone(),
+                status: *entry.status() as i32,
+            })
+        }
+        Ok(result)
+    }
+}
+
+/// Read the History entries associated with the given Asset entry.
+/// This will return a Vec which can be directly used as the `history` field
+/// in the Asset data structure.
+pub fn read_history(
+    asset: &SqlAsset,
+    connection: &SqliteConnection,
+) -> Result<Vec<HistoryEntry>, SqlCacheError> {
+    let mut entries =
+        SqlHistory::belonging_to(asset).load::<SqlHistory>(connection)?;
+    entries.sort_by_key(|entry| entry.id);
+
+    let mut history = vec![];
+    for entry in entries {
+        history.push(HistoryEntry::from_sql_history(&entry)?);
+    }
+
+    Ok(history)
+}
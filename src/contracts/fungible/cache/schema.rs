@@ -15,6 +15,10 @@ table! {
         assignment_index -> Integer,
         amount -> BigInt,
         blinding -> Text,
+        witness_txid -> Nullable<Text>,
+        status -> Integer,
+        height -> Integer,
+        confirmations -> Integer,
     }
 }
 
@@ -34,6 +38,20 @@ table! {
     }
 }
 
+table! {
+    sql_history (id) {
+        id -> Integer,
+        sql_asset_id -> Integer,
+        node_id -> Text,
+        direction -> Integer,
+        seal -> Nullable<Text>,
+        amount -> BigInt,
+        witness_txid -> Nullable<Text>,
+        timestamp -> Timestamp,
+        status -> Integer,
+    }
+}
+
 table! {
     sql_inflation (id) {
         id -> Integer,
@@ -60,6 +78,7 @@ allow_tables_to_appear_in_same_query!(
     sql_allocation_utxo,
     sql_allocations,
     sql_assets,
+    sql_history,
     sql_inflation,
     sql_issues,
 );
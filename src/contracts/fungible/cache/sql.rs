@@ -24,10 +24,11 @@ use lnpbp::rgb::prelude::*;
 use cache_schema::sql_allocation_utxo::dsl::sql_allocation_utxo as sql_allocation_utxo_table;
 use cache_schema::sql_allocations::dsl::sql_allocations as sql_allocation_table;
 use cache_schema::sql_assets::dsl::sql_assets as sql_asset_table;
+use cache_schema::sql_history::dsl::sql_history as sql_history_table;
 use cache_schema::sql_inflation::dsl::sql_inflation as sql_inflation_table;
 use cache_schema::sql_issues::dsl::sql_issues as sql_issue_table;
 
-use crate::contracts::fungible::data::Asset;
+use crate::contracts::fungible::data::{Asset, HistoryEntry};
 
 use std::path::PathBuf;
 
@@ -180,6 +181,7 @@ impl SqlCache {
         diesel::delete(sql_inflation_table).execute(&self.connection)?;
         diesel::delete(sql_allocation_utxo_table).execute(&self.connection)?;
         diesel::delete(sql_allocation_table).execute(&self.connection)?;
+        diesel::delete(sql_history_table).execute(&self.connection)?;
 
         // Create and write table entries from updated cached data
         for item in self.assets.clone().into_iter() {
@@ -200,6 +202,12 @@ impl SqlCache {
                     &self.connection,
                 )?;
 
+            let table_history = SqlHistory::from_asset(
+                &item.1,
+                &table_asset,
+                &self.connection,
+            )?;
+
             diesel::insert_into(sql_asset_table)
                 .values(table_asset)
                 .execute(&self.connection)?;
@@ -227,6 +235,12 @@ impl SqlCache {
                     .values(allocation)
                     .execute(&self.connection)?;
             }
+
+            for entry in table_history {
+                diesel::insert_into(sql_history_table)
+                    .values(entry)
+                    .execute(&self.connection)?;
+            }
         }
 
         Ok(())
@@ -285,6 +299,16 @@ impl Cache for SqlCache {
         Ok(result)
     }
 
+    #[inline]
+    fn asset_history(
+        &self,
+        contract_id: ContractId,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<HistoryEntry>, CacheError> {
+        Ok(self.asset(contract_id)?.history_page(offset, limit))
+    }
+
     fn outpoint_assets(
         &self,
         outpoint: bitcoin::OutPoint,
@@ -344,7 +368,7 @@ impl Cache for SqlCache {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::contracts::fungible::data::Asset;
+    use crate::contracts::fungible::data::{Asset, Direction, EntryStatus};
     use chrono::NaiveDate;
     use lnpbp::hex::FromHex;
     use lnpbp::rgb::ContractId;
@@ -602,6 +626,10 @@ mod test {
                         blinding:
                             "7c62d1e24a6e99e30743ff94e5d3f783efc1ab8016d342558802c7f56e06ac15"
                                 .to_string(),
+                        witness_txid: None,
+                        status: 0,
+                        height: 0,
+                        confirmations: 0,
                     };
 
                     diesel::insert_into(sql_allocation_table)
@@ -621,6 +649,10 @@ mod test {
                         blinding:
                             "d55723e84d9ac6f611610d04dfa3d4b32757d681e449201f9e587c1ecd7bcf78"
                                 .to_string(),
+                        witness_txid: None,
+                        status: 0,
+                        height: 0,
+                        confirmations: 0,
                     };
 
                     diesel::insert_into(sql_allocation_table)
@@ -640,6 +672,10 @@ mod test {
                         blinding:
                             "644549d3ac1349ec0143082b75d66b833be08b77d7e5f53c24a22ea9c16415fb"
                                 .to_string(),
+                        witness_txid: None,
+                        status: 0,
+                        height: 0,
+                        confirmations: 0,
                     };
 
                     diesel::insert_into(sql_allocation_table)
@@ -659,6 +695,10 @@ mod test {
                         blinding:
                             "37ec2ed7445ff79ca0ef3a2c95e404a4a65ba0e55c4e9e5ab26f1dde8eaa520b"
                                 .to_string(),
+                        witness_txid: None,
+                        status: 0,
+                        height: 0,
+                        confirmations: 0,
                     };
 
                     diesel::insert_into(sql_allocation_table)
@@ -678,6 +718,10 @@ mod test {
                         blinding:
                             "e2c314fe21e23e1e349851c23b6c74a8de3e938af79fb31b4e521921980443c3"
                                 .to_string(),
+                        witness_txid: None,
+                        status: 0,
+                        height: 0,
+                        confirmations: 0,
                     };
 
                     diesel::insert_into(sql_allocation_table)
@@ -697,6 +741,10 @@ mod test {
                         blinding:
                             "56e3d4561b3404353f3fd0f5729615f85e980f90a46b6a15192b8c4da97c6738"
                                 .to_string(),
+                        witness_txid: None,
+                        status: 0,
+                        height: 0,
+                        confirmations: 0,
                     };
 
                     diesel::insert_into(sql_allocation_table)
@@ -915,4 +963,85 @@ mod test {
         // Assert caclulation meets expectation
         assert_eq!(expected_map, allocation_map_calculated);
     }
+
+    #[test]
+    #[ignore]
+    fn test_sqlite_history() {
+        let database_url = env::var("DATABASE_URL").expect(
+            "Environment Variable 'DATABASE_URL' must be set to run this test",
+        );
+        let filepath = PathBuf::from(&database_url[..]);
+        let config = SqlCacheConfig { data_dir: filepath };
+        let mut cache = SqlCache::new(&config).unwrap();
+
+        let txid = bitcoin::Txid::from_hex(
+            "db2f3035e05795d72e2744dc0e88b2f72acbed97ee9a54c2c7f52d426ae05627",
+        )
+        .unwrap();
+        let node_id = NodeId::from_hex(
+            "d47df6cf7a0eff79d3afeab7614404e43a0fa4498ff081918a2e75d7366cd730",
+        )
+        .unwrap();
+
+        // Add history to both assets, so the history table ids of the second
+        // asset have to continue after the ones of the first asset
+        let mut originals = vec![];
+        let mut expected = BTreeMap::new();
+        let assets = cache
+            .assets()
+            .unwrap()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        for mut asset in assets {
+            originals.push(asset.clone());
+            asset.add_history(HistoryEntry::new(
+                Direction::Received,
+                node_id,
+                7,
+                None,
+                Some(txid),
+            ));
+            let mut reorged =
+                HistoryEntry::new(Direction::Sent, node_id, 3, None, None);
+            reorged.set_status(EntryStatus::Reorged);
+            asset.add_history(reorged);
+            asset.add_history(HistoryEntry::new(
+                Direction::UnknownSpend,
+                node_id,
+                4,
+                None,
+                None,
+            ));
+            expected.insert(*asset.id(), asset.history().clone());
+            cache.add_asset(asset).unwrap();
+        }
+        assert_eq!(expected.len(), 2);
+
+        // Re-read the database and check that the history is the same and
+        // kept in chronological order
+        let mut new_cache = SqlCache::new(&config).unwrap();
+        for (contract_id, history) in &expected {
+            assert_eq!(
+                new_cache.asset(*contract_id).unwrap().history(),
+                history
+            );
+            assert_eq!(
+                &new_cache.asset_history(*contract_id, 1, 10).unwrap(),
+                &history[1..].to_vec()
+            );
+        }
+
+        // Restore the original test data
+        for asset in originals {
+            new_cache.add_asset(asset).unwrap();
+        }
+        assert_eq!(
+            sql_history_table
+                .load::<SqlHistory>(&new_cache.connection)
+                .unwrap()
+                .len(),
+            0
+        );
+    }
 }
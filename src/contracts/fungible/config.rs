@@ -11,15 +11,23 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+use core::convert::TryFrom;
 use core::fmt::Display;
 use core::str::FromStr;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use crate::DataFormat;
 use lnpbp::bp;
 use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
 
+use crate::auth::ServerAuth;
 use crate::constants::*;
+use crate::error::ConfigError;
+#[cfg(feature = "shell")]
+use crate::util::config::LayeredOpts;
+use crate::util::file::utf8_path;
+use crate::util::WalletId;
 
 #[derive(Clap)]
 #[clap(
@@ -37,6 +45,11 @@ pub struct Opts {
     #[clap(short, long, default_value = RGB_DATA_DIR, env = "RGB_DATA_DIR")]
     pub data_dir: String,
 
+    /// Path to the configuration file; if not given, `rgb.toml` from the data
+    /// directory is used when present
+    #[clap(long, env = "RGB_CONFIG")]
+    pub config: Option<String>,
+
     /// Connection string to stash (exact format depends on used storage
     /// engine)
     #[clap(short = 's', long = "stash", default_value = FUNGIBLED_CACHE, env = "RGB_FUNGIBLED_CACHE")]
@@ -81,6 +94,73 @@ pub struct Opts {
     /// Bitcoin network to use
     #[clap(short, long, default_value = RGB_NETWORK, env = "RGB_NETWORK")]
     pub network: bp::Chain,
+
+    /// Wallet served by the daemon; each wallet has its own stash, cache and
+    /// set of daemons
+    #[clap(long, default_value = RGB_WALLET, env = "RGB_WALLET")]
+    pub wallet: WalletId,
+
+    /// Electrum server to use to track status of witness transactions
+    #[clap(
+        long = "electrum",
+        default_value = DEFAULT_ELECTRUM_ENDPOINT,
+        env = "RGB_ELECTRUM_SERVER"
+    )]
+    pub electrum_server: String,
+
+    /// URLs of the data sources for tracking status of witness and spending
+    /// transactions, in the order they are queried, in the same format as
+    /// stashd `--resolver`. Defaults to the `--electrum` server
+    #[clap(
+        long = "resolver",
+        env = "RGB_FUNGIBLED_RESOLVERS",
+        hide_env_values = true,
+        use_delimiter = true
+    )]
+    pub resolvers: Vec<String>,
+
+    /// Number of the data sources which must know the transaction for its
+    /// status to be reported; with the default value of 1 sources after the
+    /// first one are used only as fallbacks
+    #[clap(long, default_value = "1", env = "RGB_FUNGIBLED_RESOLVER_QUORUM")]
+    pub resolver_quorum: usize,
+
+    /// Minimal number of witness transaction confirmations required to
+    /// consider allocation spendable; spends of the allocated outpoints
+    /// require the same number of confirmations
+    #[clap(
+        long = "confirmations",
+        default_value = "1",
+        env = "RGB_FUNGIBLED_CONFIRMATIONS"
+    )]
+    pub min_confirmations: u32,
+
+    /// Interval in seconds for checking whether outpoints of the cached
+    /// allocations were spent; spent allocations are removed from the cache
+    /// automatically. Zero disables the check.
+    #[clap(long, default_value = "60", env = "RGB_FUNGIBLED_WATCH_INTERVAL")]
+    pub watch_interval: u64,
+
+    /// Z85-encoded public key of the node used to encrypt RPC and PUB/SUB
+    /// connections with CurveZMQ; encryption is disabled if not given
+    #[clap(long, env = "RGB_CURVE_PUBLIC")]
+    pub curve_public: Option<String>,
+
+    /// Z85-encoded secret key of the node matching `--curve-public`
+    #[clap(long, env = "RGB_CURVE_SECRET", hide_env_values = true)]
+    pub curve_secret: Option<String>,
+
+    /// Z85-encoded public keys of the clients allowed to connect, optionally
+    /// followed by the client role (`<key>:read-only`, `<key>:transfer` or
+    /// `<key>:admin`; admin by default) and the wallet the client is limited
+    /// to (`<key>:transfer@alice`). Required if `--curve-public` is given
+    #[clap(long, env = "RGB_CURVE_CLIENTS", use_delimiter = true)]
+    pub curve_clients: Vec<String>,
+
+    /// TCP socket address for exporting metrics in Prometheus text format
+    /// over HTTP; metrics are not exported if not given
+    #[clap(long = "metrics", env = "RGB_FUNGIBLED_METRICS")]
+    pub metrics_endpoint: Option<String>,
 }
 
 // We need config structure since not all of the parameters can be specified
@@ -98,22 +178,88 @@ pub struct Config {
     pub stash_rpc: ZmqSocketAddr,
     pub stash_sub: ZmqSocketAddr,
     pub network: bp::Chain,
+    pub wallet: WalletId,
+    pub electrum_server: String,
+    pub resolvers: Vec<String>,
+    pub resolver_quorum: usize,
+    pub min_confirmations: u32,
+    pub watch_interval: u64,
+    pub auth: ServerAuth,
+    pub metrics_endpoint: Option<SocketAddr>,
 }
 
-impl From<Opts> for Config {
-    fn from(opts: Opts) -> Self {
+#[cfg(feature = "shell")]
+impl LayeredOpts for Opts {
+    const FILE_PARAMS: &'static [(&'static str, &'static str)] = &[
+        ("data-dir", "data-dir"),
+        ("network", "network"),
+        ("wallet", "wallet"),
+        ("electrum-server", "electrum-server"),
+        ("curve-public", "curve-public"),
+        ("curve-secret", "curve-secret"),
+        ("curve-clients", "curve-clients"),
+        ("fungibled.cache", "cache"),
+        ("fungibled.format", "format"),
+        ("fungibled.resolvers", "resolvers"),
+        ("fungibled.resolver-quorum", "resolver-quorum"),
+        ("fungibled.confirmations", "min-confirmations"),
+        ("fungibled.watch-interval", "watch-interval"),
+        ("fungibled.rpc", "rpc-endpoint"),
+        ("fungibled.pub", "pub-endpoint"),
+        ("fungibled.metrics", "metrics-endpoint"),
+        ("stashd.rpc", "stash-rpc"),
+        ("stashd.pub", "stash-sub"),
+    ];
+
+    fn config_file(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    fn data_dir(&self) -> &str {
+        &self.data_dir
+    }
+}
+
+impl TryFrom<Opts> for Config {
+    type Error = ConfigError;
+
+    fn try_from(opts: Opts) -> Result<Self, Self::Error> {
         let mut me = Self {
             verbose: opts.verbose,
             network: opts.network,
+            wallet: opts.wallet,
+            resolver_quorum: opts.resolver_quorum,
+            min_confirmations: opts.min_confirmations,
+            watch_interval: opts.watch_interval,
             ..Config::default()
         };
-        me.data_dir = me.parse_param(opts.data_dir);
-        me.cache = me.parse_param(opts.cache);
-        me.rpc_endpoint = me.parse_param(opts.rpc_endpoint);
-        me.pub_endpoint = me.parse_param(opts.pub_endpoint);
-        me.stash_rpc = me.parse_param(opts.stash_rpc);
-        me.stash_sub = me.parse_param(opts.stash_sub);
-        me
+        me.data_dir = me.parse_param("data-dir", opts.data_dir)?;
+        me.cache = me.parse_param("stash", opts.cache)?;
+        me.rpc_endpoint = me.parse_param("rpc", opts.rpc_endpoint)?;
+        me.pub_endpoint = me.parse_param("pub", opts.pub_endpoint)?;
+        me.stash_rpc = me.parse_param("stash-rpc", opts.stash_rpc)?;
+        me.stash_sub = me.parse_param("stash-sub", opts.stash_sub)?;
+        me.electrum_server =
+            me.parse_param("electrum", opts.electrum_server)?;
+        me.resolvers = opts
+            .resolvers
+            .into_iter()
+            .map(|url| me.parse_param("resolver", url))
+            .collect::<Result<_, _>>()?;
+        if me.resolvers.is_empty() {
+            me.resolvers = vec![me.electrum_server.clone()];
+        }
+        me.metrics_endpoint = opts
+            .metrics_endpoint
+            .map(|ep| me.parse_param("metrics", ep))
+            .transpose()?;
+        me.auth = ServerAuth::from_opts(
+            opts.curve_public,
+            opts.curve_secret,
+            opts.curve_clients,
+            &me.wallet,
+        )?;
+        Ok(me)
     }
 }
 
@@ -135,28 +281,47 @@ impl Default for Config {
             stash_rpc: STASHD_RPC_ENDPOINT
                 .parse()
                 .expect("Error in STASHD_RPC_ENDPOINT constant value"),
-            stash_sub: STASHD_RPC_ENDPOINT
+            stash_sub: STASHD_PUB_ENDPOINT
                 .parse()
                 .expect("Error in STASHD_PUB_ENDPOINT constant value"),
             network: RGB_NETWORK
                 .parse()
                 .expect("Error in RGB_NETWORK constant value"),
+            wallet: WalletId::default(),
+            electrum_server: DEFAULT_ELECTRUM_ENDPOINT
+                .parse()
+                .expect("Error in DEFAULT_ELECTRUM_ENDPOINT constant value"),
+            resolvers: vec![DEFAULT_ELECTRUM_ENDPOINT.to_string()],
+            resolver_quorum: 1,
+            min_confirmations: 1,
+            watch_interval: 60,
+            auth: ServerAuth::default(),
+            metrics_endpoint: None,
         }
     }
 }
 
 impl Config {
-    pub fn parse_param<T>(&self, param: String) -> T
+    /// Parses value of the parameter with a given name, replacing
+    /// placeholders with the actual values
+    pub fn parse_param<T>(
+        &self,
+        name: &str,
+        param: String,
+    ) -> Result<T, ConfigError>
     where
         T: FromStr,
         T::Err: Display,
     {
         param
+            .replace("{id}", self.wallet.as_str())
             .replace("{network}", &self.network.to_string())
-            .replace("{data_dir}", self.data_dir.to_str().unwrap())
+            .replace("{data_dir}", utf8_path("data-dir", &self.data_dir)?)
             .parse()
-            .unwrap_or_else(|err| {
-                panic!("Error parsing parameter `{}`: {}", param, err)
+            .map_err(|err: T::Err| ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: param.clone(),
+                details: err.to_string(),
             })
     }
 }
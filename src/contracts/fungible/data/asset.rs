@@ -14,15 +14,15 @@
 use core::convert::{TryFrom, TryInto};
 use core::ops::{Add, AddAssign};
 use diesel::prelude::*;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::str::FromStr;
 
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 use crate::contracts::fungible::cache::models::{
-    read_allocations, read_inflation, SqlAllocation, SqlAllocationUtxo,
-    SqlAsset, SqlIssue,
+    read_allocations, read_history, read_inflation, SqlAllocation,
+    SqlAllocationUtxo, SqlAsset, SqlIssue,
 };
 use crate::contracts::fungible::cache::SqlCacheError;
 use lnpbp::bitcoin;
@@ -36,7 +36,9 @@ use lnpbp::secp256k1zkp::key::SecretKey;
 use lnpbp::secp256k1zkp::Secp256k1;
 
 use super::schema::{self, FieldType, OwnedRightsType};
+use super::{EntryStatus, HistoryEntry};
 use crate::error::ServiceErrorDomain;
+use crate::util::chain::{ChainStatus, TxStatus};
 
 pub type AccountingValue = f32;
 
@@ -182,6 +184,9 @@ pub struct Asset {
     unknown_inflation: AccountingAmount,
     /// Specifies outpoints controlling certain amounts of assets
     known_allocations: BTreeMap<bitcoin::OutPoint, Vec<Allocation>>,
+    /// Log of all asset movements known to us, in chronological order
+    #[cfg_attr(feature = "serde", serde(default))]
+    history: Vec<HistoryEntry>,
 }
 
 impl Asset {
@@ -221,10 +226,113 @@ impl Asset {
             known_inflation: known_inflation,
             unknown_inflation: unknown_inflation,
             known_allocations: read_allocations(&table_value, connection)?,
+            history: read_history(&table_value, connection)?,
         })
     }
 }
 
+/// Asset data as it was stored by the strict-encoded file cache before
+/// allocation statuses and transfer history were introduced (version 0 of
+/// the assets file format). Used only for migrating old files.
+#[derive(Clone, PartialEq, Debug, StrictEncode, StrictDecode)]
+pub(crate) struct AssetV0 {
+    id: ContractId,
+    ticker: String,
+    name: String,
+    description: Option<String>,
+    supply: Supply,
+    chain: bp::Chain,
+    fractional_bits: u8,
+    date: NaiveDateTime,
+    known_issues: Vec<Issue>,
+    known_inflation: BTreeMap<bitcoin::OutPoint, AccountingAmount>,
+    unknown_inflation: AccountingAmount,
+    known_allocations: BTreeMap<bitcoin::OutPoint, Vec<AllocationV0>>,
+}
+
+/// Allocation data of the version 0 assets file format
+#[derive(Clone, PartialEq, Debug, StrictEncode, StrictDecode)]
+pub(crate) struct AllocationV0 {
+    node_id: NodeId,
+    index: u16,
+    outpoint: bitcoin::OutPoint,
+    value: value::Revealed,
+}
+
+impl From<AssetV0> for Asset {
+    /// Migrated allocations start as pending and get their actual status
+    /// with the next status update; the history starts empty.
+    fn from(asset: AssetV0) -> Self {
+        Self {
+            id: asset.id,
+            ticker: asset.ticker,
+            name: asset.name,
+            description: asset.description,
+            supply: asset.supply,
+            chain: asset.chain,
+            fractional_bits: asset.fractional_bits,
+            date: asset.date,
+            known_issues: asset.known_issues,
+            known_inflation: asset.known_inflation,
+            unknown_inflation: asset.unknown_inflation,
+            known_allocations: asset
+                .known_allocations
+                .into_iter()
+                .map(|(outpoint, allocations)| {
+                    let allocations = allocations
+                        .into_iter()
+                        .map(|allocation| Allocation {
+                            node_id: allocation.node_id,
+                            index: allocation.index,
+                            outpoint: allocation.outpoint,
+                            value: allocation.value,
+                            witness_txid: None,
+                            status: AllocationStatus::Pending,
+                        })
+                        .collect();
+                    (outpoint, allocations)
+                })
+                .collect(),
+            history: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+impl From<&Asset> for AssetV0 {
+    fn from(asset: &Asset) -> Self {
+        Self {
+            id: asset.id,
+            ticker: asset.ticker.clone(),
+            name: asset.name.clone(),
+            description: asset.description.clone(),
+            supply: asset.supply,
+            chain: asset.chain.clone(),
+            fractional_bits: asset.fractional_bits,
+            date: asset.date,
+            known_issues: asset.known_issues.clone(),
+            known_inflation: asset.known_inflation.clone(),
+            unknown_inflation: asset.unknown_inflation,
+            known_allocations: asset
+                .known_allocations
+                .iter()
+                .map(|(outpoint, allocations)| {
+                    let allocations = allocations
+                        .iter()
+                        .map(|allocation| AllocationV0 {
+                            node_id: allocation.node_id,
+                            index: allocation.index,
+                            outpoint: allocation.outpoint,
+                            value: allocation.value.clone(),
+                        })
+                        .collect();
+                    (*outpoint, allocations)
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(
     Clone, Getters, PartialEq, Debug, Display, StrictEncode, StrictDecode,
 )]
@@ -243,9 +351,34 @@ pub struct Allocation {
     /// `Asset::known_allocations`
     outpoint: bitcoin::OutPoint,
     value: value::Revealed,
+    /// Transaction which has to be mined before the allocation can be spent.
+    /// `None` means that the allocation is tracked by the transaction
+    /// containing its outpoint (which is the case for genesis allocations).
+    #[cfg_attr(feature = "serde", serde(default))]
+    witness_txid: Option<Txid>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    status: AllocationStatus,
 }
 
 impl Allocation {
+    /// Id of the transaction which status determines the allocation status
+    #[inline]
+    pub fn tracked_txid(&self) -> Txid {
+        self.witness_txid.unwrap_or(self.outpoint.txid)
+    }
+
+    /// Detects whether the allocation can be spent given the minimal number
+    /// of the witness transaction confirmations
+    #[inline]
+    pub fn is_spendable(&self, min_confirmations: u32) -> bool {
+        match self.status {
+            AllocationStatus::Mined { confirmations, .. } => {
+                confirmations >= min_confirmations
+            }
+            _ => false,
+        }
+    }
+
     /// Create an Allocation structure by reading the
     /// corresponding Allocation and AllocationUtxo table entries.
     pub fn from_sql_allocation(
@@ -266,10 +399,140 @@ impl Allocation {
                     &Vec::<u8>::from_hex(&table_value.blinding[..])?[..],
                 )?,
             },
+            witness_txid: match table_value.witness_txid {
+                Some(ref txid) => Some(Txid::from_hex(&txid[..])?),
+                None => None,
+            },
+            status: match table_value.status {
+                0 => AllocationStatus::Pending,
+                1 => AllocationStatus::Mined {
+                    height: table_value.height as u32,
+                    confirmations: table_value.confirmations as u32,
+                },
+                2 => AllocationStatus::Failed,
+                status => Err(SqlCacheError::Generic(format!(
+                    "Unknown allocation status {}",
+                    status
+                )))?,
+            },
         })
     }
 }
 
+/// Lifecycle of an allocation, defined by the status of its witness
+/// transaction
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub enum AllocationStatus {
+    /// Witness transaction is not mined yet
+    Pending,
+
+    /// Witness transaction is mined at a given height and has some number of
+    /// confirmations
+    Mined { height: u32, confirmations: u32 },
+
+    /// Witness transaction was replaced or double-spent and will never be
+    /// mined
+    Failed,
+}
+
+impl Default for AllocationStatus {
+    fn default() -> Self {
+        AllocationStatus::Pending
+    }
+}
+
+impl AllocationStatus {
+    /// Computes new allocation status from the witness transaction status.
+    /// Unknown transactions keep the allocation pending, since the witness
+    /// may be not yet broadcasted.
+    pub fn with_tx_status(self, tx_status: TxStatus) -> Self {
+        match (self, tx_status) {
+            (
+                _,
+                TxStatus::Mined {
+                    height,
+                    confirmations,
+                },
+            ) => AllocationStatus::Mined {
+                height,
+                confirmations,
+            },
+            (_, TxStatus::Conflicted) => AllocationStatus::Failed,
+            (AllocationStatus::Failed, _) => AllocationStatus::Failed,
+            (_, TxStatus::Mempool) | (_, TxStatus::Unknown) => {
+                AllocationStatus::Pending
+            }
+        }
+    }
+}
+
+/// Result of applying witness transaction statuses to the allocations
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct StatusUpdate {
+    /// Whether status of any allocation or history entry has changed
+    pub changed: bool,
+    /// Allocations rolled back since their witness transaction was reorged
+    /// out of the chain or double-spent, with the new transaction status
+    pub rolled_back: Vec<(Allocation, TxStatus)>,
+    /// Transactions which status can't be fetched at the moment; allocations
+    /// tracked by them keep their last known status
+    pub unresolved: BTreeSet<Txid>,
+}
+
+impl StatusUpdate {
+    fn merge(&mut self, other: StatusUpdate) {
+        self.changed |= other.changed;
+        self.rolled_back.extend(other.rolled_back);
+        self.unresolved.extend(other.unresolved);
+    }
+}
+
+/// Asset balance split by the spendability of the allocations
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    Default,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct Balance {
+    /// Sum of allocations with witness transactions having sufficient number
+    /// of confirmations
+    pub spendable: AtomicValue,
+    /// Sum of allocations waiting for witness transaction to be mined or to
+    /// get enough confirmations
+    pub unconfirmed: AtomicValue,
+    /// Sum of allocations which witness transactions has failed
+    pub failed: AtomicValue,
+}
+
 #[derive(
     Clone,
     Copy,
@@ -417,16 +680,22 @@ impl Asset {
         node_id: NodeId,
         index: u16,
         value: value::Revealed,
+        witness_txid: Option<Txid>,
     ) -> bool {
         let new_allocation = Allocation {
             node_id,
             index,
             outpoint,
             value,
+            witness_txid,
+            status: AllocationStatus::Pending,
         };
         let allocations =
             self.known_allocations.entry(outpoint).or_insert(vec![]);
-        if !allocations.contains(&new_allocation) {
+        if !allocations
+            .iter()
+            .any(|a| a.node_id == node_id && a.index == index)
+        {
             allocations.push(new_allocation);
             true
         } else {
@@ -434,6 +703,134 @@ impl Asset {
         }
     }
 
+    /// Updates status of all allocations using information from the provided
+    /// chain status source. Allocations which transaction status can't be
+    /// fetched keep their last known status and are reported in
+    /// [`StatusUpdate::unresolved`].
+    pub fn update_allocation_status(
+        &mut self,
+        chain: &dyn ChainStatus,
+    ) -> StatusUpdate {
+        let txids = self
+            .known_allocations
+            .values()
+            .flatten()
+            .filter(|allocation| allocation.status != AllocationStatus::Failed)
+            .map(Allocation::tracked_txid)
+            .collect::<BTreeSet<_>>();
+        let mut update = StatusUpdate::default();
+        for txid in txids {
+            match chain.tx_status(&txid) {
+                Ok(tx_status) => {
+                    update.merge(self.apply_tx_status(&txid, tx_status))
+                }
+                Err(_) => {
+                    update.unresolved.insert(txid);
+                }
+            }
+        }
+        update
+    }
+
+    /// Updates status of the allocations tracked by the given transaction.
+    /// Allocations are rolled back if the transaction was mined but now is
+    /// not, or if it was double-spent; history entries of the same
+    /// transaction or state transitions are marked as reorged or invalid
+    /// then. Entries marked as reorged become active again once the
+    /// transaction is mined.
+    pub fn apply_tx_status(
+        &mut self,
+        txid: &Txid,
+        tx_status: TxStatus,
+    ) -> StatusUpdate {
+        let mut update = StatusUpdate::default();
+        let mut node_ids = BTreeSet::new();
+        for allocation in self
+            .known_allocations
+            .values_mut()
+            .flatten()
+            .filter(|allocation| allocation.tracked_txid() == *txid)
+        {
+            node_ids.insert(allocation.node_id);
+            let status = allocation.status.with_tx_status(tx_status);
+            if status == allocation.status {
+                continue;
+            }
+            let rolled_back = match (allocation.status, status) {
+                (_, AllocationStatus::Failed) => true,
+                (AllocationStatus::Mined { .. }, AllocationStatus::Pending) => {
+                    true
+                }
+                _ => false,
+            };
+            allocation.status = status;
+            update.changed = true;
+            if rolled_back {
+                update.rolled_back.push((allocation.clone(), tx_status));
+            }
+        }
+
+        let entry_status = match tx_status {
+            TxStatus::Conflicted => EntryStatus::Invalid,
+            TxStatus::Mined { .. } => EntryStatus::Active,
+            _ if update.rolled_back.is_empty() => return update,
+            _ => EntryStatus::Reorged,
+        };
+        for entry in self.history.iter_mut().filter(|entry| {
+            entry.is_movement()
+                && (*entry.witness_txid() == Some(*txid)
+                    || node_ids.contains(entry.node_id()))
+        }) {
+            // Invalid movements never become valid again, and only reorged
+            // ones are restored when the transaction is mined again
+            let status = match (*entry.status(), entry_status) {
+                (EntryStatus::Invalid, _) => continue,
+                (EntryStatus::Active, EntryStatus::Active) => continue,
+                (_, status) => status,
+            };
+            if status != *entry.status() {
+                entry.set_status(status);
+                update.changed = true;
+            }
+        }
+        update
+    }
+
+    /// Computes asset balance from the known allocations
+    pub fn balance(&self, min_confirmations: u32) -> Balance {
+        self.known_allocations.values().flatten().fold(
+            Balance::default(),
+            |mut balance, allocation| {
+                let value = allocation.value.value;
+                if allocation.is_spendable(min_confirmations) {
+                    balance.spendable += value;
+                } else if allocation.status == AllocationStatus::Failed {
+                    balance.failed += value;
+                } else {
+                    balance.unconfirmed += value;
+                }
+                balance
+            },
+        )
+    }
+
+    #[inline]
+    pub fn add_history(&mut self, entry: HistoryEntry) {
+        self.history.push(entry)
+    }
+
+    /// Returns history entries starting from `offset`, limited to `limit`
+    /// items
+    #[inline]
+    pub fn history_page(&self, offset: u32, limit: u32) -> Vec<HistoryEntry> {
+        self.history
+            .iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
     pub fn remove_allocation(
         &mut self,
         outpoint: bitcoin::OutPoint,
@@ -441,23 +838,40 @@ impl Asset {
         index: u16,
         value: value::Revealed,
     ) -> bool {
-        let old_allocation = Allocation {
-            node_id,
-            index,
-            outpoint,
-            value,
-        };
         let allocations =
             self.known_allocations.entry(outpoint).or_insert(vec![]);
-        if let Some(index) =
-            allocations.iter().position(|a| *a == old_allocation)
-        {
+        if let Some(index) = allocations.iter().position(|a| {
+            a.node_id == node_id && a.index == index && a.value == value
+        }) {
             allocations.remove(index);
             true
         } else {
             false
         }
     }
+
+    /// Removes all allocations assigned to the outpoint, returning them
+    pub fn remove_outpoint(
+        &mut self,
+        outpoint: &bitcoin::OutPoint,
+    ) -> Vec<Allocation> {
+        self.known_allocations.remove(outpoint).unwrap_or_default()
+    }
+
+    /// Removes allocation created by the assignment with the given index
+    /// within the given node, returning the removed allocation (if any)
+    pub fn remove_assignment(
+        &mut self,
+        node_id: NodeId,
+        index: u16,
+    ) -> Option<Allocation> {
+        self.known_allocations.values_mut().find_map(|allocations| {
+            allocations
+                .iter()
+                .position(|a| a.node_id == node_id && a.index == index)
+                .map(|pos| allocations.remove(pos))
+        })
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, From, Error)]
@@ -573,6 +987,8 @@ impl TryFrom<Genesis> for Asset {
                                 index: index as u16,
                                 outpoint: outpoint_reveal.into(),
                                 value: assigned_state,
+                                witness_txid: None,
+                                status: AllocationStatus::Pending,
                             })
                     }
                 });
@@ -628,6 +1044,7 @@ impl TryFrom<Genesis> for Asset {
             // we assume that each genesis allocation with revealed amount
             // and known seal (they are always revealed together) belongs to us
             known_allocations,
+            history: vec![],
         })
     }
 }
@@ -0,0 +1,215 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use chrono::{NaiveDateTime, Utc};
+use core::convert::TryFrom;
+use serde::{Deserialize, Serialize};
+
+use lnpbp::bitcoin::Txid;
+use lnpbp::bp::blind::OutpointHash;
+use lnpbp::hex::FromHex;
+use lnpbp::rgb::prelude::*;
+
+use crate::contracts::fungible::cache::models::SqlHistory;
+use crate::contracts::fungible::cache::SqlCacheError;
+
+/// Kind of the asset movement recorded in the asset history
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    FromPrimitive,
+    ToPrimitive,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[repr(u8)]
+pub enum Direction {
+    /// Assets were allocated to us by a genesis we have issued
+    Issued = 0,
+
+    /// Assets were received from some other party with a consignment
+    Received = 1,
+
+    /// Assets were sent to some other party
+    Sent = 2,
+
+    /// Allocation was removed from the cache since its outpoint was spent
+    Forgotten = 3,
+
+    /// Allocation was removed from the cache since its outpoint was spent by
+    /// a transaction unknown to us, so the assets may be lost
+    UnknownSpend = 4,
+}
+impl_enum_strict_encoding!(Direction);
+
+impl TryFrom<i32> for Direction {
+    type Error = SqlCacheError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_i32(value).ok_or(
+            SqlCacheError::Generic(format!(
+                "Unknown history entry direction {}",
+                value
+            )),
+        )
+    }
+}
+
+/// State of the asset movement recorded in the history, defined by its
+/// witness transaction
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    FromPrimitive,
+    ToPrimitive,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+#[repr(u8)]
+pub enum EntryStatus {
+    /// Movement is in effect: its witness transaction is either mined or
+    /// waits to be mined for the first time
+    Active = 0,
+
+    /// Witness transaction was mined, but then reorged out of the chain, so
+    /// the movement waits for it to be mined again
+    Reorged = 1,
+
+    /// Witness transaction was double-spent, so the movement will never
+    /// happen
+    Invalid = 2,
+}
+impl_enum_strict_encoding!(EntryStatus);
+
+impl Default for EntryStatus {
+    fn default() -> Self {
+        EntryStatus::Active
+    }
+}
+
+impl TryFrom<i32> for EntryStatus {
+    type Error = SqlCacheError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_i32(value).ok_or(
+            SqlCacheError::Generic(format!(
+                "Unknown history entry status {}",
+                value
+            )),
+        )
+    }
+}
+
+/// Single record in the asset transfer history
+#[derive(
+    Clone, Getters, PartialEq, Debug, Display, StrictEncode, StrictDecode,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub struct HistoryEntry {
+    /// Id of the genesis or state transition that has produced the movement
+    node_id: NodeId,
+    direction: Direction,
+    /// Blinded seal of the counterparty (for sent assets) or the one we have
+    /// revealed (for received assets), if known
+    seal: Option<OutpointHash>,
+    amount: AtomicValue,
+    /// Transaction committing to the state transition or, for the spent
+    /// allocations, the spending transaction if known; `None` for genesis and
+    /// forgotten allocations
+    witness_txid: Option<Txid>,
+    timestamp: NaiveDateTime,
+    #[cfg_attr(feature = "serde", serde(default))]
+    status: EntryStatus,
+}
+
+impl HistoryEntry {
+    /// Constructs new history entry timestamped with the current time. The
+    /// timestamp is truncated to seconds, since this is the precision kept
+    /// by the strict encoding.
+    pub fn new(
+        direction: Direction,
+        node_id: NodeId,
+        amount: AtomicValue,
+        seal: Option<OutpointHash>,
+        witness_txid: Option<Txid>,
+    ) -> Self {
+        Self {
+            node_id,
+            direction,
+            seal,
+            amount,
+            witness_txid,
+            timestamp: NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0),
+            status: EntryStatus::Active,
+        }
+    }
+
+    /// Detects whether the entry records movement made by the genesis or
+    /// state transition, rather than removal of an allocation
+    pub fn is_movement(&self) -> bool {
+        matches!(
+            self.direction,
+            Direction::Issued | Direction::Received | Direction::Sent
+        )
+    }
+
+    #[inline]
+    pub fn set_status(&mut self, status: EntryStatus) {
+        self.status = status
+    }
+
+    /// Create a HistoryEntry structure from reading the corresponding
+    /// History table entry in the database.
+    pub fn from_sql_history(
+        table_value: &SqlHistory,
+    ) -> Result<Self, SqlCacheError> {
+        Ok(Self {
+            node_id: NodeId::from_hex(&table_value.node_id[..])?,
+            direction: Direction::try_from(table_value.direction)?,
+            seal: match table_value.seal {
+                Some(ref seal) => Some(OutpointHash::from_hex(&seal[..])?),
+                None => None,
+            },
+            amount: table_value.amount as AtomicValue,
+            witness_txid: match table_value.witness_txid {
+                Some(ref txid) => Some(Txid::from_hex(&txid[..])?),
+                None => None,
+            },
+            timestamp: table_value.timestamp,
+            status: EntryStatus::try_from(table_value.status)?,
+        })
+    }
+}
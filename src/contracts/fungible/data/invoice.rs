@@ -17,9 +17,12 @@ use url::Url;
 
 use lnpbp::bitcoin;
 use lnpbp::bitcoin::Address;
+use lnpbp::bp;
 use lnpbp::bp::blind::OutpointHash;
 use lnpbp::rgb::{Bech32, ContractId, FromBech32, ToBech32};
 
+use crate::error::NetworkMismatch;
+
 #[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
 #[display(Debug)]
 pub enum Error {
@@ -98,6 +101,23 @@ impl FromStr for Outpoint {
     }
 }
 
+impl Invoice {
+    /// Checks that the invoice may be paid on the given network. Invoices
+    /// with blinded UTXOs do not contain network information and are always
+    /// accepted.
+    pub fn check_network(
+        &self,
+        network: &bp::Chain,
+    ) -> Result<(), NetworkMismatch> {
+        match &self.outpoint {
+            Outpoint::BlindedUtxo(_) => Ok(()),
+            Outpoint::Address(address) => {
+                NetworkMismatch::check_address(network, address)
+            }
+        }
+    }
+}
+
 impl FromStr for Invoice {
     type Err = Error;
 
@@ -12,13 +12,17 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 mod asset;
+mod history;
 mod invoice;
 mod outcoins;
 pub mod schema;
 
+pub(crate) use asset::AssetV0;
 pub use asset::{
-    AccountingAmount, AccountingValue, Allocation, Asset, Issue, Supply,
+    AccountingAmount, AccountingValue, Allocation, AllocationStatus, Asset,
+    Balance, Issue, StatusUpdate, Supply,
 };
+pub use history::{Direction, EntryStatus, HistoryEntry};
 pub use invoice::{
     Error as InvoiceError, Invoice, Outpoint, OutpointDescriptor,
 };
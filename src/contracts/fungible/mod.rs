@@ -14,16 +14,21 @@
 mod config;
 mod data;
 mod processor;
+#[cfg(feature = "node")]
 mod runtime;
+mod watcher;
 
 pub(self) mod cache;
 
 pub use data::{
-    schema, AccountingAmount, Allocation, Asset, Error, Invoice, InvoiceError,
-    Issue, Outcoincealed, Outcoins, Outpoint, OutpointDescriptor, Supply,
+    schema, AccountingAmount, Allocation, AllocationStatus, Asset, Balance,
+    Direction, EntryStatus, Error, HistoryEntry, Invoice, InvoiceError, Issue,
+    Outcoincealed, Outcoins, Outpoint, OutpointDescriptor, StatusUpdate,
+    Supply,
 };
 
 pub use config::{Config, Opts};
+#[cfg(feature = "node")]
 pub use runtime::{main_with_config, Runtime};
 
 pub use cache::{CacheError, FileCacheError, SqlCacheError};
@@ -13,9 +13,12 @@
 
 use ::core::borrow::Borrow;
 use ::core::convert::TryFrom;
+use ::std::collections::BTreeSet;
 use ::std::path::PathBuf;
+use ::std::time::{Duration, Instant};
 
-use lnpbp::bitcoin::OutPoint;
+use lnpbp::bitcoin::{OutPoint, Txid};
+use lnpbp::bp::blind::OutpointReveal;
 use lnpbp::client_side_validation::Conceal;
 use lnpbp::lnp::presentation::Encode;
 use lnpbp::lnp::zmqsocket::ZmqType;
@@ -23,26 +26,44 @@ use lnpbp::lnp::{
     session, transport, CreateUnmarshaller, PlainTranscoder, Session,
     Unmarshall, Unmarshaller,
 };
-use lnpbp::rgb::{Assignments, Consignment, ContractId, Genesis, Node};
+use lnpbp::rgb::{
+    Assignments, Consignment, ContractId, Genesis, Node, NodeId, OwnedState,
+};
 
-use super::cache::{Cache, FileCache, FileCacheConfig};
+use super::cache::{Cache, CacheError, FileCache, FileCacheConfig};
 use super::schema::OwnedRightsType;
-use super::{schema, Asset, Config, IssueStructure, Processor};
-use crate::api::stash::MergeRequest;
+use super::watcher::SpendWatcher;
+use super::{
+    schema, AccountingAmount, Asset, Config, Direction, HistoryEntry,
+    IssueStructure, Processor, StatusUpdate,
+};
+use crate::api::stash::{MergeRequest, ValidateRequest};
 use crate::api::{
     self,
-    fungible::{AcceptApi, Issue, Request, TransferApi},
+    event::{AllocationEvent, MergeEvent, RollbackEvent, SpendEvent},
+    fungible::{AcceptApi, HistoryApi, Issue, Request, TransferApi},
+    hello::{self, Capabilities, Hello},
     reply,
     stash::ConsignRequest,
-    Reply,
+    Event, Reply,
 };
+use crate::auth::{self, Role};
+use crate::constants::RGB_PROTOCOL_VERSION;
 use crate::error::{
-    ApiErrorType, BootstrapError, RuntimeError, ServiceError,
-    ServiceErrorDomain, ServiceErrorSource,
+    ApiErrorType, BootstrapError, ConfigError, NetworkMismatch, RuntimeError,
+    ServiceError, ServiceErrorDomain, ServiceErrorSource,
 };
 use crate::service::TryService;
+use crate::stash::resolvers::{CompositeTxResolver, ResolverUrl};
+use crate::util::chain::{ChainStatus, TxStatus};
+use crate::util::metrics::{Exporter, Metrics};
+use crate::util::shutdown::{self, Shutdown};
 use crate::DataFormat;
 
+/// Interval for checking whether shutdown was requested while waiting for RPC
+/// requests and stash events, in milliseconds
+const SHUTDOWN_POLL_INTERVAL: i64 = 500;
+
 pub struct Runtime {
     /// Original configuration object
     config: Config,
@@ -68,11 +89,27 @@ pub struct Runtime {
     /// Processor instance: handles business logic outside of stash scope
     processor: Processor,
 
+    /// Source of information about witness transactions status, used for
+    /// tracking allocation lifecycle
+    chain: Box<dyn ChainStatus + Send>,
+
+    /// Detects spends of the allocated outpoints
+    watcher: SpendWatcher,
+
     /// Unmarshaller instance used for parsing RPC request
     unmarshaller: Unmarshaller<Request>,
 
     /// Unmarshaller instance used for parsing RPC request
     reply_unmarshaller: Unmarshaller<Reply>,
+
+    /// Unmarshaller instance used for parsing stash events
+    event_unmarshaller: Unmarshaller<Event>,
+
+    /// Tracks shutdown signals and requests
+    shutdown: Shutdown,
+
+    /// Statistics of processed requests
+    metrics: Metrics,
 }
 
 impl Runtime {
@@ -96,33 +133,60 @@ impl Runtime {
             err
         })?;
 
-        let session_rpc = session::Raw::with_zmq_unencrypted(
+        let session_rpc = config.auth.bind(
             ZmqType::Rep,
             &config.rpc_endpoint,
-            None,
-            None,
+            "fungibled",
         )?;
 
-        let session_pub = session::Raw::with_zmq_unencrypted(
+        let session_pub = config.auth.bind(
             ZmqType::Pub,
             &config.pub_endpoint,
-            None,
-            None,
+            "fungibled",
         )?;
 
-        let stash_rpc = session::Raw::with_zmq_unencrypted(
-            ZmqType::Req,
-            &config.stash_rpc,
-            None,
-            None,
-        )?;
+        // Stash daemon is a part of the same node and shares its keys
+        let stash_auth = config.auth.client();
 
-        let stash_sub = session::Raw::with_zmq_unencrypted(
-            ZmqType::Sub,
-            &config.stash_sub,
-            None,
-            None,
-        )?;
+        let stash_rpc = stash_auth.connect(ZmqType::Req, &config.stash_rpc)?;
+
+        let stash_sub = stash_auth.connect(ZmqType::Sub, &config.stash_sub)?;
+        stash_sub.as_socket().set_subscribe(&[])?;
+
+        // Remote data sources are not contacted until the first request, so
+        // the daemon starts even if they are temporarily unavailable
+        let sources = config
+            .resolvers
+            .iter()
+            .map(|url| {
+                ResolverUrl::parse(url, &config.network)
+                    .map_err(|err| ConfigError::InvalidParam {
+                        param: s!("resolver"),
+                        value: url.clone(),
+                        details: err.to_string(),
+                    })?
+                    .connect()
+            })
+            .collect::<Result<_, _>>()?;
+        let chain: Box<dyn ChainStatus + Send> = Box::new(
+            CompositeTxResolver::new(sources, config.resolver_quorum)?,
+        );
+        let watcher = SpendWatcher::new(
+            Duration::from_secs(config.watch_interval),
+            config.min_confirmations,
+        );
+
+        let shutdown = Shutdown::listen()?;
+
+        if let Some(addr) = config.metrics_endpoint {
+            Exporter {
+                daemon: "fungibled",
+                auth: config.auth.client(),
+                endpoint: config.rpc_endpoint.clone(),
+                request: Request::Status().encode()?,
+            }
+            .spawn(addr)?;
+        }
 
         Ok(Self {
             config,
@@ -132,8 +196,13 @@ impl Runtime {
             stash_sub,
             cacher,
             processor,
+            chain,
+            watcher,
             unmarshaller: Request::create_unmarshaller(),
             reply_unmarshaller: Reply::create_unmarshaller(),
+            event_unmarshaller: Event::create_unmarshaller(),
+            shutdown,
+            metrics: Metrics::default(),
         })
     }
 }
@@ -151,23 +220,96 @@ impl TryService for Runtime {
             )
         })?;
 
-        loop {
+        while !self.shutdown.is_requested() {
             match self.run().await {
-                Ok(_) => debug!("API request processing complete"),
+                Ok(_) => trace!("API request processing complete"),
                 Err(err) => {
                     error!("Error processing API request: {}", err);
                     Err(err)?;
                 }
             }
         }
+        self.finalize();
+        Ok(())
     }
 }
 
 impl Runtime {
     async fn run(&mut self) -> Result<(), RuntimeError> {
-        trace!("Awaiting for ZMQ RPC requests...");
-        let raw = self.session_rpc.recv_raw_message()?;
-        let reply = self.rpc_process(raw).await.unwrap_or_else(|err| err);
+        trace!("Awaiting for ZMQ RPC requests or stash events...");
+        // Poll items are not `Send`, so they must be dropped before awaiting
+        let (rpc_ready, stash_ready) = {
+            let mut items = [
+                self.session_rpc.as_socket().as_poll_item(zmq::POLLIN),
+                self.stash_sub.as_socket().as_poll_item(zmq::POLLIN),
+            ];
+            zmq::poll(&mut items, SHUTDOWN_POLL_INTERVAL).map_err(|err| {
+                RuntimeError::zmq_request(
+                    &self.config.rpc_endpoint.zmq_socket_string(),
+                    err,
+                )
+            })?;
+            (items[0].is_readable(), items[1].is_readable())
+        };
+
+        if stash_ready {
+            self.process_stash_event().await?;
+        }
+        if rpc_ready {
+            self.process_rpc().await?;
+        }
+        if self.watcher.is_due() {
+            // Rollbacks are processed first, so allocations spent by a
+            // reorged-out transaction are not taken as lost
+            if let Err(err) = self.process_status_updates() {
+                error!("Unable to update allocation status: {}", err);
+            }
+            if let Err(err) = self.process_spends().await {
+                error!("Unable to process spent allocations: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps asset cache consistent with the stash, which may be updated by
+    /// other clients directly. Failures to process an event are logged and
+    /// do not stop the service.
+    async fn process_stash_event(&mut self) -> Result<(), RuntimeError> {
+        let raw = self.stash_sub.recv_raw_message()?;
+        let event = match self.event_unmarshaller.unmarshall(&raw) {
+            Ok(event) => event,
+            Err(err) => {
+                error!("Error unmarshalling stash event: {}", err);
+                return Ok(());
+            }
+        };
+        debug!("Received stash event: {}", event);
+        let result = match &*event {
+            Event::ConsignmentMerged(merge) => {
+                self.read_merge(merge.clone()).await
+            }
+            Event::ForgetRequested(removal_list) => {
+                self.forget_assignments(removal_list)
+            }
+            Event::WitnessConfirmed(witness)
+            | Event::WitnessReorged(witness)
+            | Event::WitnessConflicted(witness) => self.apply_witness_status(
+                &witness.contract_ids,
+                &witness.txid,
+                witness.status,
+            ),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            error!("Unable to update asset cache from stash event: {}", err);
+        }
+        Ok(())
+    }
+
+    async fn process_rpc(&mut self) -> Result<(), RuntimeError> {
+        let (raw, client) = auth::recv_authenticated(&mut self.session_rpc)?;
+        let role = self.config.auth.role(client.as_ref());
+        let reply = self.rpc_process(raw, role).await.unwrap_or_else(|err| err);
         trace!("Preparing ZMQ RPC reply: {:?}", reply);
         let data = reply.encode()?;
         trace!(
@@ -178,7 +320,47 @@ impl Runtime {
         Ok(())
     }
 
-    async fn rpc_process(&mut self, raw: Vec<u8>) -> Result<Reply, Reply> {
+    /// Saves asset cache, closes all sockets and removes IPC socket files.
+    /// Failures are logged, so they do not prevent the rest of the procedure.
+    fn finalize(self) {
+        info!("Shutting down fungibled");
+        if let Err(err) = self.cacher.save() {
+            error!("Unable to save asset cache: {}", err);
+        }
+        let Runtime {
+            config,
+            session_rpc,
+            session_pub,
+            stash_rpc,
+            stash_sub,
+            ..
+        } = self;
+        drop(session_rpc);
+        drop(session_pub);
+        drop(stash_rpc);
+        drop(stash_sub);
+        shutdown::remove_ipc_file(&config.rpc_endpoint);
+        shutdown::remove_ipc_file(&config.pub_endpoint);
+    }
+
+    /// Publishes event to the subscribers. Failure to publish an event must
+    /// not fail the request which has caused it, so we just log the error.
+    fn publish(&mut self, event: Event) {
+        trace!("Publishing event {}", event);
+        let result = event
+            .encode()
+            .map_err(RuntimeError::from)
+            .and_then(|data| Ok(self.session_pub.send_raw_message(&data)?));
+        if let Err(err) = result {
+            error!("Unable to publish event: {}", err);
+        }
+    }
+
+    async fn rpc_process(
+        &mut self,
+        raw: Vec<u8>,
+        role: Role,
+    ) -> Result<Reply, Reply> {
         trace!("Got {} bytes over ZMQ RPC: {:?}", raw.len(), raw);
         let message = &*self.unmarshaller.unmarshall(&raw).map_err(|err| {
             error!("Error unmarshalling the data: {}", err);
@@ -188,12 +370,16 @@ impl Runtime {
             )
         })?;
         debug!("Received ZMQ RPC request: {:?}", message);
-        Ok(match message {
+        role.check(message.required_role(), message.name())
+            .map_err(|err| ServiceError::contract(err, "fungible"))?;
+        let started = Instant::now();
+        let result = match message {
+            Request::Hello(hello) => self.rpc_hello(hello).await,
+            Request::Shutdown() => self.rpc_shutdown().await,
+            Request::Status() => self.rpc_status().await,
             Request::Issue(issue) => self.rpc_issue(issue).await,
             Request::Transfer(transfer) => self.rpc_transfer(transfer).await,
-            Request::Validate(consignment) => {
-                self.rpc_validate(consignment).await
-            }
+            Request::Validate(validate) => self.rpc_validate(validate).await,
             Request::Accept(accept) => self.rpc_accept(accept).await,
             Request::Forget(outpoint) => self.rpc_forget(outpoint).await,
             Request::ImportAsset(genesis) => {
@@ -207,8 +393,59 @@ impl Runtime {
             Request::Allocations(contract_id) => {
                 self.rpc_allocations(*contract_id).await
             }
+            Request::History(history) => self.rpc_history(history).await,
+            Request::Balance(contract_id) => {
+                self.rpc_balance(*contract_id).await
+            }
+        };
+        self.metrics.record(
+            message.name(),
+            started.elapsed(),
+            result.as_ref().err().map(ToString::to_string),
+        );
+        Ok(result.map_err(|err| ServiceError::contract(err, "fungible"))?)
+    }
+
+    async fn rpc_hello(
+        &mut self,
+        hello: &Hello,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!(
+            "Got HELLO from {} using protocol version {}",
+            hello.agent, hello.protocol_version
+        );
+        if hello.protocol_version != RGB_PROTOCOL_VERSION {
+            warn!(
+                "Client {} uses protocol version {} instead of {}",
+                hello.agent, hello.protocol_version, RGB_PROTOCOL_VERSION
+            );
         }
-        .map_err(|err| ServiceError::contract(err, "fungible"))?)
+        Ok(Reply::Capabilities(Capabilities {
+            protocol_version: RGB_PROTOCOL_VERSION,
+            agent: hello::agent("fungibled"),
+            requests: Request::NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            schemata: vec![schema::schema().schema_id()],
+            network: self.config.network.clone(),
+            storage: s!("stashd"),
+            cache: format!("file-{}", self.config.format),
+        }))
+    }
+
+    async fn rpc_shutdown(&mut self) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got SHUTDOWN");
+        self.shutdown.request();
+        Ok(Reply::Success)
+    }
+
+    async fn rpc_status(&mut self) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got STATUS");
+        let mut status = self.metrics.status(hello::agent("fungibled"));
+        status.cache_size = self.cacher.assets()?.len() as u64;
+        status.chain = Some(self.chain.connectivity());
+        Ok(Reply::Status(status))
     }
 
     async fn rpc_issue(
@@ -230,7 +467,7 @@ impl Runtime {
             },
         };
 
-        let (asset, genesis) = self.processor.issue(
+        let (mut asset, genesis) = self.processor.issue(
             self.config.network.clone(),
             issue.ticker.clone(),
             issue.title.clone(),
@@ -241,9 +478,37 @@ impl Runtime {
             vec![],
         )?;
 
+        let issued = asset
+            .known_allocations()
+            .values()
+            .flatten()
+            .map(|allocation| {
+                HistoryEntry::new(
+                    Direction::Issued,
+                    *allocation.node_id(),
+                    allocation.value().value,
+                    None,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+        for entry in issued {
+            asset.add_history(entry);
+        }
+
+        let contract_id = *asset.id();
+        let events = asset
+            .known_allocations()
+            .values()
+            .flatten()
+            .map(|allocation| AllocationEvent::with(contract_id, allocation))
+            .collect::<Vec<_>>();
+
         self.import_asset(asset, genesis).await?;
 
-        // TODO: Send push request to client informing about cache update
+        for event in events {
+            self.publish(Event::AllocationAdded(event));
+        }
 
         Ok(Reply::Success)
     }
@@ -269,13 +534,14 @@ impl Runtime {
             transfer.theirs.clone(),
         )?;
         debug!("State transition: {}", transition);
+        let node_id = transition.node_id();
 
         trace!("Requesting consignment from stash daemon");
         let reply = self
             .consign(ConsignRequest {
                 contract_id: transfer.contract_id,
                 inputs: transfer.inputs.clone(),
-                transition,
+                transition: transition.clone(),
                 // TODO: Collect blank state transitions and pass it here
                 other_transition_ids: bmap![],
                 outpoints: transfer
@@ -287,16 +553,78 @@ impl Runtime {
             })
             .await?;
 
+        if let Reply::Transfer(ref consigned) = reply {
+            let witness_txid = consigned.psbt.global.unsigned_tx.txid();
+            let mut events = vec![];
+
+            // Change allocations are kept pending until the witness
+            // transaction gets mined
+            for variant in
+                transition.owned_rights_by_type(*OwnedRightsType::Assets)
+            {
+                for (index, assignment) in
+                    variant.to_discrete_state().into_iter().enumerate()
+                {
+                    if let OwnedState::Revealed {
+                        seal_definition,
+                        assigned_state,
+                    } = assignment
+                    {
+                        let outpoint =
+                            seal_definition.outpoint_reveal(witness_txid);
+                        let amount = assigned_state.value;
+                        if asset.add_allocation(
+                            outpoint.into(),
+                            node_id,
+                            index as u16,
+                            assigned_state,
+                            Some(witness_txid),
+                        ) {
+                            events.push(AllocationEvent {
+                                contract_id: transfer.contract_id,
+                                outpoint: outpoint.into(),
+                                node_id,
+                                index: index as u16,
+                                amount,
+                            });
+                        }
+                    }
+                }
+            }
+
+            for theirs in &transfer.theirs {
+                asset.add_history(HistoryEntry::new(
+                    Direction::Sent,
+                    node_id,
+                    AccountingAmount::transmutate(
+                        *asset.fractional_bits(),
+                        theirs.coins,
+                    ),
+                    Some(theirs.seal_confidential),
+                    Some(witness_txid),
+                ));
+            }
+            self.cacher.add_asset(asset)?;
+
+            for event in events {
+                self.publish(Event::AllocationAdded(event));
+            }
+        }
+
         Ok(reply)
     }
 
     async fn rpc_validate(
         &mut self,
-        consignment: &Consignment,
+        request: &ValidateRequest,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got VALIDATE");
-        self.validate(consignment.clone()).await?;
-        Ok(Reply::Success)
+        NetworkMismatch::check(
+            &self.config.network,
+            request.consignment.genesis.chain(),
+        )?;
+        // Validation result is published by the stash daemon
+        Ok(self.validate(request.clone()).await?)
     }
 
     async fn rpc_accept(
@@ -304,6 +632,10 @@ impl Runtime {
         accept: &AcceptApi,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got ACCEPT");
+        NetworkMismatch::check(
+            &self.config.network,
+            accept.consignment.genesis.chain(),
+        )?;
         Ok(self.accept(accept.clone()).await?)
     }
 
@@ -342,11 +674,36 @@ impl Runtime {
         Ok(Reply::Allocations(data))
     }
 
+    async fn rpc_history(
+        &mut self,
+        history: &HistoryApi,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got HISTORY");
+        let data = self.cacher.asset_history(
+            history.contract_id,
+            history.offset,
+            history.limit,
+        )?;
+        Ok(Reply::History(data))
+    }
+
+    async fn rpc_balance(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got BALANCE");
+        // Allocation status is refreshed with each watch interval, so balance
+        // requests are served from the cache without querying the chain
+        let asset = self.cacher.asset(contract_id)?;
+        Ok(Reply::Balance(asset.balance(self.config.min_confirmations)))
+    }
+
     async fn rpc_import_asset(
         &mut self,
         genesis: &Genesis,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got IMPORT_ASSET");
+        NetworkMismatch::check(&self.config.network, genesis.chain())?;
         self.import_asset(Asset::try_from(genesis.clone())?, genesis.clone())
             .await?;
         Ok(Reply::Success)
@@ -380,7 +737,12 @@ impl Runtime {
             .stash_req_rep(api::stash::Request::AddGenesis(genesis))
             .await?
         {
-            Reply::Success => Ok(self.cacher.add_asset(asset)?),
+            Reply::Success => {
+                let contract_id = *asset.id();
+                let added = self.cacher.add_asset(asset)?;
+                self.publish(Event::AssetImported(contract_id));
+                Ok(added)
+            }
             _ => Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply)),
         }
     }
@@ -414,14 +776,19 @@ impl Runtime {
 
     async fn validate(
         &mut self,
-        consignment: Consignment,
+        request: ValidateRequest,
     ) -> Result<Reply, ServiceErrorDomain> {
         let reply = self
-            .stash_req_rep(api::stash::Request::Validate(consignment))
+            .stash_req_rep(api::stash::Request::Validate(ValidateRequest {
+                min_confirmations: request
+                    .min_confirmations
+                    .or(Some(self.config.min_confirmations)),
+                ..request
+            }))
             .await?;
 
         match reply {
-            Reply::Success | Reply::Failure(_) => Ok(reply),
+            Reply::ValidationStatus(_) => Ok(reply),
             _ => Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply)),
         }
     }
@@ -434,57 +801,118 @@ impl Runtime {
             .stash_req_rep(api::stash::Request::Merge(MergeRequest {
                 consignment: accept.consignment.clone(),
                 reveal_outpoints: accept.reveal_outpoints.clone(),
+                min_confirmations: Some(self.config.min_confirmations),
             }))
             .await?;
         if let Reply::Success = reply {
-            let asset_id = accept.consignment.genesis.contract_id();
-            let mut asset = if self.cacher.has_asset(asset_id)? {
-                self.cacher.asset(asset_id)?.clone()
-            } else {
-                Asset::try_from(accept.consignment.genesis)?
-            };
-
-            for (_, transition) in &accept.consignment.state_transitions {
-                let set =
-                    transition.owned_rights_by_type(*OwnedRightsType::Assets);
-                for variant in set {
-                    if let Assignments::DiscreteFiniteField(set) = variant {
-                        for (index, assignment) in set.into_iter().enumerate() {
-                            if let Some(seal) =
-                                accept.reveal_outpoints.iter().find(|op| {
-                                    op.conceal()
-                                        == assignment
-                                            .seal_definition_confidential()
-                                })
+            self.merge_consignment(
+                &accept.consignment,
+                &accept.reveal_outpoints,
+            )?;
+            Ok(reply)
+        } else if let Reply::Failure(_) = &reply {
+            Ok(reply)
+        } else {
+            Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply))
+        }
+    }
+
+    /// Reads seals revealed by the consignment merged into the stash, which
+    /// are not published with the merge event, and updates asset cache with
+    /// the allocations assigned to them
+    async fn read_merge(
+        &mut self,
+        event: MergeEvent,
+    ) -> Result<(), ServiceErrorDomain> {
+        let reply = self
+            .stash_req_rep(api::stash::Request::ReadMerge(event))
+            .await?;
+        match reply {
+            Reply::Merge(merge)
+                if merge.consignment.genesis.schema_id()
+                    == schema::schema().schema_id() =>
+            {
+                self.merge_consignment(
+                    &merge.consignment,
+                    &merge.reveal_outpoints,
+                )
+            }
+            Reply::Merge(_) => Ok(()),
+            _ => Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply)),
+        }
+    }
+
+    /// Updates asset cache with the allocations from the consignment which
+    /// were assigned to the seals we are able to reveal. Since allocations
+    /// are identified by their assignment, merging the same consignment
+    /// multiple times does not change the cache.
+    fn merge_consignment(
+        &mut self,
+        consignment: &Consignment,
+        reveal_outpoints: &Vec<OutpointReveal>,
+    ) -> Result<(), ServiceErrorDomain> {
+        let asset_id = consignment.genesis.contract_id();
+        let mut events = vec![];
+        let mut asset = if self.cacher.has_asset(asset_id)? {
+            self.cacher.asset(asset_id)?.clone()
+        } else {
+            events.push(Event::AssetImported(asset_id));
+            Asset::try_from(consignment.genesis.clone())?
+        };
+
+        for (anchor, transition) in &consignment.state_transitions {
+            let set = transition.owned_rights_by_type(*OwnedRightsType::Assets);
+            for variant in set {
+                if let Assignments::DiscreteFiniteField(set) = variant {
+                    for (index, assignment) in set.into_iter().enumerate() {
+                        if let Some(seal) = reveal_outpoints.iter().find(|op| {
+                            op.conceal()
+                                == assignment.seal_definition_confidential()
+                        }) {
+                            if let Some(assigned_state) =
+                                assignment.assigned_state()
                             {
-                                if let Some(assigned_state) =
-                                    assignment.assigned_state()
-                                {
-                                    asset.add_allocation(
-                                        seal.clone().into(),
+                                if asset.add_allocation(
+                                    seal.clone().into(),
+                                    transition.node_id(),
+                                    index as u16,
+                                    assigned_state.clone(),
+                                    Some(anchor.txid),
+                                ) {
+                                    asset.add_history(HistoryEntry::new(
+                                        Direction::Received,
                                         transition.node_id(),
-                                        index as u16,
-                                        assigned_state.clone(),
-                                    );
-                                } else {
-                                    Err(ServiceErrorDomain::Internal(
-                                        "Consignment structure is broken"
-                                            .to_string(),
-                                    ))?
+                                        assigned_state.value,
+                                        Some(seal.conceal()),
+                                        Some(anchor.txid),
+                                    ));
+                                    events.push(Event::AllocationAdded(
+                                        AllocationEvent {
+                                            contract_id: asset_id,
+                                            outpoint: seal.clone().into(),
+                                            node_id: transition.node_id(),
+                                            index: index as u16,
+                                            amount: assigned_state.value,
+                                        },
+                                    ));
                                 }
+                            } else {
+                                Err(ServiceErrorDomain::Internal(
+                                    "Consignment structure is broken"
+                                        .to_string(),
+                                ))?
                             }
                         }
                     }
                 }
             }
+        }
 
-            self.cacher.add_asset(asset)?;
-            Ok(reply)
-        } else if let Reply::Failure(_) = &reply {
-            Ok(reply)
-        } else {
-            Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply))
+        self.cacher.add_asset(asset)?;
+        for event in events {
+            self.publish(event);
         }
+        Ok(())
     }
 
     async fn forget(
@@ -492,6 +920,7 @@ impl Runtime {
         outpoint: OutPoint,
     ) -> Result<Reply, ServiceErrorDomain> {
         let mut removal_list = Vec::<_>::new();
+        let mut events = vec![];
         let assets = self
             .cacher
             .assets()?
@@ -500,21 +929,35 @@ impl Runtime {
             .collect::<Vec<_>>();
         for asset in assets {
             let mut asset = asset.clone();
-            for allocation in asset
-                .clone()
-                .allocations(&outpoint)
-                .ok_or(ServiceErrorDomain::Cache)?
-            {
-                asset.remove_allocation(
+            let allocations = asset.allocations(&outpoint).cloned().ok_or(
+                CacheError::NotFound {
+                    id: outpoint.to_string(),
+                },
+            )?;
+            for allocation in allocations {
+                if asset.remove_allocation(
                     outpoint,
                     *allocation.node_id(),
                     *allocation.index(),
                     allocation.value().clone(),
-                );
+                ) {
+                    asset.add_history(HistoryEntry::new(
+                        Direction::Forgotten,
+                        *allocation.node_id(),
+                        allocation.value().value,
+                        None,
+                        None,
+                    ));
+                    events
+                        .push(AllocationEvent::with(*asset.id(), &allocation));
+                }
                 removal_list.push((*allocation.node_id(), *allocation.index()));
             }
             self.cacher.add_asset(asset)?;
         }
+        for event in events {
+            self.publish(Event::AllocationRemoved(event));
+        }
         if removal_list.is_empty() {
             return Ok(Reply::Nothing);
         }
@@ -529,6 +972,199 @@ impl Runtime {
         }
     }
 
+    /// Updates status of the allocations of all assets from the chain, rolling
+    /// back the ones which witness transactions were reorged out of the
+    /// chain or double-spent
+    fn process_status_updates(&mut self) -> Result<(), ServiceErrorDomain> {
+        let assets = self
+            .cacher
+            .assets()?
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        for mut asset in assets {
+            let update = asset.update_allocation_status(self.chain.as_ref());
+            Self::report_unresolved(asset.id(), &update);
+            if !update.changed {
+                continue;
+            }
+            let contract_id = *asset.id();
+            self.cacher.add_asset(asset)?;
+            self.publish_rollbacks(contract_id, update);
+        }
+        Ok(())
+    }
+
+    /// Applies status of the witness transaction reported by the stash to
+    /// the allocations of the given assets
+    fn apply_witness_status(
+        &mut self,
+        contract_ids: &BTreeSet<ContractId>,
+        txid: &Txid,
+        status: TxStatus,
+    ) -> Result<(), ServiceErrorDomain> {
+        for contract_id in contract_ids {
+            if !self.cacher.has_asset(*contract_id)? {
+                continue;
+            }
+            let mut asset = self.cacher.asset(*contract_id)?.clone();
+            let update = asset.apply_tx_status(txid, status);
+            if !update.changed {
+                continue;
+            }
+            self.cacher.add_asset(asset)?;
+            self.publish_rollbacks(*contract_id, update);
+        }
+        Ok(())
+    }
+
+    /// Logs transactions which status can't be fetched from the chain, so
+    /// allocations tracked by them are reported with their last known status
+    fn report_unresolved(contract_id: &ContractId, update: &StatusUpdate) {
+        for txid in &update.unresolved {
+            warn!(
+                "Unable to get status of transaction {} for asset {}; \
+                 keeping last known status of its allocations",
+                txid, contract_id
+            );
+        }
+    }
+
+    /// Notifies subscribers about the rolled back allocations, so they can
+    /// broadcast the reorged-out transactions again or re-negotiate the
+    /// double-spent transfers
+    fn publish_rollbacks(
+        &mut self,
+        contract_id: ContractId,
+        update: StatusUpdate,
+    ) {
+        for (allocation, status) in update.rolled_back {
+            warn!(
+                "Allocation {} of asset {} is rolled back: witness {} is {}",
+                allocation.outpoint(),
+                contract_id,
+                allocation.tracked_txid(),
+                status
+            );
+            self.publish(Event::AllocationRolledBack(RollbackEvent {
+                allocation: AllocationEvent::with(contract_id, &allocation),
+                txid: allocation.tracked_txid(),
+                status,
+            }));
+        }
+    }
+
+    /// Removes allocations which outpoints were spent from the cache and the
+    /// stash, notifying subscribers. Spends by transactions not known to us
+    /// are recorded in the asset history, since they may mean asset loss.
+    async fn process_spends(&mut self) -> Result<(), ServiceErrorDomain> {
+        let assets = self.cacher.assets()?;
+        let spends = self.watcher.check(&assets, self.chain.as_ref());
+        if spends.is_empty() {
+            return Ok(());
+        }
+        let assets = assets.into_iter().cloned().collect::<Vec<_>>();
+
+        let mut removal_list = vec![];
+        let mut events = vec![];
+        for mut asset in assets {
+            let mut changed = false;
+            for spend in &spends {
+                for allocation in asset.remove_outpoint(&spend.outpoint) {
+                    changed = true;
+                    let direction = if spend.known {
+                        Direction::Forgotten
+                    } else {
+                        warn!(
+                            "Outpoint {} holding {} of asset {} was spent by \
+                             an unknown transaction; assets may be lost",
+                            spend.outpoint,
+                            allocation.value().value,
+                            asset.id()
+                        );
+                        Direction::UnknownSpend
+                    };
+                    asset.add_history(HistoryEntry::new(
+                        direction,
+                        *allocation.node_id(),
+                        allocation.value().value,
+                        None,
+                        Some(spend.txid),
+                    ));
+                    let allocation_event =
+                        AllocationEvent::with(*asset.id(), &allocation);
+                    events.push(Event::AllocationRemoved(
+                        allocation_event.clone(),
+                    ));
+                    events.push(Event::AllocationSpent(SpendEvent {
+                        allocation: allocation_event,
+                        spending_txid: spend.txid,
+                        known: spend.known,
+                    }));
+                    removal_list
+                        .push((*allocation.node_id(), *allocation.index()));
+                }
+            }
+            if changed {
+                self.cacher.add_asset(asset)?;
+            }
+        }
+        for event in events {
+            self.publish(event);
+        }
+        if removal_list.is_empty() {
+            return Ok(());
+        }
+
+        match self
+            .stash_req_rep(api::stash::Request::Forget(removal_list))
+            .await?
+        {
+            Reply::Success => Ok(()),
+            _ => Err(ServiceErrorDomain::Api(ApiErrorType::UnexpectedReply)),
+        }
+    }
+
+    /// Removes allocations created by the listed assignments from all known
+    /// assets
+    fn forget_assignments(
+        &mut self,
+        removal_list: &Vec<(NodeId, u16)>,
+    ) -> Result<(), ServiceErrorDomain> {
+        let assets = self
+            .cacher
+            .assets()?
+            .into_iter()
+            .map(Clone::clone)
+            .collect::<Vec<_>>();
+        for mut asset in assets {
+            let mut events = vec![];
+            for (node_id, index) in removal_list {
+                if let Some(allocation) =
+                    asset.remove_assignment(*node_id, *index)
+                {
+                    asset.add_history(HistoryEntry::new(
+                        Direction::Forgotten,
+                        *node_id,
+                        allocation.value().value,
+                        None,
+                        None,
+                    ));
+                    events
+                        .push(AllocationEvent::with(*asset.id(), &allocation));
+                }
+            }
+            if events.is_empty() {
+                continue;
+            }
+            self.cacher.add_asset(asset)?;
+            for event in events {
+                self.publish(Event::AllocationRemoved(event));
+            }
+        }
+        Ok(())
+    }
+
     async fn stash_req_rep(
         &mut self,
         request: api::stash::Request,
@@ -547,7 +1183,8 @@ impl Runtime {
 
 pub async fn main_with_config(config: Config) -> Result<(), BootstrapError> {
     let runtime = Runtime::init(config)?;
-    runtime.run_or_panic("Fungible contract runtime").await;
-
-    unreachable!()
+    runtime
+        .run_until_shutdown("Fungible contract runtime")
+        .await;
+    Ok(())
 }
@@ -0,0 +1,353 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use lnpbp::bitcoin::{OutPoint, Txid};
+
+use super::{Asset, Direction};
+use crate::util::chain::{ChainStatus, OutputStatus, TxStatus};
+
+/// Spend of an outpoint holding some of the cached allocations
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct Spend {
+    pub outpoint: OutPoint,
+    /// Spending transaction
+    pub txid: Txid,
+    /// Whether the spending transaction is a witness of some state transition
+    /// known to us. Otherwise the assets held by the outpoint may be lost.
+    pub known: bool,
+}
+
+/// Chain watcher which periodically checks whether outpoints of the cached
+/// allocations were spent, so they do not have to be forgotten manually.
+///
+/// Spends are reported only once the spending transaction gets the required
+/// number of confirmations; until then they are kept pending, and dropped if
+/// the transaction leaves the mempool or gets conflicted.
+#[derive(Clone, Debug)]
+pub struct SpendWatcher {
+    /// Interval between checks; `None` disables the watcher
+    interval: Option<Duration>,
+    checked: Instant,
+    /// Number of confirmations of the spending transaction required to
+    /// report the spend
+    min_confirmations: u32,
+    /// Spending transactions waiting for confirmations
+    pending: BTreeMap<OutPoint, Txid>,
+}
+
+impl SpendWatcher {
+    /// Constructs watcher with the given check interval and number of
+    /// confirmations required for the spends; zero interval disables
+    /// watching
+    pub fn new(interval: Duration, min_confirmations: u32) -> Self {
+        Self {
+            interval: Some(interval)
+                .filter(|interval| interval.as_millis() > 0),
+            checked: Instant::now(),
+            min_confirmations,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Detects whether it is time for the next check
+    pub fn is_due(&self) -> bool {
+        self.interval
+            .map(|interval| self.checked.elapsed() >= interval)
+            .unwrap_or(false)
+    }
+
+    /// Checks outpoints of all allocations of the given assets, returning
+    /// the ones spent by sufficiently confirmed transactions. Outpoints which
+    /// can't be checked are left for the next time.
+    pub fn check(
+        &mut self,
+        assets: &[&Asset],
+        chain: &dyn ChainStatus,
+    ) -> Vec<Spend> {
+        let outpoints = assets
+            .iter()
+            .flat_map(|asset| asset.known_allocations())
+            .filter(|(_, allocations)| !allocations.is_empty())
+            .map(|(outpoint, _)| *outpoint)
+            .collect::<BTreeSet<_>>();
+        self.check_outpoints(outpoints, chain, |txid| {
+            is_known_witness(assets, txid)
+        })
+    }
+
+    /// Checks the given outpoints, telling whether their spending
+    /// transactions are known with `is_known`
+    fn check_outpoints(
+        &mut self,
+        outpoints: BTreeSet<OutPoint>,
+        chain: &dyn ChainStatus,
+        is_known: impl Fn(&Txid) -> bool,
+    ) -> Vec<Spend> {
+        self.checked = Instant::now();
+        trace!(
+            "Checking {} allocated outpoints for spends",
+            outpoints.len()
+        );
+        self.pending
+            .retain(|outpoint, _| outpoints.contains(outpoint));
+
+        let mut spends = vec![];
+        for outpoint in outpoints {
+            let txid = match chain.output_status(&outpoint) {
+                Ok(OutputStatus::Spent(Some(txid))) => txid,
+                Ok(OutputStatus::Unspent) => {
+                    if let Some(txid) = self.pending.remove(&outpoint) {
+                        info!(
+                            "Spend of {} by {} has been dropped",
+                            outpoint, txid
+                        );
+                    }
+                    continue;
+                }
+                // Data sources which can't tell the spending transaction may
+                // still have told it earlier, while it was in the mempool
+                Ok(status) => match self.pending.get(&outpoint) {
+                    Some(txid) => *txid,
+                    None => {
+                        if status != OutputStatus::Unknown {
+                            debug!(
+                                "Outpoint {} is spent by an unknown \
+                                 transaction",
+                                outpoint
+                            );
+                        }
+                        continue;
+                    }
+                },
+                Err(err) => {
+                    warn!("Unable to check outpoint {}: {}", outpoint, err);
+                    continue;
+                }
+            };
+            let status = match chain.tx_status(&txid) {
+                Ok(status) => status,
+                Err(err) => {
+                    warn!("Unable to check spend of {}: {}", outpoint, err);
+                    continue;
+                }
+            };
+            match status {
+                TxStatus::Unknown | TxStatus::Conflicted => {
+                    debug!("Spend of {} by {} is {:?}", outpoint, txid, status);
+                    self.pending.remove(&outpoint);
+                }
+                TxStatus::Mempool | TxStatus::Mined { .. }
+                    if status.confirmations() < self.min_confirmations =>
+                {
+                    self.pending.insert(outpoint, txid);
+                }
+                TxStatus::Mempool | TxStatus::Mined { .. } => {
+                    self.pending.remove(&outpoint);
+                    spends.push(Spend {
+                        outpoint,
+                        txid,
+                        known: is_known(&txid),
+                    });
+                }
+            }
+        }
+        spends
+    }
+}
+
+/// Detects whether the transaction is a witness of the state transition we
+/// have made or received
+fn is_known_witness(assets: &[&Asset], txid: &Txid) -> bool {
+    assets.iter().any(|asset| {
+        asset.history().iter().any(|entry| {
+            matches!(entry.direction(), Direction::Sent | Direction::Received)
+                && *entry.witness_txid() == Some(*txid)
+        }) || asset
+            .known_allocations()
+            .values()
+            .flatten()
+            .any(|allocation| *allocation.witness_txid() == Some(*txid))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use lnpbp::bitcoin::hashes::Hash;
+
+    use super::*;
+    use crate::util::chain::{ChainStatusError, Connectivity};
+
+    #[derive(Default)]
+    struct Chain {
+        outputs: BTreeMap<OutPoint, OutputStatus>,
+        txs: BTreeMap<Txid, TxStatus>,
+    }
+
+    impl ChainStatus for Chain {
+        fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainStatusError> {
+            Ok(self.txs.get(txid).copied().unwrap_or(TxStatus::Unknown))
+        }
+
+        fn output_status(
+            &self,
+            outpoint: &OutPoint,
+        ) -> Result<OutputStatus, ChainStatusError> {
+            self.outputs
+                .get(outpoint)
+                .copied()
+                .ok_or_else(|| ChainStatusError::Unavailable(s!("offline")))
+        }
+
+        fn connectivity(&self) -> Connectivity {
+            Connectivity::Connected
+        }
+    }
+
+    fn mined(confirmations: u32) -> TxStatus {
+        TxStatus::Mined {
+            height: 100,
+            confirmations,
+        }
+    }
+
+    fn setup() -> (Chain, OutPoint, Txid, BTreeSet<OutPoint>) {
+        let outpoint = OutPoint::new(Txid::hash(&[1]), 0);
+        let spender = Txid::hash(&[2]);
+        let mut chain = Chain::default();
+        chain.outputs.insert(outpoint, OutputStatus::Unspent);
+        (chain, outpoint, spender, bset! {outpoint})
+    }
+
+    #[test]
+    fn test_confirmed_spend() {
+        let (mut chain, outpoint, spender, outpoints) = setup();
+        let mut watcher = SpendWatcher::new(Duration::from_secs(1), 2);
+        let mut check = |chain: &Chain| {
+            watcher.check_outpoints(outpoints.clone(), chain, |txid| {
+                *txid == spender
+            })
+        };
+
+        assert_eq!(check(&chain), vec![]);
+        chain
+            .outputs
+            .insert(outpoint, OutputStatus::Spent(Some(spender)));
+        chain.txs.insert(spender, TxStatus::Mempool);
+        assert_eq!(check(&chain), vec![]);
+        chain.txs.insert(spender, mined(1));
+        assert_eq!(check(&chain), vec![]);
+        chain.txs.insert(spender, mined(2));
+        assert_eq!(
+            check(&chain),
+            vec![Spend {
+                outpoint,
+                txid: spender,
+                known: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unconfirmed_spend() {
+        let (mut chain, outpoint, spender, outpoints) = setup();
+        let mut watcher = SpendWatcher::new(Duration::from_secs(1), 0);
+
+        chain
+            .outputs
+            .insert(outpoint, OutputStatus::Spent(Some(spender)));
+        chain.txs.insert(spender, TxStatus::Mempool);
+        assert_eq!(
+            watcher.check_outpoints(outpoints, &chain, |_| false),
+            vec![Spend {
+                outpoint,
+                txid: spender,
+                known: false
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dropped_spend() {
+        let (mut chain, outpoint, spender, outpoints) = setup();
+        let mut watcher = SpendWatcher::new(Duration::from_secs(1), 1);
+        let mut check = |chain: &Chain| {
+            watcher.check_outpoints(outpoints.clone(), chain, |_| true)
+        };
+
+        // Spend leaves the mempool
+        chain
+            .outputs
+            .insert(outpoint, OutputStatus::Spent(Some(spender)));
+        chain.txs.insert(spender, TxStatus::Mempool);
+        assert_eq!(check(&chain), vec![]);
+        chain.outputs.insert(outpoint, OutputStatus::Unspent);
+        chain.txs.remove(&spender);
+        assert_eq!(check(&chain), vec![]);
+
+        // Spend is replaced with a conflicting transaction
+        chain
+            .outputs
+            .insert(outpoint, OutputStatus::Spent(Some(spender)));
+        chain.txs.insert(spender, TxStatus::Mempool);
+        assert_eq!(check(&chain), vec![]);
+        chain.txs.insert(spender, TxStatus::Conflicted);
+        assert_eq!(check(&chain), vec![]);
+
+        // Neither spend is remembered after being dropped
+        chain.outputs.insert(outpoint, OutputStatus::Unknown);
+        chain.txs.insert(spender, mined(1));
+        assert_eq!(check(&chain), vec![]);
+    }
+
+    #[test]
+    fn test_untold_spender() {
+        let (mut chain, outpoint, spender, outpoints) = setup();
+        let mut watcher = SpendWatcher::new(Duration::from_secs(1), 1);
+        let mut check = |chain: &Chain| {
+            watcher.check_outpoints(outpoints.clone(), chain, |_| false)
+        };
+
+        // Spending transaction is not known, so nothing can be reported
+        chain.outputs.insert(outpoint, OutputStatus::Spent(None));
+        chain.txs.insert(spender, mined(1));
+        assert_eq!(check(&chain), vec![]);
+
+        // Data source has told the spending transaction while it was in the
+        // mempool, but not after it was mined
+        chain
+            .outputs
+            .insert(outpoint, OutputStatus::Spent(Some(spender)));
+        chain.txs.insert(spender, TxStatus::Mempool);
+        assert_eq!(check(&chain), vec![]);
+        chain.outputs.insert(outpoint, OutputStatus::Unknown);
+        chain.txs.insert(spender, mined(1));
+        assert_eq!(
+            check(&chain),
+            vec![Spend {
+                outpoint,
+                txid: spender,
+                known: false
+            }]
+        );
+
+        // Unavailable data source does not affect the result
+        chain.outputs.clear();
+        assert_eq!(check(&chain), vec![]);
+    }
+}
@@ -13,10 +13,13 @@
 
 // TODO: Consider moving parts of this file to common daemon modules (LNP/BP)
 
-use std::collections::HashMap;
+use core::convert::TryFrom;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 use tokio::task::JoinError;
 
+use lnpbp::bitcoin;
+use lnpbp::bp;
 use lnpbp::lnp;
 
 #[derive(Debug, Display, Error, From)]
@@ -41,6 +44,12 @@ pub enum BootstrapError {
     #[from]
     MessageBusError(lnp::transport::Error),
 
+    #[from(lnp::presentation::Error)]
+    EncodingError,
+
+    #[from]
+    ConfigError(ConfigError),
+
     #[from]
     ElectrumError(electrum_client::Error),
 
@@ -53,6 +62,35 @@ pub enum BootstrapError {
     Other,
 }
 
+/// Errors in node configuration detected at startup
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum ConfigError {
+    /// Invalid command-line arguments: {_0}
+    Args(String),
+
+    /// Unable to read configuration file `{_0}`: {_1}
+    File(String, String),
+
+    /// Unknown configuration parameter `{_0}`
+    UnknownParam(String),
+
+    /// Configuration parameter `{_0}` must be a string, a number, a boolean
+    /// or a list of them
+    UnsupportedValue(String),
+
+    /// Invalid value `{value}` of `{param}` parameter: {details}
+    InvalidParam {
+        param: String,
+        value: String,
+        details: String,
+    },
+
+    /// Invalid CurveZMQ settings: {_0}
+    #[from]
+    CurveKeys(crate::auth::KeyParseError),
+}
+
 impl From<&str> for BootstrapError {
     fn from(err: &str) -> Self {
         BootstrapError::ArgParseError(err.to_string())
@@ -128,11 +166,9 @@ pub enum ServiceErrorDomain {
     #[from(::std::io::Error)]
     Io,
     Stash,
-    Storage(String),
+    Storage(StorageError),
     Index,
-    #[from(crate::contracts::fungible::FileCacheError)]
-    #[from(crate::contracts::fungible::SqlCacheError)]
-    Cache,
+    Cache(crate::contracts::fungible::CacheError),
     Multithreading,
     P2pwire,
     #[from]
@@ -144,12 +180,149 @@ pub enum ServiceErrorDomain {
     Bifrost,
     BpNode,
     LnpNode,
+    #[from(crate::util::chain::ChainStatusError)]
     Bitcoin,
     Lightning,
     Schema(String),
     Anchor(String),
     #[from]
     Internal(String),
+    #[from]
+    Network(NetworkMismatch),
+}
+
+impl ServiceErrorDomain {
+    /// Returns registered code for the error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ServiceErrorDomain::Io => ErrorCode::Io,
+            ServiceErrorDomain::Stash => ErrorCode::Stash,
+            ServiceErrorDomain::Storage(err) => err.code(),
+            ServiceErrorDomain::Index => ErrorCode::Index,
+            ServiceErrorDomain::Cache(err) => err.into(),
+            ServiceErrorDomain::Multithreading => ErrorCode::Multithreading,
+            ServiceErrorDomain::P2pwire => ErrorCode::P2pwire,
+            ServiceErrorDomain::LnpRpc(_) => ErrorCode::LnpRpc,
+            ServiceErrorDomain::LnpTransport(_) => ErrorCode::LnpTransport,
+            ServiceErrorDomain::Api(err) => err.code(),
+            ServiceErrorDomain::Monitoring => ErrorCode::Monitoring,
+            ServiceErrorDomain::Bifrost => ErrorCode::Bifrost,
+            ServiceErrorDomain::BpNode => ErrorCode::BpNode,
+            ServiceErrorDomain::LnpNode => ErrorCode::LnpNode,
+            ServiceErrorDomain::Bitcoin => ErrorCode::Bitcoin,
+            ServiceErrorDomain::Lightning => ErrorCode::Lightning,
+            ServiceErrorDomain::Schema(_) => ErrorCode::Schema,
+            ServiceErrorDomain::Anchor(_) => ErrorCode::Anchor,
+            ServiceErrorDomain::Internal(_) => ErrorCode::Internal,
+            ServiceErrorDomain::Network(_) => ErrorCode::Network,
+        }
+    }
+
+    /// Returns error parameters which may be used by clients to compose
+    /// localized error message
+    pub fn details(&self) -> BTreeMap<String, String> {
+        let mut details = BTreeMap::new();
+        match self {
+            ServiceErrorDomain::Storage(StorageError::Io(msg))
+            | ServiceErrorDomain::Storage(StorageError::NotFound(msg))
+            | ServiceErrorDomain::Storage(StorageError::DataIntegrity(msg))
+            | ServiceErrorDomain::Storage(StorageError::Index(msg))
+            | ServiceErrorDomain::Schema(msg)
+            | ServiceErrorDomain::Anchor(msg)
+            | ServiceErrorDomain::Internal(msg) => {
+                details.insert("message".to_string(), msg.clone());
+            }
+            ServiceErrorDomain::Cache(err) => {
+                details.insert("message".to_string(), err.to_string());
+            }
+            ServiceErrorDomain::Network(err) => {
+                details
+                    .insert("expected".to_string(), err.expected.to_string());
+                details.insert("actual".to_string(), err.actual.to_string());
+            }
+            ServiceErrorDomain::LnpRpc(err) => {
+                details.insert("message".to_string(), err.to_string());
+            }
+            ServiceErrorDomain::LnpTransport(err) => {
+                details.insert("message".to_string(), err.to_string());
+            }
+            ServiceErrorDomain::Api(ApiErrorType::MalformedRequest {
+                request,
+            }) => {
+                details.insert("request".to_string(), request.clone());
+            }
+            ServiceErrorDomain::Api(ApiErrorType::UnknownCommand {
+                command,
+            }) => {
+                details.insert("command".to_string(), command.clone());
+            }
+            ServiceErrorDomain::Api(ApiErrorType::MissedArgument {
+                request,
+                argument,
+            })
+            | ServiceErrorDomain::Api(ApiErrorType::UnknownArgument {
+                request,
+                argument,
+            })
+            | ServiceErrorDomain::Api(ApiErrorType::MalformedArgument {
+                request,
+                argument,
+            }) => {
+                details.insert("request".to_string(), request.clone());
+                details.insert("argument".to_string(), argument.clone());
+            }
+            ServiceErrorDomain::Api(ApiErrorType::PermissionDenied {
+                request,
+                role,
+            }) => {
+                details.insert("request".to_string(), request.clone());
+                details.insert("role".to_string(), role.clone());
+            }
+            ServiceErrorDomain::Api(ApiErrorType::IncompatibleVersion {
+                client,
+                daemon,
+            }) => {
+                details.insert("client".to_string(), client.to_string());
+                details.insert("daemon".to_string(), daemon.to_string());
+            }
+            _ => {}
+        }
+        details
+    }
+}
+
+impl From<crate::contracts::fungible::FileCacheError> for ServiceErrorDomain {
+    fn from(err: crate::contracts::fungible::FileCacheError) -> Self {
+        ServiceErrorDomain::Cache(err.into())
+    }
+}
+
+impl From<crate::contracts::fungible::SqlCacheError> for ServiceErrorDomain {
+    fn from(err: crate::contracts::fungible::SqlCacheError) -> Self {
+        ServiceErrorDomain::Cache(err.into())
+    }
+}
+
+/// Error of the persistent storage used by stash daemon
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(Debug)]
+pub enum StorageError {
+    Io(String),
+    NotFound(String),
+    DataIntegrity(String),
+    Index(String),
+}
+
+impl StorageError {
+    /// Returns registered code for the error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            StorageError::Io(_) => ErrorCode::StorageIo,
+            StorageError::NotFound(_) => ErrorCode::StorageNotFound,
+            StorageError::DataIntegrity(_) => ErrorCode::StorageDataIntegrity,
+            StorageError::Index(_) => ErrorCode::StorageIndex,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Display)]
@@ -158,6 +331,7 @@ pub enum ServiceErrorSource {
     Broker,
     Stash,
     Contract(String),
+    Gateway,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Display)]
@@ -179,6 +353,85 @@ pub enum ApiErrorType {
     UnknownArgument { request: String, argument: String },
     MalformedArgument { request: String, argument: String },
     UnexpectedReply,
+    PermissionDenied { request: String, role: String },
+    IncompatibleVersion { client: u16, daemon: u16 },
+}
+
+impl ApiErrorType {
+    /// Returns registered code for the error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiErrorType::MalformedRequest { .. } => {
+                ErrorCode::ApiMalformedRequest
+            }
+            ApiErrorType::UnknownCommand { .. } => ErrorCode::ApiUnknownCommand,
+            ApiErrorType::UnimplementedCommand => {
+                ErrorCode::ApiUnimplementedCommand
+            }
+            ApiErrorType::MissedArgument { .. } => ErrorCode::ApiMissedArgument,
+            ApiErrorType::UnknownArgument { .. } => {
+                ErrorCode::ApiUnknownArgument
+            }
+            ApiErrorType::MalformedArgument { .. } => {
+                ErrorCode::ApiMalformedArgument
+            }
+            ApiErrorType::UnexpectedReply => ErrorCode::ApiUnexpectedReply,
+            ApiErrorType::PermissionDenied { .. } => {
+                ErrorCode::ApiPermissionDenied
+            }
+            ApiErrorType::IncompatibleVersion { .. } => {
+                ErrorCode::ApiIncompatibleVersion
+            }
+        }
+    }
+}
+
+/// Data refer to a bitcoin network different from the one used by the node
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(Debug)]
+pub struct NetworkMismatch {
+    /// Network used by the node
+    pub expected: bp::Chain,
+    /// Network the data refer to
+    pub actual: bp::Chain,
+}
+
+impl NetworkMismatch {
+    /// Checks that the data network matches the one used by the node
+    pub fn check(
+        expected: &bp::Chain,
+        actual: &bp::Chain,
+    ) -> Result<(), NetworkMismatch> {
+        if expected != actual {
+            Err(NetworkMismatch {
+                expected: expected.clone(),
+                actual: actual.clone(),
+            })?
+        }
+        Ok(())
+    }
+
+    /// Checks that the bitcoin address may be used on the node network.
+    /// Base58 addresses are shared by all test networks, so testnet
+    /// addresses are accepted on any of them.
+    pub fn check_address(
+        expected: &bp::Chain,
+        address: &bitcoin::Address,
+    ) -> Result<(), NetworkMismatch> {
+        match bitcoin::Network::try_from(expected) {
+            Ok(network) if network == address.network => Ok(()),
+            Ok(network)
+                if network != bitcoin::Network::Bitcoin
+                    && address.network == bitcoin::Network::Testnet =>
+            {
+                Ok(())
+            }
+            _ => Err(NetworkMismatch {
+                expected: expected.clone(),
+                actual: address.network.into(),
+            }),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
@@ -216,3 +469,159 @@ pub struct ServiceErrorRepresentation {
     pub description: String,
     pub info: HashMap<String, String>,
 }
+
+/// Registry of the error codes returned by RGB node daemons in
+/// [`crate::api::reply::Failure`] replies.
+///
+/// Codes are stable: once assigned, a code is never reused for another kind
+/// of error. Codes are grouped into ranges by the error origin:
+/// - `0x00xx`: messaging and daemon runtime errors;
+/// - `0x01xx`: service errors by their domain;
+/// - `0x02xx`: API request errors;
+/// - `0x03xx`: asset cache errors;
+/// - `0x04xx`: stash storage errors.
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    FromPrimitive,
+    ToPrimitive,
+)]
+#[display(doc_comments)]
+#[repr(u16)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// Unknown error
+    Unknown = 0x0000,
+
+    /// Malformed RPC message
+    Presentation = 0x0001,
+
+    /// RPC transport failure
+    Transport = 0x0002,
+
+    /// Daemon runtime failure
+    Runtime = 0x0003,
+
+    /// Input/output error
+    Io = 0x0100,
+
+    /// Stash daemon error
+    Stash = 0x0101,
+
+    /// Stash index error
+    Index = 0x0102,
+
+    /// Multithreading error
+    Multithreading = 0x0103,
+
+    /// Peer-to-peer protocol error
+    P2pwire = 0x0104,
+
+    /// Malformed RPC message between daemons
+    LnpRpc = 0x0105,
+
+    /// RPC transport failure between daemons
+    LnpTransport = 0x0106,
+
+    /// Monitoring error
+    Monitoring = 0x0107,
+
+    /// Bifrost server error
+    Bifrost = 0x0108,
+
+    /// Bitcoin node error
+    BpNode = 0x0109,
+
+    /// Lightning node error
+    LnpNode = 0x010A,
+
+    /// Bitcoin blockchain data are not available
+    Bitcoin = 0x010B,
+
+    /// Lightning network error
+    Lightning = 0x010C,
+
+    /// Contract schema error
+    Schema = 0x010D,
+
+    /// Anchor or witness transaction error
+    Anchor = 0x010E,
+
+    /// Internal daemon error
+    Internal = 0x010F,
+
+    /// Data refer to a different bitcoin network
+    Network = 0x0110,
+
+    /// Malformed API request
+    ApiMalformedRequest = 0x0200,
+
+    /// Unknown API command
+    ApiUnknownCommand = 0x0201,
+
+    /// API command is not implemented yet
+    ApiUnimplementedCommand = 0x0202,
+
+    /// Required API request argument is missed
+    ApiMissedArgument = 0x0203,
+
+    /// Unknown API request argument
+    ApiUnknownArgument = 0x0204,
+
+    /// Malformed API request argument
+    ApiMalformedArgument = 0x0205,
+
+    /// Unexpected reply from the daemon
+    ApiUnexpectedReply = 0x0206,
+
+    /// Client role does not permit the requested API command
+    ApiPermissionDenied = 0x0207,
+
+    /// Client and daemon use incompatible API protocol versions
+    ApiIncompatibleVersion = 0x0208,
+
+    /// Asset cache input/output error
+    CacheIo = 0x0300,
+
+    /// Asset is not found in the cache
+    CacheNotFound = 0x0301,
+
+    /// Asset cache data can't be accessed
+    CacheDataAccess = 0x0302,
+
+    /// Asset cache data are corrupted
+    CacheDataIntegrity = 0x0303,
+
+    /// Asset cache database error
+    CacheSqlite = 0x0304,
+
+    /// Stash storage input/output error
+    StorageIo = 0x0400,
+
+    /// Requested data are not found in the stash storage
+    StorageNotFound = 0x0401,
+
+    /// Stash storage data are corrupted
+    StorageDataIntegrity = 0x0402,
+
+    /// Stash index error
+    StorageIndex = 0x0403,
+}
+
+impl ErrorCode {
+    /// Returns registered error code for a raw numeric value, or
+    /// [`ErrorCode::Unknown`] if the value is not registered
+    pub fn from_u16(code: u16) -> Self {
+        num_traits::FromPrimitive::from_u16(code).unwrap_or(ErrorCode::Unknown)
+    }
+
+    /// Returns numeric value of the error code
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
+}
@@ -0,0 +1,180 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Authentication of the gateway clients. Clients present their token with
+//! `Authorization: Bearer <token>` header and are given the role the token is
+//! configured with; clients without the header are given read-only role.
+//! Tokens may be bound to a single wallet, and gateways serving other wallets
+//! do not accept them.
+
+use core::fmt::{self, Debug, Formatter};
+use core::str::FromStr;
+
+use crate::auth::Role;
+use crate::error::ConfigError;
+use crate::util::WalletId;
+
+/// Tokens of the gateway clients with their roles. Debug representation does
+/// not reveal the tokens, so configurations containing them can be logged
+/// safely.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct ClientTokens(Vec<(String, Role)>);
+
+impl Debug for ClientTokens {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ClientTokens({} clients)", self.0.len())
+    }
+}
+
+impl ClientTokens {
+    /// Parses `<token>:<role>` entries as they are given in command-line
+    /// arguments or environment for the gateway serving the given wallet.
+    /// Entries may be followed by `@` and wallet identifier, like
+    /// `<token>:transfer@alice`; tokens bound to other wallets are skipped.
+    pub fn from_opts(
+        entries: Vec<String>,
+        wallet: &WalletId,
+    ) -> Result<Self, ConfigError> {
+        let mut tokens = vec![];
+        for entry in entries {
+            let invalid = |details: &str| ConfigError::InvalidParam {
+                param: s!("client"),
+                // Tokens must not get into the logs
+                value: s!("<token>:<role>"),
+                details: details.to_string(),
+            };
+            let pos = entry
+                .rfind(':')
+                .ok_or_else(|| invalid("client role is missing"))?;
+            let (token, rest) = (&entry[..pos], &entry[pos + 1..]);
+            if token.is_empty() {
+                Err(invalid("client token must not be empty"))?
+            }
+            let (role, bound) = match rest.find('@') {
+                Some(pos) => (&rest[..pos], Some(&rest[pos + 1..])),
+                None => (rest, None),
+            };
+            let role = role.parse().map_err(|_| {
+                invalid("role must be `read-only`, `transfer` or `admin`")
+            })?;
+            let bound = bound
+                .map(WalletId::from_str)
+                .transpose()
+                .map_err(|err| invalid(&err.to_string()))?;
+            if bound.map(|bound| &bound == wallet).unwrap_or(true) {
+                tokens.push((token.to_string(), role));
+            }
+        }
+        Ok(ClientTokens(tokens))
+    }
+
+    /// Returns role of the client sending request with the given
+    /// `Authorization` header value, or `None` if the client presents an
+    /// unknown token or uses an unsupported authentication scheme
+    pub fn role(&self, authorization: Option<&str>) -> Option<Role> {
+        let authorization = match authorization {
+            None => return Some(Role::ReadOnly),
+            Some(authorization) => authorization.trim(),
+        };
+        let token = match authorization.find(' ') {
+            Some(pos)
+                if authorization[..pos].eq_ignore_ascii_case("bearer") =>
+            {
+                authorization[pos + 1..].trim()
+            }
+            _ => return None,
+        };
+        // All the tokens are compared in constant time, so the response time
+        // does not tell how close the presented token is to a valid one
+        self.0
+            .iter()
+            .filter(|(known, _)| constant_time_eq(known, token))
+            .map(|(_, role)| *role)
+            .fold(None, |found, role| found.or(Some(role)))
+    }
+}
+
+/// Compares strings in time depending only on the length of the longer one,
+/// so neither the token nor its length can be guessed from the timing
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let byte = |s: &[u8], i: usize| s.get(i).copied().unwrap_or(0) as usize;
+    (0..a.len().max(b.len())).fold(a.len() ^ b.len(), |diff, i| {
+        diff | (byte(a, i) ^ byte(b, i))
+    }) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_client_tokens() {
+        let alice: WalletId = "alice".parse().unwrap();
+        let tokens = ClientTokens::from_opts(
+            vec![
+                s!("reader:read-only"),
+                s!("with:colon:transfer@alice"),
+                s!("root:admin"),
+                s!("bob:admin@bob"),
+            ],
+            &alice,
+        )
+        .unwrap();
+
+        assert_eq!(tokens.role(None), Some(Role::ReadOnly));
+        assert_eq!(tokens.role(Some("Bearer reader")), Some(Role::ReadOnly));
+        assert_eq!(
+            tokens.role(Some("bearer with:colon")),
+            Some(Role::Transfer)
+        );
+        assert_eq!(tokens.role(Some(" Bearer  root ")), Some(Role::Admin));
+        assert_eq!(tokens.role(Some("Bearer unknown")), None);
+        assert_eq!(tokens.role(Some("Bearer roo")), None);
+        assert_eq!(tokens.role(Some("Basic cm9vdDphZG1pbg==")), None);
+        assert_eq!(tokens.role(Some("root")), None);
+        // Tokens of other wallets are not accepted
+        assert_eq!(tokens.role(Some("Bearer bob")), None);
+        assert_eq!(format!("{:?}", tokens), "ClientTokens(3 clients)");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("token", "token"));
+        assert!(constant_time_eq("", ""));
+        assert!(!constant_time_eq("token", "tokens"));
+        assert!(!constant_time_eq("tok", "tok\0"));
+        assert!(!constant_time_eq("token", ""));
+    }
+
+    #[test]
+    fn test_client_tokens_parsing() {
+        let parse = |entry: &str| {
+            ClientTokens::from_opts(
+                vec![entry.to_string()],
+                &WalletId::default(),
+            )
+        };
+        assert!(parse("token").is_err());
+        assert!(parse(":admin").is_err());
+        assert!(parse("token:owner").is_err());
+        assert!(parse("token:admin@").is_err());
+        assert!(parse("token:admin@a@b").is_err());
+        assert_eq!(
+            ClientTokens::from_opts(vec![], &WalletId::default())
+                .unwrap()
+                .role(Some("Bearer x")),
+            None
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use core::convert::TryFrom;
+use core::fmt::Display;
+use core::str::FromStr;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use lnpbp::bp;
+use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
+
+use super::ClientTokens;
+use crate::auth::ClientAuth;
+use crate::constants::*;
+use crate::error::ConfigError;
+#[cfg(feature = "shell")]
+use crate::util::config::LayeredOpts;
+use crate::util::file::utf8_path;
+use crate::util::WalletId;
+
+#[derive(Clap)]
+#[clap(
+    name = "rgb-gateway",
+    version = "0.1.0",
+    author = "Dr Maxim Orlovsky <orlovsky@pandoracore.com>",
+    about = "RGB gateway: JSON-RPC over HTTP and WebSocket interface to RGB node; part of RGB suite"
+)]
+pub struct Opts {
+    /// Sets verbosity level; can be used multiple times to increase verbosity
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Data directory path
+    #[clap(short, long, default_value = RGB_DATA_DIR, env = "RGB_DATA_DIR")]
+    pub data_dir: String,
+
+    /// Path to the configuration file; if not given, `rgb.toml` from the data
+    /// directory is used when present
+    #[clap(long, env = "RGB_CONFIG")]
+    pub config: Option<String>,
+
+    /// Socket address to listen for HTTP connections; binding to other than
+    /// loopback interface exposes the node to the network
+    #[clap(
+        long = "bind",
+        default_value = GATEWAY_HTTP_ENDPOINT,
+        env = "RGB_GATEWAY_BIND"
+    )]
+    pub http_endpoint: String,
+
+    /// ZMQ socket address string for fungibled REQ/REP API
+    #[clap(
+        long,
+        default_value = FUNGIBLED_RPC_ENDPOINT,
+        env = "RGB_FUNGIBLED_RPC"
+    )]
+    pub fungible_rpc: String,
+
+    /// ZMQ socket address string for stashd REQ/REP API
+    #[clap(
+        long,
+        default_value = STASHD_RPC_ENDPOINT,
+        env = "RGB_STASHD_RPC"
+    )]
+    pub stash_rpc: String,
+
+    /// ZMQ socket address string for fungibled PUB/SUB API
+    #[clap(
+        long,
+        default_value = FUNGIBLED_PUB_ENDPOINT,
+        env = "RGB_FUNGIBLED_PUB"
+    )]
+    pub fungible_pub: String,
+
+    /// ZMQ socket address string for stashd PUB/SUB API
+    #[clap(
+        long,
+        default_value = STASHD_PUB_ENDPOINT,
+        env = "RGB_STASHD_PUB"
+    )]
+    pub stash_pub: String,
+
+    /// Bitcoin network to use; selects the daemons serving it if rgbd runs
+    /// several networks
+    #[clap(short, long, default_value = RGB_NETWORK, env = "RGB_NETWORK")]
+    pub network: bp::Chain,
+
+    /// Wallet to work with; selects the daemons serving it if rgbd runs
+    /// several wallets
+    #[clap(long, default_value = RGB_WALLET, env = "RGB_WALLET")]
+    pub wallet: WalletId,
+
+    /// Z85-encoded public key of the node; if given, connections to the node
+    /// are encrypted with CurveZMQ
+    #[clap(long, env = "RGB_NODE_KEY")]
+    pub node_key: Option<String>,
+
+    /// Z85-encoded public key used by the gateway to connect to the node
+    #[clap(long, env = "RGB_GATEWAY_CURVE_PUBLIC")]
+    pub curve_public: Option<String>,
+
+    /// Z85-encoded secret key matching `--curve-public`
+    #[clap(long, env = "RGB_GATEWAY_CURVE_SECRET", hide_env_values = true)]
+    pub curve_secret: Option<String>,
+
+    /// Tokens of the HTTP clients with their roles, as `<token>:<role>`,
+    /// where role is `read-only`, `transfer` or `admin`, optionally bound to
+    /// a wallet (`<token>:transfer@alice`). Clients present the token with
+    /// `Authorization: Bearer <token>` header; clients without it may call
+    /// only read-only methods and receive only read-only events
+    #[clap(
+        long = "client",
+        env = "RGB_GATEWAY_CLIENTS",
+        use_delimiter = true,
+        hide_env_values = true
+    )]
+    pub clients: Vec<String>,
+}
+
+// We need config structure since not all of the parameters can be specified
+// via environment and command-line arguments. Thus we need a config file and
+// default set of configuration
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct Config {
+    pub verbose: u8,
+    pub data_dir: PathBuf,
+    pub http_endpoint: SocketAddr,
+    pub fungible_rpc: ZmqSocketAddr,
+    pub stash_rpc: ZmqSocketAddr,
+    pub fungible_pub: ZmqSocketAddr,
+    pub stash_pub: ZmqSocketAddr,
+    pub network: bp::Chain,
+    pub wallet: WalletId,
+    pub auth: ClientAuth,
+    /// Tokens of the HTTP clients with their roles
+    pub clients: ClientTokens,
+}
+
+#[cfg(feature = "shell")]
+impl LayeredOpts for Opts {
+    const FILE_PARAMS: &'static [(&'static str, &'static str)] = &[
+        ("data-dir", "data-dir"),
+        ("network", "network"),
+        ("wallet", "wallet"),
+        ("node-key", "node-key"),
+        ("gateway.bind", "http-endpoint"),
+        ("gateway.curve-public", "curve-public"),
+        ("gateway.curve-secret", "curve-secret"),
+        ("fungibled.rpc", "fungible-rpc"),
+        ("stashd.rpc", "stash-rpc"),
+        ("fungibled.pub", "fungible-pub"),
+        ("stashd.pub", "stash-pub"),
+    ];
+
+    fn config_file(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    fn data_dir(&self) -> &str {
+        &self.data_dir
+    }
+}
+
+impl TryFrom<Opts> for Config {
+    type Error = ConfigError;
+
+    fn try_from(opts: Opts) -> Result<Self, Self::Error> {
+        let mut me = Self {
+            verbose: opts.verbose,
+            network: opts.network,
+            wallet: opts.wallet,
+            ..Config::default()
+        };
+        me.data_dir = me.parse_param("data-dir", opts.data_dir)?;
+        me.http_endpoint = me.parse_param("bind", opts.http_endpoint)?;
+        me.fungible_rpc = me.parse_param("fungible-rpc", opts.fungible_rpc)?;
+        me.stash_rpc = me.parse_param("stash-rpc", opts.stash_rpc)?;
+        me.fungible_pub = me.parse_param("fungible-pub", opts.fungible_pub)?;
+        me.stash_pub = me.parse_param("stash-pub", opts.stash_pub)?;
+        me.auth = ClientAuth::from_opts(
+            opts.node_key,
+            opts.curve_public,
+            opts.curve_secret,
+        )?;
+        me.clients = ClientTokens::from_opts(opts.clients, &me.wallet)?;
+        Ok(me)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            verbose: 0,
+            data_dir: RGB_DATA_DIR
+                .parse()
+                .expect("Error in RGB_DATA_DIR constant value"),
+            http_endpoint: GATEWAY_HTTP_ENDPOINT
+                .parse()
+                .expect("Error in GATEWAY_HTTP_ENDPOINT constant value"),
+            fungible_rpc: FUNGIBLED_RPC_ENDPOINT
+                .parse()
+                .expect("Error in FUNGIBLED_RPC_ENDPOINT constant value"),
+            stash_rpc: STASHD_RPC_ENDPOINT
+                .parse()
+                .expect("Error in STASHD_RPC_ENDPOINT constant value"),
+            fungible_pub: FUNGIBLED_PUB_ENDPOINT
+                .parse()
+                .expect("Error in FUNGIBLED_PUB_ENDPOINT constant value"),
+            stash_pub: STASHD_PUB_ENDPOINT
+                .parse()
+                .expect("Error in STASHD_PUB_ENDPOINT constant value"),
+            network: RGB_NETWORK
+                .parse()
+                .expect("Error in RGB_NETWORK constant value"),
+            wallet: WalletId::default(),
+            auth: ClientAuth::default(),
+            clients: ClientTokens::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Parses value of the parameter with a given name, replacing
+    /// placeholders with the actual values
+    pub fn parse_param<T>(
+        &self,
+        name: &str,
+        param: String,
+    ) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        param
+            .replace("{id}", self.wallet.as_str())
+            .replace("{network}", &self.network.to_string())
+            .replace("{data_dir}", utf8_path("data-dir", &self.data_dir)?)
+            .parse()
+            .map_err(|err: T::Err| ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: param.clone(),
+                details: err.to_string(),
+            })
+    }
+}
@@ -0,0 +1,303 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Minimal HTTP/1.1 support sufficient for serving JSON-RPC requests. Each
+//! connection serves a single request and is closed after the response.
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::time;
+
+/// Maximal size of the request line and headers
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+/// Maximal size of the request body; consignments may be large
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Time given to the client to send the whole request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// I/O error: {_0}
+    #[from]
+    Io(io::Error),
+
+    /// Connection was closed before the request was received
+    ConnectionClosed,
+
+    /// Malformed HTTP request
+    Malformed,
+
+    /// HTTP request headers are too large
+    HeadTooLarge,
+
+    /// HTTP request body is too large
+    BodyTooLarge,
+
+    /// HTTP request was not received in time
+    Timeout,
+}
+
+impl Error {
+    /// HTTP status and reason which should be returned to the client
+    pub fn status(&self) -> (u16, &'static str) {
+        match self {
+            Error::HeadTooLarge => (431, "Request Header Fields Too Large"),
+            Error::BodyTooLarge => (413, "Payload Too Large"),
+            Error::Timeout => (408, "Request Timeout"),
+            _ => (400, "Bad Request"),
+        }
+    }
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header values indexed by lowercase header names
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_lowercase())
+            .map(|value| value.as_str())
+    }
+
+    /// Reads request line, headers and the body (if `Content-Length` is
+    /// given) from the stream, failing if the client does not send them
+    /// within [`READ_TIMEOUT`]
+    pub async fn read(
+        stream: &mut (impl AsyncBufRead + Unpin),
+    ) -> Result<Self, Error> {
+        time::timeout(READ_TIMEOUT, Self::read_untimed(stream))
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    async fn read_untimed(
+        stream: &mut (impl AsyncBufRead + Unpin),
+    ) -> Result<Self, Error> {
+        let mut head_size = 0usize;
+        let mut line = String::new();
+        head_size += read_line(stream, &mut line, MAX_HEAD_SIZE).await?;
+        let mut parts = line.split_whitespace();
+        let (method, path) = match (parts.next(), parts.next()) {
+            (Some(method), Some(path)) => {
+                (method.to_string(), path.to_string())
+            }
+            _ => Err(Error::Malformed)?,
+        };
+
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            head_size +=
+                read_line(stream, &mut line, MAX_HEAD_SIZE - head_size).await?;
+            if line.is_empty() {
+                break;
+            }
+            let mut header = line.splitn(2, ':');
+            match (header.next(), header.next()) {
+                (Some(name), Some(value)) => {
+                    headers.insert(
+                        name.trim().to_lowercase(),
+                        value.trim().to_string(),
+                    );
+                }
+                _ => Err(Error::Malformed)?,
+            }
+        }
+
+        let len = match headers.get("content-length") {
+            Some(len) => len.parse().map_err(|_| Error::Malformed)?,
+            None => 0usize,
+        };
+        if len > MAX_BODY_SIZE {
+            Err(Error::BodyTooLarge)?
+        }
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).await?;
+
+        Ok(Self {
+            method,
+            path,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Reads line of at most `limit` bytes, stripping line terminator; returns
+/// number of bytes read
+async fn read_line(
+    stream: &mut (impl AsyncBufRead + Unpin),
+    line: &mut String,
+    limit: usize,
+) -> Result<usize, Error> {
+    let len = (&mut *stream)
+        .take(limit as u64)
+        .read_line(line)
+        .await
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData => Error::Malformed,
+            _ => Error::Io(err),
+        })?;
+    if len == 0 && limit > 0 {
+        Err(Error::ConnectionClosed)?
+    }
+    if !line.ends_with('\n') {
+        // Either the limit is reached or the connection was closed in the
+        // middle of the line
+        Err(if len >= limit {
+            Error::HeadTooLarge
+        } else {
+            Error::ConnectionClosed
+        })?
+    }
+    let trimmed = line.trim_end_matches(&['\r', '\n'][..]).len();
+    line.truncate(trimmed);
+    Ok(len)
+}
+
+pub struct Response {
+    pub status: u16,
+    pub reason: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            reason: "OK",
+            headers: vec![("Content-Type", s!("application/json"))],
+            body,
+        }
+    }
+
+    pub fn error(status: u16, reason: &'static str) -> Self {
+        Self {
+            status,
+            reason,
+            headers: vec![("Content-Type", s!("text/plain"))],
+            body: reason.as_bytes().to_vec(),
+        }
+    }
+
+    pub async fn write(
+        &self,
+        stream: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<(), io::Error> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, self.reason);
+        for (name, value) in &self.headers {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        head += &format!("Content-Length: {}\r\n", self.body.len());
+        head += "Connection: close\r\n\r\n";
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&self.body).await?;
+        stream.flush().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::BufReader;
+
+    async fn parse(data: &[u8]) -> Result<Request, Error> {
+        Request::read(&mut BufReader::new(data)).await
+    }
+
+    #[tokio::test]
+    async fn test_request() {
+        let request = parse(
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: \
+              application/json\r\nContent-Length: 2\r\n\r\n{}trailing",
+        )
+        .await
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("HOST"), Some("localhost"));
+        assert_eq!(request.body, b"{}");
+
+        let request = parse(b"GET /events HTTP/1.1\n\n").await.unwrap();
+        assert_eq!(request.path, "/events");
+        assert!(request.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_malformed() {
+        assert!(matches!(parse(b"").await, Err(Error::ConnectionClosed)));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nHost: local").await,
+            Err(Error::ConnectionClosed)
+        ));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n{}").await,
+            Err(Error::Io(_))
+        ));
+        assert!(matches!(
+            parse(b"POST\r\n\r\n").await,
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nno colon\r\n\r\n").await,
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n").await,
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nHost: \xff\r\n\r\n").await,
+            Err(Error::Malformed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        // Single line without terminator is not buffered beyond the limit
+        let mut data = b"POST / HTTP/1.1\r\nHost: ".to_vec();
+        data.extend(vec![b'a'; MAX_HEAD_SIZE * 4]);
+        assert!(matches!(parse(&data).await, Err(Error::HeadTooLarge)));
+
+        // Many short headers are limited by the total head size
+        let mut data = b"POST / HTTP/1.1\r\n".to_vec();
+        for _ in 0..MAX_HEAD_SIZE / 8 {
+            data.extend(b"X-A: b\r\n");
+        }
+        data.extend(b"\r\n");
+        assert!(matches!(parse(&data).await, Err(Error::HeadTooLarge)));
+
+        let data = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_SIZE + 1
+        );
+        assert!(matches!(
+            parse(data.as_bytes()).await,
+            Err(Error::BodyTooLarge)
+        ));
+    }
+}
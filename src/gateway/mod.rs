@@ -0,0 +1,34 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Gateway exposing fungible and stash daemon APIs to non-Rust integrators
+//! as JSON-RPC 2.0 over HTTP. Requests are converted into LNP messages and
+//! forwarded to the daemons over ZMQ RPC; daemon failures are returned as
+//! JSON-RPC errors carrying codes from [`crate::error::ErrorCode`].
+//!
+//! Events published by the daemons are relayed as JSON to the WebSocket
+//! clients connected to `/events` endpoint.
+//!
+//! Each JSON-RPC method requires the same client role as the daemon request
+//! it is converted into; see [`auth`] module for the client authentication.
+
+mod auth;
+mod config;
+mod http;
+pub mod rpc;
+mod runtime;
+mod ws;
+
+pub use auth::ClientTokens;
+pub use config::{Config, Opts};
+pub use runtime::{main_with_config, Runtime};
@@ -0,0 +1,334 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! JSON-RPC 2.0 representation of the fungible and stash daemon APIs.
+//!
+//! Methods are named after the daemon and the request, like
+//! `fungible.balance` or `stash.list_geneses`; parameters are passed as JSON
+//! objects with named fields. Consignments are represented as Base64-encoded
+//! strict encoding, PSBTs as Base64 strings and RGB schemata, geneses and
+//! transitions as Bech32 strings.
+
+use core::fmt::Display;
+
+use lnpbp::bitcoin::OutPoint;
+use lnpbp::rgb::{ContractId, Genesis, NodeId, Schema, SchemaId};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::api::reply::{Failure, SyncFormat};
+use crate::api::{fungible, stash, Hello, Reply};
+use crate::auth::Role;
+use crate::DataFormat;
+
+pub const JSONRPC_VERSION: &'static str = "2.0";
+
+/// Standard JSON-RPC error codes. Errors returned by the daemons use codes
+/// from [`crate::error::ErrorCode`] registry, which never intersect with
+/// these.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "serde_crate")]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub id: Value,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "serde_crate")]
+pub struct JsonRpcResponse {
+    pub jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+    pub id: Value,
+}
+
+impl JsonRpcResponse {
+    pub fn with(id: Value, result: Result<Value, JsonRpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: JSONRPC_VERSION,
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "serde_crate")]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn with(code: i64, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+}
+
+impl From<&Failure> for JsonRpcError {
+    fn from(failure: &Failure) -> Self {
+        Self {
+            code: failure.code as i64,
+            message: failure.info.clone(),
+            data: Some(json!({
+                "error": format!("{:?}", failure.error_code()),
+                "details": failure.details,
+            })),
+        }
+    }
+}
+
+impl From<Failure> for JsonRpcError {
+    fn from(failure: Failure) -> Self {
+        JsonRpcError::from(&failure)
+    }
+}
+
+/// Request to one of the node daemons
+#[derive(Clone, Debug, Display)]
+#[display(Debug)]
+pub enum DaemonRequest {
+    Fungible(fungible::Request),
+    Stash(stash::Request),
+}
+
+impl DaemonRequest {
+    /// Request name used in logs and error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            DaemonRequest::Fungible(request) => request.name(),
+            DaemonRequest::Stash(request) => request.name(),
+        }
+    }
+
+    /// Minimal client role required to call the request
+    pub fn required_role(&self) -> Role {
+        match self {
+            DaemonRequest::Fungible(request) => request.required_role(),
+            DaemonRequest::Stash(request) => request.required_role(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct GenesisParams {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    genesis: Genesis,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct SchemaParams {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    schema: Schema,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct SchemaIdParams {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    schema_id: SchemaId,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct ContractParams {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    contract_id: ContractId,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct OutpointParams {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    outpoint: OutPoint,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct SyncParams {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    format: DataFormat,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct NodeIdsParams {
+    node_ids: Vec<NodeId>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate")]
+struct ForgetParams {
+    assignments: Vec<(NodeId, u16)>,
+}
+
+fn params<T>(params: Value) -> Result<T, JsonRpcError>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_value(params)
+        .map_err(|err| JsonRpcError::with(INVALID_PARAMS, err))
+}
+
+/// Parses JSON-RPC method and parameters into the daemon request
+pub fn parse_request(
+    method: &str,
+    p: Value,
+) -> Result<DaemonRequest, JsonRpcError> {
+    use fungible::Request as F;
+    use stash::Request as S;
+    use DaemonRequest::{Fungible, Stash};
+
+    Ok(match method {
+        "fungible.hello" => Fungible(F::Hello(Hello::with("rgb-gateway"))),
+        "fungible.status" => Fungible(F::Status()),
+        "fungible.issue" => Fungible(F::Issue(params(p)?)),
+        "fungible.transfer" => Fungible(F::Transfer(params(p)?)),
+        "fungible.validate" => Fungible(F::Validate(params(p)?)),
+        "fungible.accept" => Fungible(F::Accept(params(p)?)),
+        "fungible.import_asset" => {
+            Fungible(F::ImportAsset(params::<GenesisParams>(p)?.genesis))
+        }
+        "fungible.export_asset" => {
+            Fungible(F::ExportAsset(params::<ContractParams>(p)?.contract_id))
+        }
+        "fungible.forget" => {
+            Fungible(F::Forget(params::<OutpointParams>(p)?.outpoint))
+        }
+        "fungible.sync" => Fungible(F::Sync(params::<SyncParams>(p)?.format)),
+        "fungible.assets" => {
+            Fungible(F::Assets(params::<OutpointParams>(p)?.outpoint))
+        }
+        "fungible.allocations" => {
+            Fungible(F::Allocations(params::<ContractParams>(p)?.contract_id))
+        }
+        "fungible.history" => Fungible(F::History(params(p)?)),
+        "fungible.balance" => {
+            Fungible(F::Balance(params::<ContractParams>(p)?.contract_id))
+        }
+
+        "stash.hello" => Stash(S::Hello(Hello::with("rgb-gateway"))),
+        "stash.status" => Stash(S::Status()),
+        "stash.add_schema" => {
+            Stash(S::AddSchema(params::<SchemaParams>(p)?.schema))
+        }
+        "stash.list_schemata" => Stash(S::ListSchemata()),
+        "stash.read_schema" => {
+            Stash(S::ReadSchema(params::<SchemaIdParams>(p)?.schema_id))
+        }
+        "stash.add_genesis" => {
+            Stash(S::AddGenesis(params::<GenesisParams>(p)?.genesis))
+        }
+        "stash.list_geneses" => Stash(S::ListGeneses()),
+        "stash.read_genesis" => {
+            Stash(S::ReadGenesis(params::<ContractParams>(p)?.contract_id))
+        }
+        "stash.read_transitions" => {
+            Stash(S::ReadTransitions(params::<NodeIdsParams>(p)?.node_ids))
+        }
+        "stash.consign" => Stash(S::Consign(params(p)?)),
+        "stash.validate" => Stash(S::Validate(params(p)?)),
+        "stash.merge" => Stash(S::Merge(params(p)?)),
+        "stash.forget" => {
+            Stash(S::Forget(params::<ForgetParams>(p)?.assignments))
+        }
+
+        unknown => Err(JsonRpcError::with(
+            METHOD_NOT_FOUND,
+            format!("Unknown method `{}`", unknown),
+        ))?,
+    })
+}
+
+fn to_value(data: &impl Serialize) -> Result<Value, JsonRpcError> {
+    serde_json::to_value(data)
+        .map_err(|err| JsonRpcError::with(INTERNAL_ERROR, err))
+}
+
+fn to_strings<T>(list: &[T]) -> Value
+where
+    T: ToString,
+{
+    Value::from(list.iter().map(T::to_string).collect::<Vec<_>>())
+}
+
+/// Converts daemon reply into JSON-RPC result, or error if the daemon has
+/// returned failure
+pub fn reply_to_result(reply: &Reply) -> Result<Value, JsonRpcError> {
+    Ok(match reply {
+        Reply::Success | Reply::Nothing => Value::Null,
+        Reply::Failure(failure) => Err(JsonRpcError::from(failure))?,
+        Reply::Sync(SyncFormat(format, data)) => json!({
+            "format": format.to_string(),
+            "data": base64::encode(data),
+        }),
+        Reply::Assets(assets) => Value::Object(
+            assets
+                .iter()
+                .map(|(contract_id, amounts)| {
+                    (contract_id.to_string(), Value::from(amounts.clone()))
+                })
+                .collect(),
+        ),
+        Reply::Allocations(allocations) => Value::Object(
+            allocations
+                .iter()
+                .map(|(outpoint, amounts)| {
+                    (outpoint.to_string(), Value::from(amounts.clone()))
+                })
+                .collect(),
+        ),
+        Reply::SchemaIds(ids) => to_strings(ids),
+        Reply::ContractIds(ids) => to_strings(ids),
+        Reply::Genesis(genesis) => Value::from(genesis.to_string()),
+        Reply::Schema(schema) => Value::from(schema.to_string()),
+        Reply::Transitions(transitions) => to_strings(transitions),
+        Reply::ValidationStatus(status) => {
+            let mut value = to_value(status)?;
+            value["validity"] = Value::from(status.validity().to_string());
+            value
+        }
+        Reply::Transfer(transfer) => to_value(transfer)?,
+        Reply::History(history) => to_value(history)?,
+        Reply::Balance(balance) => to_value(balance)?,
+        Reply::Capabilities(capabilities) => to_value(capabilities)?,
+        Reply::Status(status) => to_value(status)?,
+        Reply::Merge(merge) => to_value(merge)?,
+    })
+}
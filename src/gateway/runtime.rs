@@ -0,0 +1,386 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use lnpbp::lnp::presentation::Encode;
+use lnpbp::lnp::transport;
+use lnpbp::lnp::zmqsocket::{ZmqSocketAddr, ZmqType};
+use lnpbp::lnp::{CreateUnmarshaller, Session, Unmarshall, Unmarshaller};
+use serde_json::Value;
+use tokio::io::{self, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task;
+
+use super::http;
+use super::rpc::{
+    self, DaemonRequest, JsonRpcError, JsonRpcRequest, JsonRpcResponse,
+};
+use super::ws::{self, Opcode};
+use super::ClientTokens;
+use super::Config;
+use crate::api::reply::Failure;
+use crate::api::{Event, Reply};
+use crate::auth::{ClientAuth, Role, ZmqSession};
+use crate::error::{
+    BootstrapError, RuntimeError, ServiceError, ServiceErrorDomain,
+    ServiceErrorSource,
+};
+use crate::service::TryService;
+use crate::util::shutdown::Shutdown;
+
+/// Client sessions to the node daemons. ZMQ request sockets require strict
+/// request-reply alternation, so the bridge is shared between connections
+/// behind a mutex.
+struct Bridge {
+    fungible_rpc: ZmqSession,
+    stash_rpc: ZmqSession,
+    unmarshaller: Unmarshaller<Reply>,
+}
+
+impl Bridge {
+    fn request(
+        &mut self,
+        request: &DaemonRequest,
+    ) -> Result<Arc<Reply>, ServiceErrorDomain> {
+        let (session, data) = match request {
+            DaemonRequest::Fungible(request) => {
+                (&mut self.fungible_rpc, request.encode()?)
+            }
+            DaemonRequest::Stash(request) => {
+                (&mut self.stash_rpc, request.encode()?)
+            }
+        };
+        session.send_raw_message(&data)?;
+        let raw = session.recv_raw_message()?;
+        Ok(self.unmarshaller.unmarshall(&raw)?)
+    }
+}
+
+/// Number of events kept for the WebSocket clients which are slow to read
+/// them; if a client lags behind more, oldest events are dropped for it
+const EVENT_QUEUE_LEN: usize = 1024;
+
+/// Event serialized to JSON together with the minimal role required to
+/// receive it
+type RelayedEvent = (Role, String);
+
+/// Subscribes to the daemon PUB socket and relays all its events as JSON to
+/// the WebSocket clients from a separate thread. Gateway connects only to the
+/// daemons of its wallet, so the clients never get events of other wallets.
+fn relay_events(
+    endpoint: &ZmqSocketAddr,
+    auth: &ClientAuth,
+    sender: broadcast::Sender<RelayedEvent>,
+) -> Result<(), BootstrapError> {
+    let mut session_sub = auth.connect(ZmqType::Sub, endpoint)?;
+    session_sub
+        .as_socket()
+        .set_subscribe(&[])
+        .map_err(transport::Error::from)?;
+    let unmarshaller = Event::create_unmarshaller();
+    let endpoint = endpoint.to_string();
+
+    thread::spawn(move || loop {
+        let event = match session_sub
+            .recv_raw_message()
+            .map_err(ServiceErrorDomain::from)
+            .and_then(|raw| Ok(unmarshaller.unmarshall(&raw)?))
+        {
+            Ok(event) => event,
+            Err(err) => {
+                error!("Error receiving event from {}: {}", endpoint, err);
+                continue;
+            }
+        };
+        trace!("Relaying event {}", event);
+        match serde_json::to_string(&*event) {
+            // Error here only means that there are no WebSocket clients
+            Ok(json) => {
+                let _ = sender.send((event.required_role(), json));
+            }
+            Err(err) => error!("Unable to serialize event to JSON: {}", err),
+        }
+    });
+    Ok(())
+}
+
+pub struct Runtime {
+    /// Original configuration object
+    config: Config,
+
+    /// Client sessions to the node daemons
+    bridge: Arc<Mutex<Bridge>>,
+
+    /// Tokens of the HTTP clients with their roles
+    clients: Arc<ClientTokens>,
+
+    /// Events relayed to the WebSocket clients
+    events: broadcast::Sender<RelayedEvent>,
+}
+
+impl Runtime {
+    pub fn init(config: Config) -> Result<Self, BootstrapError> {
+        let fungible_rpc =
+            config.auth.connect(ZmqType::Req, &config.fungible_rpc)?;
+        let stash_rpc = config.auth.connect(ZmqType::Req, &config.stash_rpc)?;
+
+        let (events, _) = broadcast::channel(EVENT_QUEUE_LEN);
+        relay_events(&config.fungible_pub, &config.auth, events.clone())?;
+        relay_events(&config.stash_pub, &config.auth, events.clone())?;
+
+        Ok(Self {
+            clients: Arc::new(config.clients.clone()),
+            config,
+            events,
+            bridge: Arc::new(Mutex::new(Bridge {
+                fungible_rpc,
+                stash_rpc,
+                unmarshaller: Reply::create_unmarshaller(),
+            })),
+        })
+    }
+}
+
+#[async_trait]
+impl TryService for Runtime {
+    type ErrorType = RuntimeError;
+
+    async fn try_run_loop(self) -> Result<(), RuntimeError> {
+        let mut listener = TcpListener::bind(self.config.http_endpoint).await?;
+        info!(
+            "Listening for HTTP connections on {}",
+            self.config.http_endpoint
+        );
+
+        let mut shutdown = Shutdown::listen()?;
+        loop {
+            let (stream, remote) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = shutdown.signalled() => break,
+            };
+            debug!("Accepted HTTP connection from {}", remote);
+            let bridge = self.bridge.clone();
+            let clients = self.clients.clone();
+            let events = self.events.subscribe();
+            task::spawn(async move {
+                if let Err(err) =
+                    serve_connection(stream, bridge, clients, events).await
+                {
+                    error!("Error serving HTTP connection: {}", err);
+                }
+            });
+        }
+
+        info!("Shutting down RGB gateway");
+        // Wait for the request which may be relayed to a daemon right now
+        drop(self.bridge.lock());
+        Ok(())
+    }
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    bridge: Arc<Mutex<Bridge>>,
+    clients: Arc<ClientTokens>,
+    events: broadcast::Receiver<RelayedEvent>,
+) -> Result<(), std::io::Error> {
+    let mut stream = BufReader::new(stream);
+    let request = match http::Request::read(&mut stream).await {
+        Ok(request) => request,
+        Err(http::Error::ConnectionClosed) => return Ok(()),
+        Err(err) => {
+            let (status, reason) = err.status();
+            debug!("Malformed HTTP request: {}", err);
+            return http::Response::error(status, reason)
+                .write(&mut stream)
+                .await;
+        }
+    };
+
+    let role = match clients.role(request.header("authorization")) {
+        Some(role) => role,
+        None => {
+            debug!("HTTP client has presented unknown credentials");
+            let mut response = http::Response::error(401, "Unauthorized");
+            response.headers.push(("WWW-Authenticate", s!("Bearer")));
+            return response.write(&mut stream).await;
+        }
+    };
+
+    let response = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/") => {
+            let reply = process_jsonrpc(&request.body, role, bridge).await;
+            match serde_json::to_vec(&reply) {
+                Ok(body) => http::Response::json(body),
+                Err(err) => {
+                    error!("Unable to serialize JSON-RPC response: {}", err);
+                    http::Response::error(500, "Internal Server Error")
+                }
+            }
+        }
+        (_, "/") => http::Response::error(405, "Method Not Allowed"),
+        (_, "/events") => {
+            return match ws::handshake(&request, &mut stream).await {
+                Ok(()) => serve_events(stream.into_inner(), role, events).await,
+                Err(ws::Error::Io(err)) => Err(err),
+                Err(err) => {
+                    let (status, reason) = err.status();
+                    debug!("Malformed WebSocket handshake: {}", err);
+                    http::Response::error(status, reason)
+                        .write(&mut stream)
+                        .await
+                }
+            };
+        }
+        _ => http::Response::error(404, "Not Found"),
+    };
+    response.write(&mut stream).await
+}
+
+/// Pushes events permitted for the client role to the WebSocket client until
+/// the client closes the connection
+async fn serve_events(
+    stream: TcpStream,
+    role: Role,
+    mut events: broadcast::Receiver<RelayedEvent>,
+) -> Result<(), std::io::Error> {
+    let (mut reader, mut writer) = io::split(stream);
+
+    // Client frames are read by a separate task, so reading a frame is never
+    // interrupted by the arrival of an event
+    let (mut control, mut control_recv) = mpsc::channel(1);
+    task::spawn(async move {
+        loop {
+            let frame = ws::read_frame(&mut reader).await;
+            let last = frame
+                .as_ref()
+                .map(|frame| frame.opcode == Opcode::Close)
+                .unwrap_or(true);
+            if control.send(frame).await.is_err() || last {
+                break;
+            }
+        }
+    });
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok((required, json)) if role.permits(required) => {
+                    ws::write_frame(&mut writer, Opcode::Text, json.as_bytes())
+                        .await?
+                }
+                Ok(_) => {}
+                Err(broadcast::RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "WebSocket client is too slow; {} events were dropped",
+                        skipped
+                    );
+                }
+                Err(broadcast::RecvError::Closed) => break,
+            },
+            frame = control_recv.recv() => match frame {
+                Some(Ok(ws::Frame { opcode: Opcode::Ping, payload })) => {
+                    ws::write_frame(&mut writer, Opcode::Pong, &payload)
+                        .await?
+                }
+                Some(Ok(ws::Frame { opcode: Opcode::Close, .. })) | None => {
+                    break
+                }
+                Some(Ok(_)) => {}
+                Some(Err(ws::Error::Io(err))) => Err(err)?,
+                Some(Err(err)) => {
+                    debug!("Malformed WebSocket frame: {}", err);
+                    break;
+                }
+            },
+        }
+    }
+
+    ws::write_frame(&mut writer, Opcode::Close, &[]).await
+}
+
+async fn process_jsonrpc(
+    body: &[u8],
+    role: Role,
+    bridge: Arc<Mutex<Bridge>>,
+) -> JsonRpcResponse {
+    let request: JsonRpcRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(err) => {
+            let code = if err.is_data() {
+                rpc::INVALID_REQUEST
+            } else {
+                rpc::PARSE_ERROR
+            };
+            return JsonRpcResponse::with(
+                Value::Null,
+                Err(JsonRpcError::with(code, err)),
+            );
+        }
+    };
+    if request.jsonrpc != rpc::JSONRPC_VERSION {
+        return JsonRpcResponse::with(
+            request.id,
+            Err(JsonRpcError::with(
+                rpc::INVALID_REQUEST,
+                "Only JSON-RPC 2.0 is supported",
+            )),
+        );
+    }
+
+    trace!("Got JSON-RPC request `{}`", request.method);
+    let result = match rpc::parse_request(&request.method, request.params)
+        .and_then(|daemon_request| {
+            role.check(daemon_request.required_role(), daemon_request.name())
+                .map_err(|err| {
+                    JsonRpcError::from(Failure::from(ServiceError {
+                        domain: err,
+                        service: ServiceErrorSource::Gateway,
+                    }))
+                })?;
+            Ok(daemon_request)
+        }) {
+        Ok(daemon_request) => {
+            // ZMQ calls are blocking, so we move them out of the async
+            // executor threads
+            task::spawn_blocking(move || {
+                bridge
+                    .lock()
+                    .expect("Gateway bridge lock is poisoned")
+                    .request(&daemon_request)
+                    .map_err(|err| {
+                        Failure::from(ServiceError {
+                            domain: err,
+                            service: ServiceErrorSource::Gateway,
+                        })
+                    })
+            })
+            .await
+            .map_err(|err| JsonRpcError::with(rpc::INTERNAL_ERROR, err))
+            .and_then(|reply| {
+                rpc::reply_to_result(&*reply.map_err(JsonRpcError::from)?)
+            })
+        }
+        Err(err) => Err(err),
+    };
+    JsonRpcResponse::with(request.id, result)
+}
+
+pub async fn main_with_config(config: Config) -> Result<(), BootstrapError> {
+    let runtime = Runtime::init(config)?;
+    runtime.run_until_shutdown("RGB gateway").await;
+    Ok(())
+}
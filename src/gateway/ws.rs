@@ -0,0 +1,337 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Minimal server-side WebSocket (RFC 6455) support sufficient for pushing
+//! JSON events to the clients. Client data frames are ignored; only ping and
+//! close control frames are processed.
+//!
+//! LNP/BP library does not implement its WebSocket transport yet; once it
+//! does, this module should be replaced with it.
+
+use std::io;
+
+use lnpbp::hashes::{sha1, Hash};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::http;
+
+/// GUID appended to the client key when computing `Sec-WebSocket-Accept`
+const HANDSHAKE_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Maximal payload size of the frames accepted from the client. Clients are
+/// not expected to send anything except control frames.
+const MAX_FRAME_SIZE: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+#[repr(u8)]
+pub enum Opcode {
+    Continuation = 0x0,
+    Text = 0x1,
+    Binary = 0x2,
+    Close = 0x8,
+    Ping = 0x9,
+    Pong = 0xA,
+}
+
+impl Opcode {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            0x8 => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xA => Opcode::Pong,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct Frame {
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Error {
+    /// I/O error: {_0}
+    #[from]
+    Io(io::Error),
+
+    /// Request is not a valid WebSocket upgrade request
+    NotUpgrade,
+
+    /// Unsupported WebSocket protocol version
+    UnsupportedVersion,
+
+    /// Client frame is not masked
+    Unmasked,
+
+    /// Unknown frame opcode {_0:#x}
+    UnknownOpcode(u8),
+
+    /// Client frame is too large
+    FrameTooLarge,
+
+    /// Client frame has reserved bits set, while no extensions are negotiated
+    ReservedBits,
+
+    /// Control frame is fragmented or its payload is too large
+    InvalidControl,
+}
+
+impl Error {
+    /// HTTP status and reason which should be returned to the client if the
+    /// error happened during the handshake
+    pub fn status(&self) -> (u16, &'static str) {
+        match self {
+            Error::UnsupportedVersion => (426, "Upgrade Required"),
+            _ => (400, "Bad Request"),
+        }
+    }
+}
+
+/// Checks whether HTTP request asks for WebSocket protocol upgrade
+pub fn is_upgrade(request: &http::Request) -> bool {
+    request
+        .header("Upgrade")
+        .map(|value| value.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false)
+}
+
+/// Validates WebSocket upgrade request and completes the handshake
+pub async fn handshake(
+    request: &http::Request,
+    stream: &mut (impl AsyncWrite + Unpin),
+) -> Result<(), Error> {
+    let connection = request.header("Connection").unwrap_or_default();
+    if request.method != "GET"
+        || !is_upgrade(request)
+        || !connection
+            .split(',')
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    {
+        Err(Error::NotUpgrade)?
+    }
+    if request.header("Sec-WebSocket-Version") != Some("13") {
+        Err(Error::UnsupportedVersion)?
+    }
+    let key = request
+        .header("Sec-WebSocket-Key")
+        .ok_or(Error::NotUpgrade)?;
+    // Key must be a Base64-encoded 16-byte nonce
+    if base64::decode(key).map(|nonce| nonce.len()) != Ok(16) {
+        Err(Error::NotUpgrade)?
+    }
+    let accept =
+        sha1::Hash::hash(format!("{}{}", key, HANDSHAKE_GUID).as_bytes());
+
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        base64::encode(&accept[..])
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Writes a single unfragmented frame; server frames are never masked
+pub async fn write_frame(
+    stream: &mut (impl AsyncWrite + Unpin),
+    opcode: Opcode,
+    payload: &[u8],
+) -> Result<(), io::Error> {
+    let mut head = vec![0x80 | opcode as u8];
+    match payload.len() {
+        len if len < 126 => head.push(len as u8),
+        len if len <= core::u16::MAX as usize => {
+            head.push(126);
+            head.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            head.push(127);
+            head.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    stream.write_all(&head).await?;
+    stream.write_all(payload).await?;
+    stream.flush().await
+}
+
+/// Reads a single frame sent by the client, removing the masking
+pub async fn read_frame(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<Frame, Error> {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await?;
+    if head[0] & 0x70 != 0 {
+        Err(Error::ReservedBits)?
+    }
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    let opcode = Opcode::from_u8(opcode).ok_or(Error::UnknownOpcode(opcode))?;
+    if head[1] & 0x80 == 0 {
+        Err(Error::Unmasked)?
+    }
+    let len = match head[1] & 0x7F {
+        126 => stream.read_u16().await? as u64,
+        127 => stream.read_u64().await?,
+        len => len as u64,
+    };
+    if opcode as u8 & 0x08 != 0 && (!fin || len > 125) {
+        Err(Error::InvalidControl)?
+    }
+    if len > MAX_FRAME_SIZE {
+        Err(Error::FrameTooLarge)?
+    }
+    let mut mask = [0u8; 4];
+    stream.read_exact(&mut mask).await?;
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload).await?;
+    payload
+        .iter_mut()
+        .enumerate()
+        .for_each(|(pos, byte)| *byte ^= mask[pos % 4]);
+    Ok(Frame { opcode, payload })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn upgrade_request(headers: &[(&str, &str)]) -> http::Request {
+        let mut all: HashMap<_, _> = [
+            ("upgrade", "websocket"),
+            ("connection", "keep-alive, Upgrade"),
+            ("sec-websocket-version", "13"),
+            ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        for (name, value) in headers {
+            all.insert(name.to_string(), value.to_string());
+        }
+        http::Request {
+            method: s!("GET"),
+            path: s!("/events"),
+            headers: all,
+            body: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let mut response = vec![];
+        handshake(&upgrade_request(&[]), &mut response)
+            .await
+            .unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        // Example from RFC 6455, section 1.3
+        assert!(response.contains(
+            "Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"
+        ));
+
+        let mut response = vec![];
+        for (headers, status) in &[
+            (&[("upgrade", "h2c")][..], 400),
+            (&[("connection", "close")][..], 400),
+            (&[("sec-websocket-version", "8")][..], 426),
+            (&[("sec-websocket-key", "c2hvcnQ=")][..], 400),
+            (&[("sec-websocket-key", "not base64!")][..], 400),
+        ] {
+            let err = handshake(&upgrade_request(headers), &mut response)
+                .await
+                .unwrap_err();
+            assert_eq!(err.status().0, *status, "{:?}", headers);
+        }
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_write_frame() {
+        for (len, head) in &[
+            (5usize, vec![0x81, 5]),
+            (200, vec![0x81, 126, 0, 200]),
+            (70000, vec![0x81, 127, 0, 0, 0, 0, 0, 1, 0x11, 0x70]),
+        ] {
+            let mut data = vec![];
+            write_frame(&mut data, Opcode::Text, &vec![b'a'; *len])
+                .await
+                .unwrap();
+            assert_eq!(&data[..head.len()], &head[..]);
+            assert_eq!(data.len(), head.len() + len);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_frame() {
+        // Masked ping with "Hello" payload from RFC 6455, section 5.7
+        let ping: &[u8] = &[
+            0x89, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        let frame = read_frame(&mut &ping[..]).await.unwrap();
+        assert_eq!(frame.opcode, Opcode::Ping);
+        assert_eq!(frame.payload, b"Hello");
+
+        let close: &[u8] = &[0x88, 0x80, 1, 2, 3, 4];
+        let frame = read_frame(&mut &close[..]).await.unwrap();
+        assert_eq!(frame.opcode, Opcode::Close);
+        assert!(frame.payload.is_empty());
+
+        let unmasked: &[u8] = &[0x81, 0x05, b'H', b'e', b'l', b'l', b'o'];
+        assert!(matches!(
+            read_frame(&mut &unmasked[..]).await,
+            Err(Error::Unmasked)
+        ));
+        let unknown: &[u8] = &[0x83, 0x80, 1, 2, 3, 4];
+        assert!(matches!(
+            read_frame(&mut &unknown[..]).await,
+            Err(Error::UnknownOpcode(3))
+        ));
+        let reserved: &[u8] = &[0xC1, 0x80, 1, 2, 3, 4];
+        assert!(matches!(
+            read_frame(&mut &reserved[..]).await,
+            Err(Error::ReservedBits)
+        ));
+        let fragmented_ping: &[u8] = &[0x09, 0x80, 1, 2, 3, 4];
+        assert!(matches!(
+            read_frame(&mut &fragmented_ping[..]).await,
+            Err(Error::InvalidControl)
+        ));
+        let large_ping: &[u8] = &[0x89, 0xFE, 0, 126];
+        assert!(matches!(
+            read_frame(&mut &large_ping[..]).await,
+            Err(Error::InvalidControl)
+        ));
+        let large: &[u8] = &[0x82, 0xFF, 0, 0, 0, 0, 0, 1, 0, 1];
+        assert!(matches!(
+            read_frame(&mut &large[..]).await,
+            Err(Error::FrameTooLarge)
+        ));
+        let truncated: &[u8] = &[0x81, 0x85, 1, 2, 3, 4, b'H'];
+        assert!(matches!(
+            read_frame(&mut &truncated[..]).await,
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -16,17 +16,24 @@ use std::collections::HashMap;
 use lnpbp::bp;
 use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
 
+use crate::auth::ClientAuth;
 use crate::constants::*;
 use crate::rgbd::ContractName;
+use crate::util::WalletId;
 
 #[derive(Clone, PartialEq, Eq, Debug, Display)]
 #[display(Debug)]
 pub struct Config {
     pub stash_endpoint: ZmqSocketAddr,
+    pub stash_pub_endpoint: ZmqSocketAddr,
     pub contract_endpoints: HashMap<ContractName, ZmqSocketAddr>,
+    pub contract_pub_endpoints: HashMap<ContractName, ZmqSocketAddr>,
     pub network: bp::Chain,
+    pub wallet: WalletId,
     pub threaded: bool,
     pub data_dir: String,
+    /// CurveZMQ settings for the connections to the node services
+    pub auth: ClientAuth,
 }
 
 impl Default for Config {
@@ -35,17 +42,69 @@ impl Default for Config {
             stash_endpoint: STASHD_RPC_ENDPOINT
                 .parse()
                 .expect("Error in STASHD_RPC_ENDPOINT constant value"),
+            stash_pub_endpoint: STASHD_PUB_ENDPOINT
+                .parse()
+                .expect("Error in STASHD_PUB_ENDPOINT constant value"),
             contract_endpoints: map! {
                 ContractName::Fungible
                     => FUNGIBLED_RPC_ENDPOINT
                         .parse()
                         .expect("Error in FUNGIBLED_RPC_ENDPOINT constant value")
             },
+            contract_pub_endpoints: map! {
+                ContractName::Fungible
+                    => FUNGIBLED_PUB_ENDPOINT
+                        .parse()
+                        .expect("Error in FUNGIBLED_PUB_ENDPOINT constant value")
+            },
             network: RGB_NETWORK
                 .parse()
                 .expect("Error in RGB_NETWORK constant value"),
+            wallet: WalletId::default(),
             threaded: true,
             data_dir: RGB_DATA_DIR.to_string(),
+            auth: ClientAuth::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Configuration for connecting to the daemons which rgbd runs for the
+    /// given network and default wallet within the data directory, using
+    /// default endpoints
+    pub fn with_network(network: bp::Chain, data_dir: &str) -> Self {
+        Self::with_wallet(network, WalletId::default(), data_dir)
+    }
+
+    /// Configuration for connecting to the daemons which rgbd runs for the
+    /// given network and wallet within the data directory, using default
+    /// endpoints
+    pub fn with_wallet(
+        network: bp::Chain,
+        wallet: WalletId,
+        data_dir: &str,
+    ) -> Self {
+        let endpoint = |template: &str| -> ZmqSocketAddr {
+            template
+                .replace("{data_dir}", data_dir)
+                .replace("{network}", &network.to_string())
+                .replace("{id}", wallet.as_str())
+                .parse()
+                .expect("Error in daemon endpoint constant value")
+        };
+        Self {
+            stash_endpoint: endpoint(STASHD_RPC_ENDPOINT),
+            stash_pub_endpoint: endpoint(STASHD_PUB_ENDPOINT),
+            contract_endpoints: map! {
+                ContractName::Fungible => endpoint(FUNGIBLED_RPC_ENDPOINT)
+            },
+            contract_pub_endpoints: map! {
+                ContractName::Fungible => endpoint(FUNGIBLED_PUB_ENDPOINT)
+            },
+            network: network.clone(),
+            wallet: wallet.clone(),
+            data_dir: data_dir.to_string(),
+            ..Config::default()
         }
     }
 }
@@ -12,7 +12,7 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 use crate::api::reply;
-use crate::error::ServiceErrorDomain;
+use crate::error::{ErrorCode, ServiceErrorDomain};
 
 #[derive(Debug, Display, Error, From)]
 #[display(Debug)]
@@ -34,3 +34,18 @@ pub enum Error {
 
     UnexpectedResponse,
 }
+
+impl Error {
+    /// Returns registered code of the error, which can be used to present
+    /// localized error message to the user
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::ServiceError(domain) => domain.code(),
+            Error::Reply(failure) => failure.error_code(),
+            Error::Base64(_) | Error::Bitcoin(_) | Error::Encoding(_) => {
+                ErrorCode::Presentation
+            }
+            Error::UnexpectedResponse => ErrorCode::ApiUnexpectedReply,
+        }
+    }
+}
@@ -27,32 +27,63 @@ use lnpbp::rgb::{Consignment, ContractId, PSBT_OUT_PUBKEY};
 
 use super::{Error, Runtime};
 use crate::api::{
-    fungible::AcceptApi, fungible::Issue, fungible::Request,
-    fungible::TransferApi, reply, Reply,
+    fungible::AcceptApi, fungible::HistoryApi, fungible::Issue,
+    fungible::Request, fungible::TransferApi, reply, stash::ValidateRequest,
+    Capabilities, Handshake, Hello, Reply, ValidationStatus,
 };
-use crate::error::ServiceErrorDomain;
+use crate::error::{NetworkMismatch, ServiceErrorDomain};
 use crate::fungible::{
-    Invoice, IssueStructure, Outcoincealed, Outcoins, Outpoint,
+    Balance, HistoryEntry, Invoice, IssueStructure, Outcoincealed, Outcoins,
+    Outpoint,
 };
 use crate::util::file::ReadWrite;
 use crate::util::SealSpec;
 use crate::DataFormat;
 
 impl Runtime {
-    fn command(
+    pub(super) fn request(
         &mut self,
-        command: Request,
+        request: Request,
     ) -> Result<Arc<Reply>, ServiceErrorDomain> {
-        let data = command.encode()?;
+        let data = request.encode()?;
         self.session_rpc.send_raw_message(&data)?;
         let raw = self.session_rpc.recv_raw_message()?;
         let reply = self.unmarshaller.unmarshall(&raw)?;
         Ok(reply)
     }
 
+    fn handshake(&mut self) -> Result<&Handshake, ServiceErrorDomain> {
+        if self.handshake.is_pending() {
+            let reply = self.request(Request::Hello(Hello::with("rgb-i9n")))?;
+            self.handshake = Handshake::with_reply(&reply)?;
+            if let Some(capabilities) = self.handshake.capabilities() {
+                NetworkMismatch::check(
+                    &self.config.network,
+                    &capabilities.network,
+                )?;
+            }
+        }
+        Ok(&self.handshake)
+    }
+
+    fn command(
+        &mut self,
+        command: Request,
+    ) -> Result<Arc<Reply>, ServiceErrorDomain> {
+        self.handshake()?.check_request(command.name())?;
+        self.request(command)
+    }
+
+    /// Returns capabilities reported by the fungible daemon, or `None` if
+    /// the daemon is of an outdated version not supporting the handshake.
+    /// Fails if the daemon uses incompatible API protocol version.
+    pub fn capabilities(&mut self) -> Result<Option<Capabilities>, Error> {
+        Ok(self.handshake()?.capabilities().cloned())
+    }
+
     pub fn issue(
         &mut self,
-        _network: bp::Chain,
+        network: bp::Chain,
         ticker: String,
         title: String,
         description: Option<String>,
@@ -61,7 +92,8 @@ impl Runtime {
         precision: u8,
         _prune_seals: Vec<SealSpec>,
     ) -> Result<(), Error> {
-        // TODO: Make sure we use the same network
+        NetworkMismatch::check(&self.config.network, &network)
+            .map_err(ServiceErrorDomain::from)?;
         let (supply, inflatable) = match issue_structure {
             IssueStructure::SingleIssue => (None, None),
             IssueStructure::MultipleIssues {
@@ -94,6 +126,9 @@ impl Runtime {
         consignment_file: String,
         transaction_file: String,
     ) -> Result<(), Error> {
+        invoice
+            .check_network(&self.config.network)
+            .map_err(ServiceErrorDomain::from)?;
         let seal_confidential = match invoice.outpoint {
             Outpoint::BlindedUtxo(outpoint_hash) => outpoint_hash,
             Outpoint::Address(_address) => unimplemented!(),
@@ -160,6 +195,11 @@ impl Runtime {
         consignment: Consignment,
         reveal_outpoints: Vec<bp::blind::OutpointReveal>,
     ) -> Result<(), Error> {
+        NetworkMismatch::check(
+            &self.config.network,
+            consignment.genesis.chain(),
+        )
+        .map_err(ServiceErrorDomain::from)?;
         let api = AcceptApi {
             consignment,
             reveal_outpoints,
@@ -176,14 +216,21 @@ impl Runtime {
         }
     }
 
-    pub fn validate(&mut self, consignment: Consignment) -> Result<(), Error> {
-        match &*self.command(Request::Validate(consignment))? {
+    pub fn validate(
+        &mut self,
+        consignment: Consignment,
+    ) -> Result<ValidationStatus, Error> {
+        NetworkMismatch::check(
+            &self.config.network,
+            consignment.genesis.chain(),
+        )
+        .map_err(ServiceErrorDomain::from)?;
+        match &*self.command(Request::Validate(ValidateRequest {
+            consignment,
+            min_confirmations: None,
+        }))? {
             Reply::Failure(failure) => Err(Error::Reply(failure.clone())),
-            Reply::Success => {
-                println!("Validate succeeded");
-
-                Ok(())
-            }
+            Reply::ValidationStatus(status) => Ok(status.clone()),
             _ => Err(Error::UnexpectedResponse),
         }
     }
@@ -215,6 +262,34 @@ impl Runtime {
         }
     }
 
+    pub fn balance(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<Balance, Error> {
+        match &*self.command(Request::Balance(contract_id))? {
+            Reply::Balance(balance) => Ok(*balance),
+            Reply::Failure(failmsg) => Err(Error::Reply(failmsg.clone())),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn history(
+        &mut self,
+        contract_id: ContractId,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<HistoryEntry>, Error> {
+        match &*self.command(Request::History(HistoryApi {
+            contract_id,
+            offset,
+            limit,
+        }))? {
+            Reply::History(history) => Ok(history.clone()),
+            Reply::Failure(failmsg) => Err(Error::Reply(failmsg.clone())),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
     pub fn sync(
         &mut self,
         data_format: DataFormat,
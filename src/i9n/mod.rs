@@ -15,7 +15,13 @@ mod config;
 mod error;
 mod fungible;
 mod runtime;
+mod subscription;
 
 pub use config::Config;
 pub use error::Error;
 pub use runtime::Runtime;
+pub use subscription::Subscription;
+
+pub use crate::api::Capabilities;
+pub use crate::auth::{ClientAuth, CurveKeys, CurvePublicKey};
+pub use crate::error::ErrorCode;
@@ -19,7 +19,10 @@ use lnpbp::lnp::{
 };
 
 use super::Config;
-use crate::api::Reply;
+use crate::api::{fungible::Request, Handshake, Reply};
+use crate::constants::{
+    RGBD_HEALTH_FILE, RGBD_RPC_ENDPOINT, STASHD_RPC_ENDPOINT,
+};
 use crate::error::BootstrapError;
 use crate::rgbd::{self, ContractName};
 
@@ -28,48 +31,82 @@ pub struct Runtime {
     pub(super) session_rpc:
         session::Raw<PlainTranscoder, transport::zmqsocket::Connection>,
     pub(super) unmarshaller: Unmarshaller<Reply>,
+    pub(super) handshake: Handshake,
+    /// Thread running embedded rgbd, if any
+    rgbd: Option<thread::JoinHandle<()>>,
 }
 
 impl Runtime {
     pub fn init(config: Config) -> Result<Self, BootstrapError> {
+        let fungible_rpc = config
+            .contract_endpoints
+            .get(&ContractName::Fungible)
+            .expect("Fungible engine is not connected in the configuration");
+
         // Start rgbd on a separate thread
+        let mut rgbd = None;
         if config.threaded {
-            let rgbd_opts = rgbd::Opts {
+            let mut rgbd_config = rgbd::Config {
                 verbose: 5,
-                bin_dir: String::new(),
-                data_dir: config.data_dir.clone(),
+                data_dir: config.data_dir.clone().into(),
                 contracts: config
                     .contract_endpoints
                     .iter()
                     .map(|(k, _)| k.clone())
                     .collect(),
-                network: config.network.clone(),
                 threaded: true,
+                ..rgbd::Config::default()
             };
+            rgbd_config.daemon_sets = vec![rgbd::DaemonSet {
+                network: config.network.clone(),
+                wallet: config.wallet.clone(),
+                stash_rpc: rgbd_config.parse_daemon_param(
+                    &config.network,
+                    &config.wallet,
+                    "stash-rpc",
+                    STASHD_RPC_ENDPOINT.to_string(),
+                )?,
+                fungible_rpc: fungible_rpc.clone(),
+            }];
+            rgbd_config.health_file = rgbd_config
+                .parse_param("health-file", RGBD_HEALTH_FILE.to_string())?;
+            rgbd_config.rpc_endpoint = rgbd_config
+                .parse_param("rpc", RGBD_RPC_ENDPOINT.to_string())?;
+            rgbd_config.auth = config.auth;
 
-            thread::spawn(move || {
+            rgbd = Some(thread::spawn(move || {
                 let mut rt = tokio::runtime::Runtime::new().unwrap();
                 rt.block_on(async {
-                    rgbd::main_with_config(rgbd_opts.into()).await.unwrap();
+                    rgbd::main_with_config(rgbd_config).await.unwrap();
                 });
-            });
+            }));
         }
 
-        let session_rpc = session::Raw::with_zmq_unencrypted(
-            ZmqType::Req,
-            config
-                .contract_endpoints
-                .get(&ContractName::Fungible)
-                .expect(
-                    "Fungible engine is not connected in the configuration",
-                ),
-            None,
-            None,
-        )?;
+        let session_rpc = config.auth.connect(ZmqType::Req, fungible_rpc)?;
         Ok(Self {
             config,
             session_rpc,
             unmarshaller: Reply::create_unmarshaller(),
+            handshake: Handshake::Pending,
+            rgbd,
         })
     }
 }
+
+impl Drop for Runtime {
+    /// Shuts down embedded rgbd and waits for all its daemons to flush their
+    /// state; when fungibled exits, rgbd stops the rest of the daemons
+    fn drop(&mut self) {
+        let rgbd = match self.rgbd.take() {
+            Some(rgbd) => rgbd,
+            None => return,
+        };
+        if let Err(err) = self.request(Request::Shutdown()) {
+            error!("Unable to shut down embedded RGB node: {}", err);
+            return;
+        }
+        if rgbd.join().is_err() {
+            error!("Embedded RGB node has failed during shutdown");
+        }
+    }
+}
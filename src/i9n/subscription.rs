@@ -0,0 +1,95 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::sync::Arc;
+
+use lnpbp::lnp::transport::zmqsocket::{ZmqSocketAddr, ZmqType};
+use lnpbp::lnp::{
+    session, transport, CreateUnmarshaller, PlainTranscoder, Session,
+    Unmarshall, Unmarshaller,
+};
+
+use super::{Error, Runtime};
+use crate::api::Event;
+use crate::auth::ClientAuth;
+use crate::error::ServiceErrorDomain;
+use crate::rgbd::ContractName;
+
+/// Subscription to the events published by a stash or contract daemon.
+/// Subscription owns its own socket, so it can be moved to a separate thread
+/// and polled independently from the RPC requests made with [`Runtime`].
+pub struct Subscription {
+    session_sub:
+        session::Raw<PlainTranscoder, transport::zmqsocket::Connection>,
+    unmarshaller: Unmarshaller<Event>,
+}
+
+impl Subscription {
+    fn with(
+        endpoint: &ZmqSocketAddr,
+        auth: &ClientAuth,
+    ) -> Result<Self, ServiceErrorDomain> {
+        let session_sub = auth.connect(ZmqType::Sub, endpoint)?;
+        session_sub
+            .as_socket()
+            .set_subscribe(&[])
+            .map_err(transport::Error::from)?;
+        Ok(Self {
+            session_sub,
+            unmarshaller: Event::create_unmarshaller(),
+        })
+    }
+
+    /// Blocks until the next event is received
+    pub fn next_event(&mut self) -> Result<Arc<Event>, Error> {
+        Ok(self.recv_event()?)
+    }
+
+    /// Returns the next event if it is already received, or `None` otherwise
+    pub fn try_next_event(&mut self) -> Result<Option<Arc<Event>>, Error> {
+        let ready = self.session_sub.as_socket().poll(zmq::POLLIN, 0).map_err(
+            |err| ServiceErrorDomain::from(transport::Error::from(err)),
+        )?;
+        if ready == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.recv_event()?))
+    }
+
+    fn recv_event(&mut self) -> Result<Arc<Event>, ServiceErrorDomain> {
+        let raw = self.session_sub.recv_raw_message()?;
+        let event = self.unmarshaller.unmarshall(&raw)?;
+        Ok(event)
+    }
+}
+
+impl Runtime {
+    /// Subscribes to the events published by the fungible contract daemon
+    pub fn subscribe(&self) -> Result<Subscription, Error> {
+        let endpoint = self
+            .config
+            .contract_pub_endpoints
+            .get(&ContractName::Fungible)
+            .expect("Fungible engine is not connected in the configuration");
+        Ok(Subscription::with(endpoint, &self.config.auth)?)
+    }
+
+    /// Subscribes to the events published by the stash daemon, like merges
+    /// of consignments and status changes of their witness transactions
+    pub fn subscribe_stash(&self) -> Result<Subscription, Error> {
+        Ok(Subscription::with(
+            &self.config.stash_pub_endpoint,
+            &self.config.auth,
+        )?)
+    }
+}
@@ -43,6 +43,8 @@ pub extern crate diesel;
 
 #[cfg(any(feature = "node", feature = "client"))]
 pub mod api;
+#[cfg(any(feature = "node", feature = "client"))]
+pub mod auth;
 #[cfg(feature = "cli")]
 pub mod cli;
 pub mod constants;
@@ -53,6 +55,8 @@ pub mod util;
 
 #[cfg(any(feature = "node", feature = "client"))]
 mod contracts;
+#[cfg(feature = "node")]
+pub mod gateway;
 #[cfg(any(feature = "node", feature = "client"))]
 pub mod rgbd;
 #[cfg(any(feature = "node", feature = "client"))]
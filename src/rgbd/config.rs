@@ -12,12 +12,24 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 use clap::{ArgEnum, Clap};
+use core::convert::TryFrom;
+use core::fmt::Display;
+use core::str::FromStr;
+use core::time::Duration;
 use lnpbp::bp;
+use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use serde::Deserialize;
 
+use crate::auth::{ClientAuth, ServerAuth};
 use crate::constants::*;
+use crate::error::ConfigError;
+#[cfg(feature = "shell")]
+use crate::util::config::LayeredOpts;
+use crate::util::file::utf8_path;
+use crate::util::WalletId;
 
 #[derive(Clap)]
 #[clap(
@@ -39,17 +51,142 @@ pub struct Opts {
     #[clap(short, long, default_value = RGB_DATA_DIR, env = "RGB_DATA_DIR")]
     pub data_dir: String,
 
+    /// Path to the configuration file; if not given, `rgb.toml` from the data
+    /// directory is used when present
+    #[clap(long, env = "RGB_CONFIG")]
+    pub config: Option<String>,
+
     /// Contract daemons to launch
-    #[clap(arg_enum, long = "contract", default_value = RGB_CONTRACTS, env = "RGB_CONTRACTS")]
+    #[clap(
+        arg_enum,
+        long = "contract",
+        default_value = RGB_CONTRACTS,
+        env = "RGB_CONTRACTS",
+        use_delimiter = true
+    )]
     pub contracts: Vec<ContractName>,
 
     /// Run services as threads instead of daemons
     #[clap(short, long)]
     pub threaded: bool,
 
-    /// Bitcoin network to use
-    #[clap(short, long, default_value = RGB_NETWORK, env = "RGB_NETWORK")]
-    pub network: bp::Chain,
+    /// Bitcoin networks to serve; a separate set of daemons is launched for
+    /// each of them
+    #[clap(
+        short,
+        long = "network",
+        default_value = RGB_NETWORK,
+        env = "RGB_NETWORK",
+        use_delimiter = true
+    )]
+    pub networks: Vec<bp::Chain>,
+
+    /// Wallets to serve; a separate set of daemons with its own stash and
+    /// cache is launched for each of them on each network
+    #[clap(
+        long = "wallet",
+        default_value = RGB_WALLET,
+        env = "RGB_WALLETS",
+        use_delimiter = true
+    )]
+    pub wallets: Vec<WalletId>,
+
+    /// Policy for restarting daemons which have exited
+    #[clap(
+        arg_enum,
+        long = "restart",
+        default_value = RGBD_RESTART_POLICY,
+        env = "RGB_RESTART"
+    )]
+    pub restart_policy: RestartPolicy,
+
+    /// Maximal number of consecutive daemon restarts; when exceeded, the
+    /// whole node is shut down
+    #[clap(
+        long,
+        default_value = RGBD_MAX_RESTARTS,
+        env = "RGB_MAX_RESTARTS"
+    )]
+    pub max_restarts: u32,
+
+    /// Time (in seconds) to wait for stashd to become ready before launching
+    /// contract daemons
+    #[clap(
+        long,
+        default_value = RGBD_STARTUP_TIMEOUT,
+        env = "RGB_STARTUP_TIMEOUT"
+    )]
+    pub startup_timeout: u64,
+
+    /// Path to the file where health of the daemons is reported in JSON
+    /// format
+    #[clap(
+        long,
+        default_value = RGBD_HEALTH_FILE,
+        env = "RGB_HEALTH_FILE"
+    )]
+    pub health_file: String,
+
+    /// ZMQ socket address string for stashd REQ/REP API, used to detect
+    /// stashd readiness and to shut it down. Must contain `{network}` and
+    /// `{id}` placeholders if multiple networks or wallets are served
+    #[clap(
+        long,
+        default_value = STASHD_RPC_ENDPOINT,
+        env = "RGB_STASHD_RPC"
+    )]
+    pub stash_rpc: String,
+
+    /// ZMQ socket address string for fungibled REQ/REP API, used to shut it
+    /// down. Must contain `{network}` and `{id}` placeholders if multiple
+    /// networks or wallets are served
+    #[clap(
+        long,
+        default_value = FUNGIBLED_RPC_ENDPOINT,
+        env = "RGB_FUNGIBLED_RPC"
+    )]
+    pub fungible_rpc: String,
+
+    /// ZMQ socket address string for rgbd RPC API
+    #[clap(
+        long = "rpc",
+        default_value = RGBD_RPC_ENDPOINT,
+        env = "RGB_RGBD_RPC"
+    )]
+    pub rpc_endpoint: String,
+
+    /// TCP socket address for exporting metrics in Prometheus text format
+    /// over HTTP; metrics are not exported if not given
+    #[clap(long = "metrics", env = "RGB_RGBD_METRICS")]
+    pub metrics_endpoint: Option<String>,
+
+    /// Z85-encoded public key of the node, if CurveZMQ is used
+    #[clap(long, env = "RGB_CURVE_PUBLIC")]
+    pub curve_public: Option<String>,
+
+    /// Z85-encoded secret key of the node matching `--curve-public`
+    #[clap(long, env = "RGB_CURVE_SECRET", hide_env_values = true)]
+    pub curve_secret: Option<String>,
+}
+
+/// Policy for restarting daemons which have exited
+#[derive(Clap, Clone, Copy, PartialEq, Eq, Hash, Debug, Display)]
+pub enum RestartPolicy {
+    /// Never restart daemons; node is shut down when any of them exits
+    #[display("never")]
+    Never,
+
+    /// Always restart daemons, even if they have exited without an error
+    #[display("always")]
+    Always,
+
+    /// Restart daemons only if they have failed
+    #[display("on-failure")]
+    OnFailure,
+
+    /// Restart failed daemons with exponentially increasing delays
+    #[display("backoff")]
+    Backoff,
 }
 
 #[derive(Clap, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
@@ -77,34 +214,162 @@ impl ContractName {
     }
 }
 
+/// Bitcoin network and wallet served by rgbd together with RPC endpoints of
+/// the daemons launched for them
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct DaemonSet {
+    pub network: bp::Chain,
+    pub wallet: WalletId,
+    pub stash_rpc: ZmqSocketAddr,
+    pub fungible_rpc: ZmqSocketAddr,
+}
+
+impl DaemonSet {
+    /// Returns RPC endpoint of the daemon with a given name
+    pub fn daemon_rpc(&self, daemon: &str) -> Option<&ZmqSocketAddr> {
+        match daemon {
+            "stashd" => Some(&self.stash_rpc),
+            "fungibled" => Some(&self.fungible_rpc),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Display)]
 #[display(Debug)]
 pub struct Config {
+    /// Configuration file given explicitly, which is passed to the daemons
+    pub config_file: Option<PathBuf>,
     pub data_dir: PathBuf,
     pub bin_dir: PathBuf,
     pub threaded: bool,
     pub contracts: Vec<ContractName>,
-    pub network: bp::Chain,
+    /// Networks and wallets served by the node, in the order of daemon launch
+    pub daemon_sets: Vec<DaemonSet>,
     pub verbose: u8,
+    pub restart_policy: RestartPolicy,
+    pub max_restarts: u32,
+    pub startup_timeout: Duration,
+    pub health_file: PathBuf,
+    pub rpc_endpoint: ZmqSocketAddr,
+    pub metrics_endpoint: Option<SocketAddr>,
+    /// CurveZMQ settings used for connecting to the launched daemons
+    pub auth: ClientAuth,
+    /// CurveZMQ settings used for rgbd RPC API
+    pub rpc_auth: ServerAuth,
 }
 
-impl From<Opts> for Config {
-    fn from(opts: Opts) -> Self {
-        Self {
+#[cfg(feature = "shell")]
+impl LayeredOpts for Opts {
+    const FILE_PARAMS: &'static [(&'static str, &'static str)] = &[
+        ("data-dir", "data-dir"),
+        ("network", "networks"),
+        ("curve-public", "curve-public"),
+        ("curve-secret", "curve-secret"),
+        ("rgbd.bin-dir", "bin-dir"),
+        ("rgbd.contracts", "contracts"),
+        ("rgbd.wallets", "wallets"),
+        ("rgbd.restart", "restart-policy"),
+        ("rgbd.max-restarts", "max-restarts"),
+        ("rgbd.startup-timeout", "startup-timeout"),
+        ("rgbd.health-file", "health-file"),
+        ("rgbd.rpc", "rpc-endpoint"),
+        ("rgbd.metrics", "metrics-endpoint"),
+        ("stashd.rpc", "stash-rpc"),
+        ("fungibled.rpc", "fungible-rpc"),
+    ];
+
+    fn config_file(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    fn data_dir(&self) -> &str {
+        &self.data_dir
+    }
+}
+
+impl TryFrom<Opts> for Config {
+    type Error = ConfigError;
+
+    fn try_from(opts: Opts) -> Result<Self, Self::Error> {
+        let mut me = Self {
+            config_file: opts.config.map(PathBuf::from),
             data_dir: opts.data_dir.into(),
             bin_dir: opts.bin_dir.into(),
             threaded: opts.threaded,
-            network: opts.network,
             contracts: opts.contracts,
             verbose: opts.verbose,
+            restart_policy: opts.restart_policy,
+            max_restarts: opts.max_restarts,
+            startup_timeout: Duration::from_secs(opts.startup_timeout),
             ..Config::default()
+        };
+        me.health_file = me.parse_param("health-file", opts.health_file)?;
+        let networks = unique_values("network", opts.networks)?;
+        let wallets = unique_values("wallet", opts.wallets)?;
+        for (name, param) in &[
+            ("stash-rpc", &opts.stash_rpc),
+            ("fungible-rpc", &opts.fungible_rpc),
+        ] {
+            for (placeholder, count) in
+                &[("{network}", networks.len()), ("{id}", wallets.len())]
+            {
+                if *count > 1 && !param.contains(placeholder) {
+                    Err(ConfigError::InvalidParam {
+                        param: name.to_string(),
+                        value: param.to_string(),
+                        details: format!(
+                            "`{}` placeholder is required to serve multiple \
+                             networks and wallets",
+                            placeholder
+                        ),
+                    })?
+                }
+            }
+        }
+        let mut daemon_sets = vec![];
+        for network in &networks {
+            for wallet in &wallets {
+                daemon_sets.push(DaemonSet {
+                    stash_rpc: me.parse_daemon_param(
+                        network,
+                        wallet,
+                        "stash-rpc",
+                        opts.stash_rpc.clone(),
+                    )?,
+                    fungible_rpc: me.parse_daemon_param(
+                        network,
+                        wallet,
+                        "fungible-rpc",
+                        opts.fungible_rpc.clone(),
+                    )?,
+                    network: network.clone(),
+                    wallet: wallet.clone(),
+                });
+            }
         }
+        me.daemon_sets = daemon_sets;
+        me.rpc_endpoint = me.parse_param("rpc", opts.rpc_endpoint)?;
+        me.metrics_endpoint = opts
+            .metrics_endpoint
+            .map(|ep| me.parse_param("metrics", ep))
+            .transpose()?;
+        me.auth = ClientAuth::from_opts(
+            opts.curve_public.clone(),
+            opts.curve_public.clone(),
+            opts.curve_secret.clone(),
+        )?;
+        me.rpc_auth =
+            ServerAuth::node_only(opts.curve_public, opts.curve_secret)?;
+        Ok(me)
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            config_file: None,
             data_dir: RGB_DATA_DIR
                 .parse()
                 .expect("Error in RGB_DATA_DIR constant value"),
@@ -114,10 +379,101 @@ impl Default for Config {
             threaded: false,
             contracts: vec![ContractName::from_str(RGB_CONTRACTS, false)
                 .expect("Error in RGB_CONTRACTS constant value")],
-            network: RGB_NETWORK
-                .parse()
-                .expect("Error in RGB_NETWORK constant value"),
+            daemon_sets: vec![DaemonSet {
+                network: RGB_NETWORK
+                    .parse()
+                    .expect("Error in RGB_NETWORK constant value"),
+                wallet: WalletId::default(),
+                stash_rpc: STASHD_RPC_ENDPOINT
+                    .parse()
+                    .expect("Error in STASHD_RPC_ENDPOINT constant value"),
+                fungible_rpc: FUNGIBLED_RPC_ENDPOINT
+                    .parse()
+                    .expect("Error in FUNGIBLED_RPC_ENDPOINT constant value"),
+            }],
             verbose: 0,
+            restart_policy: RestartPolicy::from_str(RGBD_RESTART_POLICY, false)
+                .expect("Error in RGBD_RESTART_POLICY constant value"),
+            max_restarts: RGBD_MAX_RESTARTS
+                .parse()
+                .expect("Error in RGBD_MAX_RESTARTS constant value"),
+            startup_timeout: Duration::from_secs(
+                RGBD_STARTUP_TIMEOUT
+                    .parse()
+                    .expect("Error in RGBD_STARTUP_TIMEOUT constant value"),
+            ),
+            health_file: RGBD_HEALTH_FILE
+                .parse()
+                .expect("Error in RGBD_HEALTH_FILE constant value"),
+            rpc_endpoint: RGBD_RPC_ENDPOINT
+                .parse()
+                .expect("Error in RGBD_RPC_ENDPOINT constant value"),
+            metrics_endpoint: None,
+            auth: ClientAuth::default(),
+            rpc_auth: ServerAuth::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Parses value of the parameter specific to the daemons serving given
+    /// network and wallet
+    pub fn parse_daemon_param<T>(
+        &self,
+        network: &bp::Chain,
+        wallet: &WalletId,
+        name: &str,
+        param: String,
+    ) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.parse_param(
+            name,
+            param
+                .replace("{network}", &network.to_string())
+                .replace("{id}", wallet.as_str()),
+        )
+    }
+
+    /// Parses value of the parameter with a given name, replacing
+    /// placeholders with the actual values
+    pub fn parse_param<T>(
+        &self,
+        name: &str,
+        param: String,
+    ) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        param
+            .replace("{data_dir}", utf8_path("data-dir", &self.data_dir)?)
+            .parse()
+            .map_err(|err: T::Err| ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: param.clone(),
+                details: err.to_string(),
+            })
+    }
+}
+
+/// Checks that none of the values is given more than once
+fn unique_values<T>(name: &str, values: Vec<T>) -> Result<Vec<T>, ConfigError>
+where
+    T: PartialEq + Display,
+{
+    let mut unique: Vec<T> = vec![];
+    for value in values {
+        if unique.contains(&value) {
+            Err(ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: value.to_string(),
+                details: format!("{} is given more than once", name),
+            })?
         }
+        unique.push(value);
     }
+    Ok(unique)
 }
@@ -0,0 +1,88 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::api::status::{DaemonHealth, DaemonStatus};
+
+/// Health of all daemons supervised by rgbd, which is reported to the health
+/// file every time it changes
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct Health {
+    file: PathBuf,
+    daemons: BTreeMap<String, DaemonHealth>,
+    last_error: Option<String>,
+}
+
+impl Health {
+    pub fn with(file: PathBuf) -> Self {
+        Self {
+            file,
+            daemons: BTreeMap::new(),
+            last_error: None,
+        }
+    }
+
+    pub fn daemons(&self) -> &BTreeMap<String, DaemonHealth> {
+        &self.daemons
+    }
+
+    /// Last error with which any of the daemons has failed
+    pub fn last_error(&self) -> Option<&String> {
+        self.last_error.as_ref()
+    }
+
+    /// Updates daemon status
+    pub fn set_status(&mut self, daemon: &str, status: DaemonStatus) {
+        let health = self.daemons.entry(daemon.to_string()).or_default();
+        if health.status != status {
+            info!("Daemon {} is {}", daemon, status);
+        }
+        if status == DaemonStatus::Restarting {
+            health.restarts += 1;
+        }
+        health.status = status;
+        self.report();
+    }
+
+    /// Records the error with which daemon has failed
+    pub fn set_error(&mut self, daemon: &str, error: String) {
+        self.daemons
+            .entry(daemon.to_string())
+            .or_default()
+            .last_error = Some(error.clone());
+        self.last_error = Some(format!("{}: {}", daemon, error));
+        self.report();
+    }
+
+    /// Writes health information to the health file. Failures are only
+    /// logged, since they must not affect the daemons.
+    fn report(&self) {
+        let json = match serde_json::to_string_pretty(&self.daemons) {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("Unable to serialize health information: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = fs::write(&self.file, json) {
+            warn!(
+                "Unable to write health information to {:?}: {}",
+                self.file, err
+            );
+        }
+    }
+}
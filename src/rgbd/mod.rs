@@ -12,9 +12,15 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 mod config;
+#[cfg(feature = "node")]
+mod health;
+#[cfg(feature = "node")]
+mod rpc;
 mod runtime;
 
-pub use config::{Config, ContractName, Opts};
+pub use config::{Config, ContractName, DaemonSet, Opts, RestartPolicy};
+#[cfg(feature = "node")]
+pub use health::Health;
 pub use runtime::Runtime;
 
 #[cfg(feature = "node")]
@@ -0,0 +1,205 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! RPC API of rgbd, reporting status of the node and its daemons
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use futures::executor::block_on;
+use lnpbp::bp;
+use lnpbp::lnp::presentation::Encode;
+use lnpbp::lnp::transport::zmqsocket::{ZmqSocketAddr, ZmqType};
+use lnpbp::lnp::{CreateUnmarshaller, Session, Unmarshall, Unmarshaller};
+use tokio::sync::{mpsc, oneshot};
+
+use super::runtime::Supervision;
+use super::Config;
+use crate::api::hello::{self, Capabilities, Hello};
+use crate::api::rgbd::Request;
+use crate::api::Reply;
+use crate::auth::{self, Role, ServerAuth, ZmqSession};
+use crate::constants::RGB_PROTOCOL_VERSION;
+use crate::error::{
+    BootstrapError, RuntimeError, ServiceError, ServiceErrorDomain,
+    ServiceErrorSource,
+};
+use crate::util::metrics::Metrics;
+use crate::util::shutdown;
+
+/// Interval for checking whether the server must be stopped while waiting
+/// for RPC requests, in milliseconds
+const STOP_POLL_INTERVAL: i64 = 500;
+
+/// RPC server running on its own thread, since the supervisor loop must not
+/// be blocked by ZMQ. Health of the daemons is requested from the supervisor
+/// over the supervision channel.
+pub(super) struct RpcServer {
+    session: ZmqSession,
+    endpoint: ZmqSocketAddr,
+    auth: ServerAuth,
+    network: bp::Chain,
+    supervisor: mpsc::UnboundedSender<Supervision>,
+    unmarshaller: Unmarshaller<Request>,
+    metrics: Metrics,
+    stop: Arc<AtomicBool>,
+}
+
+/// Stops RPC server thread when dropped
+pub(super) struct RpcHandle(Arc<AtomicBool>);
+
+impl Drop for RpcHandle {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl RpcServer {
+    pub fn bind(
+        config: &Config,
+        supervisor: mpsc::UnboundedSender<Supervision>,
+    ) -> Result<Self, BootstrapError> {
+        let session =
+            config
+                .rpc_auth
+                .bind(ZmqType::Rep, &config.rpc_endpoint, "rgbd")?;
+        Ok(Self {
+            session,
+            endpoint: config.rpc_endpoint.clone(),
+            auth: config.rpc_auth.clone(),
+            // rgbd may serve several networks; handshake reports the first
+            // of them, which is the default one
+            network: config
+                .daemon_sets
+                .first()
+                .map(|set| set.network.clone())
+                .ok_or(BootstrapError::ArgParseError(s!(
+                    "no networks are configured"
+                )))?,
+            supervisor,
+            unmarshaller: Request::create_unmarshaller(),
+            metrics: Metrics::default(),
+            stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Starts serving requests on a separate thread, which runs until the
+    /// returned handle is dropped
+    pub fn spawn(self) -> RpcHandle {
+        let handle = RpcHandle(self.stop.clone());
+        thread::spawn(move || self.run());
+        handle
+    }
+
+    fn run(mut self) {
+        while !self.stop.load(Ordering::Relaxed) {
+            if let Err(err) = self.serve() {
+                error!("Error processing rgbd RPC request: {}", err);
+            }
+        }
+        let RpcServer {
+            session, endpoint, ..
+        } = self;
+        drop(session);
+        shutdown::remove_ipc_file(&endpoint);
+    }
+
+    fn serve(&mut self) -> Result<(), RuntimeError> {
+        let mut items = [self.session.as_socket().as_poll_item(zmq::POLLIN)];
+        zmq::poll(&mut items, STOP_POLL_INTERVAL).map_err(|err| {
+            RuntimeError::zmq_request(&self.endpoint.zmq_socket_string(), err)
+        })?;
+        if !items[0].is_readable() {
+            return Ok(());
+        }
+
+        let (raw, client) = auth::recv_authenticated(&mut self.session)?;
+        let role = self.auth.role(client.as_ref());
+        let reply = self.rpc_process(raw, role).unwrap_or_else(|err| err);
+        trace!("Preparing ZMQ RPC reply: {:?}", reply);
+        let data = reply.encode()?;
+        self.session.send_raw_message(&data)?;
+        Ok(())
+    }
+
+    fn rpc_process(
+        &mut self,
+        raw: Vec<u8>,
+        role: Role,
+    ) -> Result<Reply, Reply> {
+        let message = &*self.unmarshaller.unmarshall(&raw).map_err(|err| {
+            ServiceError::from_rpc(ServiceErrorSource::Broker, err)
+        })?;
+        debug!("Received ZMQ RPC request: {:?}", message);
+        role.check(message.required_role(), message.name())
+            .map_err(|err| ServiceError {
+                domain: err,
+                service: ServiceErrorSource::Broker,
+            })?;
+        let started = Instant::now();
+        let result = match message {
+            Request::Hello(hello) => self.rpc_hello(hello),
+            Request::Status() => self.rpc_status(),
+        };
+        self.metrics.record(
+            message.name(),
+            started.elapsed(),
+            result.as_ref().err().map(ToString::to_string),
+        );
+        Ok(result.map_err(|err| ServiceError {
+            domain: err,
+            service: ServiceErrorSource::Broker,
+        })?)
+    }
+
+    fn rpc_hello(
+        &mut self,
+        hello: &Hello,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!(
+            "Got HELLO from {} using protocol version {}",
+            hello.agent, hello.protocol_version
+        );
+        Ok(Reply::Capabilities(Capabilities {
+            protocol_version: RGB_PROTOCOL_VERSION,
+            agent: hello::agent("rgbd"),
+            requests: Request::NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            schemata: vec![],
+            network: self.network.clone(),
+            storage: s!("none"),
+            cache: s!("none"),
+        }))
+    }
+
+    fn rpc_status(&mut self) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got STATUS");
+        let (sender, receiver) = oneshot::channel();
+        let not_running = || {
+            ServiceErrorDomain::Internal(s!("rgbd supervisor is not running"))
+        };
+        self.supervisor
+            .send(Supervision::Status(sender))
+            .map_err(|_| not_running())?;
+        let health = block_on(receiver).map_err(|_| not_running())?;
+
+        let mut status = self.metrics.status(hello::agent("rgbd"));
+        status.daemons = health.daemons().clone();
+        status.last_error = health.last_error().cloned().or(status.last_error);
+        Ok(Reply::Status(status))
+    }
+}
@@ -11,20 +11,64 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
-#[cfg(any(feature = "node"))]
+#[cfg(all(feature = "node", not(feature = "shell")))]
 use clap::Clap;
 #[cfg(any(feature = "node"))]
-use futures::future::join_all;
+use core::convert::TryFrom;
+use core::time::Duration;
+#[cfg(any(feature = "node"))]
+use lnpbp::lnp::presentation::Encode;
+#[cfg(any(feature = "node"))]
+use lnpbp::lnp::transport::zmqsocket::{ZmqSocketAddr, ZmqType};
+#[cfg(any(feature = "node"))]
+use lnpbp::lnp::Session;
+#[cfg(any(feature = "node"))]
+use std::collections::HashMap;
+use std::process::ExitStatus;
+#[cfg(any(feature = "node"))]
+use std::time::Instant;
+#[cfg(any(feature = "node"))]
+use tokio::sync::{mpsc, oneshot};
+#[cfg(any(feature = "node"))]
+use tokio::time;
 use tokio::{process, task};
 
-use super::Config;
+#[cfg(any(feature = "node"))]
+use super::health::Health;
+#[cfg(any(feature = "node"))]
+use super::rpc::RpcServer;
+use super::{Config, DaemonSet, RestartPolicy};
+#[cfg(any(feature = "node"))]
+use crate::api::{self, status::DaemonStatus, Hello};
+#[cfg(any(feature = "node"))]
+use crate::auth::ClientAuth;
 #[cfg(any(feature = "node"))]
 use crate::contracts::fungible;
+#[cfg(any(feature = "node"))]
+use crate::error::ConfigError;
 use crate::error::{BootstrapError, RuntimeError};
 #[cfg(any(feature = "node"))]
 use crate::service::TryService;
 #[cfg(feature = "node")]
 use crate::stash;
+#[cfg(all(feature = "node", feature = "shell"))]
+use crate::util::config::LayeredOpts;
+#[cfg(feature = "node")]
+use crate::util::metrics::Exporter;
+#[cfg(feature = "node")]
+use crate::util::shutdown::Shutdown;
+
+/// Delay before restarting a daemon which has exited
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+/// Upper limit for the restart delay with [`RestartPolicy::Backoff`]
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+/// Daemon which has been running for longer than this period is considered
+/// healthy, so its counter of consecutive restarts is reset
+const STABLE_PERIOD: Duration = Duration::from_secs(60);
+/// Time given to a daemon to shut down gracefully before it gets killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time to wait for daemon reply to a single request, in milliseconds
+const REQUEST_TIMEOUT: i32 = 1000;
 
 pub struct Runtime {
     config: Config,
@@ -38,52 +82,234 @@ impl Runtime {
     #[cfg(any(feature = "node"))]
     fn get_task_for(
         name: &str,
-        args: &[&str],
+        args: &[String],
     ) -> Result<task::JoinHandle<Result<(), DaemonError>>, DaemonError> {
         match name {
             "stashd" => {
-                let opts = stash::Opts::parse_from(args.into_iter());
+                let opts = Self::daemon_opts::<stash::Opts>(args)
+                    .map_err(BootstrapError::from)?;
+                let config = stash::Config::try_from(opts)
+                    .map_err(BootstrapError::from)?;
                 Ok(task::spawn(async move {
-                    Ok(stash::main_with_config(opts.into()).await?)
+                    Ok(stash::main_with_config(config).await?)
                 }))
             }
             "fungibled" => {
-                let opts = fungible::Opts::parse_from(args.into_iter());
+                let opts = Self::daemon_opts::<fungible::Opts>(args)
+                    .map_err(BootstrapError::from)?;
+                let config = fungible::Config::try_from(opts)
+                    .map_err(BootstrapError::from)?;
                 Ok(task::spawn(async move {
-                    Ok(fungible::main_with_config(opts.into()).await?)
+                    Ok(fungible::main_with_config(config).await?)
                 }))
             }
             _ => Err(DaemonError::UnknownDaemon(name.into())),
         }
     }
 
+    /// Parses options of the daemon running in a thread, taking parameters
+    /// missing from the arguments from the configuration file
+    #[cfg(all(feature = "node", feature = "shell"))]
+    fn daemon_opts<O: LayeredOpts>(args: &[String]) -> Result<O, ConfigError> {
+        O::try_parse_layered_from(args)
+    }
+
+    /// Parses options of the daemon running in a thread; builds without
+    /// shell support do not read the configuration file
+    #[cfg(all(feature = "node", not(feature = "shell")))]
+    fn daemon_opts<O: Clap>(args: &[String]) -> Result<O, ConfigError> {
+        O::try_parse_from(args)
+            .map_err(|err| ConfigError::Args(err.to_string()))
+    }
+
+    /// Composes command line for the daemon, starting with its name. Daemons
+    /// get the same configuration file, data directory and verbosity as rgbd
+    /// itself, together with the network and wallet they serve; the rest of
+    /// their parameters are taken from their sections of the configuration
+    /// file.
     #[cfg(any(feature = "node"))]
-    fn daemon(&self, bin: &str) -> Result<DaemonHandle, DaemonError> {
-        let args = [
-            "-vvvv",
-            "--data-dir",
-            self.config
-                .data_dir
-                .to_str()
-                .expect("Datadir path is wrong"),
-        ];
+    fn daemon_args(&self, daemon: &Daemon) -> Vec<String> {
+        let mut args = vec![daemon.bin.clone()];
+        if self.config.verbose > 0 {
+            args.push(format!("-{}", "v".repeat(self.config.verbose as usize)));
+        }
+        if let Some(config_file) = &self.config.config_file {
+            args.push(s!("--config"));
+            args.push(config_file.display().to_string());
+        }
+        args.push(s!("--data-dir"));
+        args.push(self.config.data_dir.display().to_string());
+        args.push(s!("--network"));
+        args.push(daemon.set.network.to_string());
+        args.push(s!("--wallet"));
+        args.push(daemon.set.wallet.to_string());
+        args
+    }
+
+    #[cfg(any(feature = "node"))]
+    fn daemon(&self, daemon: &Daemon) -> Result<DaemonHandle, DaemonError> {
+        let bin = daemon.bin.as_str();
+        let args = self.daemon_args(daemon);
 
         if self.config.threaded {
-            Ok(DaemonHandle::Task(Self::get_task_for(bin, &args)?))
+            let stopper = Stopper {
+                auth: self.config.auth,
+                endpoint: daemon
+                    .set
+                    .daemon_rpc(bin)
+                    .ok_or_else(|| DaemonError::UnknownDaemon(bin.into()))?
+                    .clone(),
+                request: shutdown_request(bin)?,
+            };
+            Ok(DaemonHandle::Task(Self::get_task_for(bin, &args)?, stopper))
         } else {
-            let mut daemon = self.config.bin_dir.clone();
-            daemon.push(bin);
-            let mut cmd = process::Command::new(daemon);
-            cmd.args(&args);
+            let mut path = self.config.bin_dir.clone();
+            path.push(bin);
+            let mut cmd = process::Command::new(path);
+            cmd.args(&args[1..]).kill_on_drop(true);
             Ok(DaemonHandle::Process(cmd.spawn()?))
         }
     }
+
+    /// Launches daemon and spawns a task watching for its termination, which
+    /// is reported to the supervisor through `supervisor` channel
+    #[cfg(any(feature = "node"))]
+    fn launch(
+        &self,
+        daemon: &Daemon,
+        supervisor: &mpsc::UnboundedSender<Supervision>,
+    ) -> Result<oneshot::Sender<()>, DaemonError> {
+        let handle = self.daemon(daemon)?;
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let supervisor = supervisor.clone();
+        let daemon = daemon.id();
+        task::spawn(async move {
+            let result = handle.supervise(stop_receiver).await;
+            // Supervisor may be already gone if the node is shutting down
+            let _ = supervisor.send(Supervision::Exited { daemon, result });
+        });
+        Ok(stop_sender)
+    }
+
+    /// Waits for stashd of the daemon set to reply to `hello` request, so
+    /// contract daemons will not fail connecting to it
+    #[cfg(any(feature = "node"))]
+    async fn wait_ready(&self, set: &DaemonSet) -> Result<(), DaemonError> {
+        let endpoint = set.stash_rpc.clone();
+        let auth = self.config.auth;
+        let timeout = self.config.startup_timeout;
+        task::spawn_blocking(move || {
+            let started = Instant::now();
+            let hello = api::stash::Request::Hello(Hello::with("rgbd"))
+                .encode()
+                .map_err(RuntimeError::from)?;
+            loop {
+                match request(&auth, &endpoint, &hello) {
+                    Ok(()) => return Ok(()),
+                    Err(err) if started.elapsed() >= timeout => {
+                        return Err(DaemonError::NotReady(err.to_string()))
+                    }
+                    Err(err) => {
+                        trace!("stashd is not ready yet: {}", err);
+                        std::thread::sleep(RESTART_DELAY);
+                    }
+                }
+            }
+        })
+        .await?
+    }
+
+    /// Stops all running daemons in the reverse order of their launch and
+    /// waits for them to terminate
+    #[cfg(any(feature = "node"))]
+    async fn shutdown(
+        &self,
+        order: &[Daemon],
+        running: &mut HashMap<String, oneshot::Sender<()>>,
+        supervisor: &mut mpsc::UnboundedReceiver<Supervision>,
+        health: &mut Health,
+    ) {
+        for name in order.iter().rev().map(Daemon::id) {
+            let stop = match running.remove(&name) {
+                Some(stop) => stop,
+                None => continue,
+            };
+            info!("Stopping {}", name);
+            // Watcher may have already finished if the daemon has just exited
+            let _ = stop.send(());
+            while let Some(msg) = supervisor.recv().await {
+                match msg {
+                    Supervision::Exited { daemon, result } => {
+                        if let Err(err) = result {
+                            health.set_error(&daemon, err.to_string());
+                        }
+                        health.set_status(&daemon, DaemonStatus::Stopped);
+                        if daemon == name {
+                            break;
+                        }
+                        running.remove(&daemon);
+                    }
+                    Supervision::Restart(_) | Supervision::Status(_) => {}
+                }
+            }
+        }
+    }
+}
+
+/// Sends a single encoded request to the daemon and waits for any reply
+#[cfg(any(feature = "node"))]
+fn request(
+    auth: &ClientAuth,
+    endpoint: &ZmqSocketAddr,
+    data: &[u8],
+) -> Result<(), BootstrapError> {
+    let mut session = auth.connect(ZmqType::Req, endpoint)?;
+    session.as_socket().set_linger(0)?;
+    session.as_socket().set_rcvtimeo(REQUEST_TIMEOUT)?;
+    session.send_raw_message(data)?;
+    session.recv_raw_message()?;
+    Ok(())
 }
 
+/// Encodes request asking the daemon with a given name to shut down
+#[cfg(any(feature = "node"))]
+fn shutdown_request(name: &str) -> Result<Vec<u8>, DaemonError> {
+    let data = match name {
+        "stashd" => api::stash::Request::Shutdown().encode(),
+        "fungibled" => api::fungible::Request::Shutdown().encode(),
+        _ => Err(DaemonError::UnknownDaemon(name.into()))?,
+    };
+    Ok(data.map_err(RuntimeError::from)?)
+}
+
+/// Data required to ask a threaded daemon to shut down over its RPC API,
+/// since tokio tasks can't be cancelled
+#[cfg(any(feature = "node"))]
+#[derive(Debug)]
+struct Stopper {
+    auth: ClientAuth,
+    endpoint: ZmqSocketAddr,
+    request: Vec<u8>,
+}
+
+/// Messages received by the supervisor from daemon watchers, restart timers
+/// and RPC server
+#[cfg(any(feature = "node"))]
+#[derive(Debug)]
+pub(super) enum Supervision {
+    Exited {
+        daemon: String,
+        result: Result<(), DaemonError>,
+    },
+    Restart(String),
+    Status(oneshot::Sender<Health>),
+}
+
+#[cfg(any(feature = "node"))]
 #[derive(Debug)]
 enum DaemonHandle {
     Process(process::Child),
-    Task(task::JoinHandle<Result<(), DaemonError>>),
+    Task(task::JoinHandle<Result<(), DaemonError>>, Stopper),
 }
 
 #[derive(Debug, Error)]
@@ -93,6 +319,9 @@ pub enum DaemonError {
     IO(std::io::Error),
     Bootstrap(BootstrapError),
     UnknownDaemon(String),
+    Exit(ExitStatus),
+    NotReady(String),
+    RestartLimit(String),
 }
 
 impl std::fmt::Display for DaemonError {
@@ -125,48 +354,303 @@ impl From<BootstrapError> for DaemonError {
     }
 }
 
+impl From<ExitStatus> for DaemonError {
+    fn from(other: ExitStatus) -> DaemonError {
+        DaemonError::Exit(other)
+    }
+}
+
+#[cfg(any(feature = "node"))]
 impl DaemonHandle {
-    async fn future(self) -> Result<(), DaemonError> {
+    /// Waits for the daemon to terminate, or stops it when the `stop` signal
+    /// is received: processes are sent SIGTERM, while threaded daemons are
+    /// asked to shut down with RPC request. Daemons which have not stopped
+    /// in time are killed (processes) or abandoned (threaded daemons).
+    #[cfg(any(feature = "node"))]
+    async fn supervise(
+        self,
+        stop: oneshot::Receiver<()>,
+    ) -> Result<(), DaemonError> {
+        match self {
+            DaemonHandle::Process(mut child) => tokio::select! {
+                status = &mut child => Self::check(status?),
+                _ = stop => {
+                    Self::terminate(&mut child)?;
+                    match time::timeout(SHUTDOWN_TIMEOUT, &mut child).await {
+                        Ok(status) => Self::check(status?),
+                        Err(_) => {
+                            warn!("Daemon has not stopped in time, killing");
+                            child.kill()?;
+                            Ok(())
+                        }
+                    }
+                }
+            },
+            DaemonHandle::Task(mut handle, stopper) => tokio::select! {
+                result = &mut handle => result?,
+                _ = stop => {
+                    let Stopper { auth, endpoint, request: data } = stopper;
+                    // Daemon may be already shutting down if the process has
+                    // received a signal, so it may not reply
+                    if let Err(err) = task::spawn_blocking(move || {
+                        request(&auth, &endpoint, &data)
+                    })
+                    .await?
+                    {
+                        debug!("Shutdown request has failed: {}", err);
+                    }
+                    match time::timeout(SHUTDOWN_TIMEOUT, &mut handle).await {
+                        Ok(result) => result?,
+                        Err(_) => {
+                            warn!("Daemon has not stopped in time");
+                            Ok(())
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    fn check(status: ExitStatus) -> Result<(), DaemonError> {
+        if status.success() {
+            Ok(())
+        } else {
+            Err(status)?
+        }
+    }
+
+    #[cfg(feature = "nix")]
+    fn terminate(child: &mut process::Child) -> Result<(), DaemonError> {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+
+        kill(Pid::from_raw(child.id() as i32), Signal::SIGTERM).map_err(
+            |err| std::io::Error::new(std::io::ErrorKind::Other, err),
+        )?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "nix"))]
+    fn terminate(child: &mut process::Child) -> Result<(), DaemonError> {
+        Ok(child.kill()?)
+    }
+}
+
+impl RestartPolicy {
+    /// Detects whether daemon which has exited with the given result must be
+    /// restarted
+    pub fn should_restart<E>(self, result: &Result<(), E>) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure | RestartPolicy::Backoff => {
+                result.is_err()
+            }
+        }
+    }
+
+    /// Delay before the next restart of a daemon which has been restarted
+    /// `attempt` times in a row
+    pub fn delay(self, attempt: u32) -> Duration {
         match self {
-            DaemonHandle::Process(child) => Ok(child.await.map(|_| ())?),
-            DaemonHandle::Task(task) => Ok(task.await??),
+            RestartPolicy::Backoff => RESTART_DELAY
+                .checked_mul(1 << attempt.min(6))
+                .unwrap_or(MAX_RESTART_DELAY)
+                .min(MAX_RESTART_DELAY),
+            _ => RESTART_DELAY,
         }
     }
 }
 
+/// Daemon launched by rgbd for one of the served networks and wallets
+#[cfg(any(feature = "node"))]
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Daemon {
+    /// Name of the daemon executable
+    bin: String,
+    set: DaemonSet,
+}
+
+#[cfg(any(feature = "node"))]
+impl Daemon {
+    /// Identifier of the daemon used in supervision and health reports
+    fn id(&self) -> String {
+        format!("{}@{}/{}", self.bin, self.set.network, self.set.wallet)
+    }
+}
+
+/// Supervision state of a single daemon
+#[cfg(any(feature = "node"))]
+struct Supervised {
+    daemon: Daemon,
+    started: Instant,
+    attempts: u32,
+}
+
 #[cfg(any(feature = "node"))]
 #[async_trait]
 impl TryService for Runtime {
     type ErrorType = DaemonError;
 
-    async fn try_run_loop(mut self) -> Result<(), DaemonError> {
-        let mut handlers = vec![];
+    async fn try_run_loop(self) -> Result<(), DaemonError> {
+        let mut health = Health::with(self.config.health_file.clone());
+        let (sender, mut supervisor) = mpsc::unbounded_channel();
+        let mut shutdown = Shutdown::listen()?;
+        let _rpc = RpcServer::bind(&self.config, sender.clone())?.spawn();
+        if let Some(addr) = self.config.metrics_endpoint {
+            Exporter {
+                daemon: "rgbd",
+                auth: self.config.rpc_auth.client(),
+                endpoint: self.config.rpc_endpoint.clone(),
+                request: api::rgbd::Request::Status()
+                    .encode()
+                    .map_err(RuntimeError::from)?,
+            }
+            .spawn(addr)?;
+        }
+
+        // Each network and wallet gets its own stashd followed by contract
+        // daemons, so wallets do not share any of the processes or data
+        let mut order = vec![];
+        for set in &self.config.daemon_sets {
+            order.push(Daemon {
+                bin: s!("stashd"),
+                set: set.clone(),
+            });
+            order.extend(self.config.contracts.iter().map(|contract_name| {
+                Daemon {
+                    bin: contract_name.daemon_name().to_string(),
+                    set: set.clone(),
+                }
+            }));
+        }
 
-        handlers.push(self.daemon("stashd")?);
+        let mut running = HashMap::new();
+        let mut state = HashMap::new();
+        for daemon in &order {
+            let name = daemon.id();
+            health.set_status(&name, DaemonStatus::Starting);
+            running.insert(name.clone(), self.launch(daemon, &sender)?);
+            state.insert(
+                name.clone(),
+                Supervised {
+                    daemon: daemon.clone(),
+                    started: Instant::now(),
+                    attempts: 0,
+                },
+            );
+            if daemon.bin == "stashd" {
+                if let Err(err) = self.wait_ready(&daemon.set).await {
+                    health.set_error(&name, err.to_string());
+                    health.set_status(&name, DaemonStatus::Failed);
+                    self.shutdown(
+                        &order,
+                        &mut running,
+                        &mut supervisor,
+                        &mut health,
+                    )
+                    .await;
+                    Err(err)?
+                }
+            }
+            health.set_status(&name, DaemonStatus::Running);
+        }
 
-        self.config.contracts.iter().try_for_each(
-            |contract_name| -> Result<(), DaemonError> {
-                handlers.push(self.daemon(contract_name.daemon_name())?);
-                Ok(())
-            },
-        )?;
+        loop {
+            let msg = tokio::select! {
+                msg = supervisor.recv() => msg,
+                _ = shutdown.signalled() => None,
+            };
+            let (daemon, result) = match msg {
+                Some(Supervision::Exited { daemon, result }) => {
+                    (daemon, result)
+                }
+                Some(Supervision::Status(reply)) => {
+                    // RPC server may have already given up waiting
+                    let _ = reply.send(health.clone());
+                    continue;
+                }
+                Some(Supervision::Restart(daemon)) => {
+                    info!("Restarting {}", daemon);
+                    let supervised = state.get_mut(&daemon).expect(
+                        "Supervision state is created at daemon launch",
+                    );
+                    running.insert(
+                        daemon.clone(),
+                        self.launch(&supervised.daemon, &sender)?,
+                    );
+                    supervised.started = Instant::now();
+                    health.set_status(&daemon, DaemonStatus::Running);
+                    continue;
+                }
+                None => {
+                    info!("Stopping daemons");
+                    self.shutdown(
+                        &order,
+                        &mut running,
+                        &mut supervisor,
+                        &mut health,
+                    )
+                    .await;
+                    return Ok(());
+                }
+            };
 
-        join_all(handlers.into_iter().map(|d| d.future()))
-            .await
-            .into_iter()
-            .try_for_each(|res| -> Result<(), DaemonError> {
-                res?;
-                Ok(())
-            })?;
+            running.remove(&daemon);
+            if let Err(ref err) = result {
+                error!("Daemon {} has failed: {}", daemon, err);
+                health.set_error(&daemon, err.to_string());
+            } else {
+                warn!("Daemon {} has exited", daemon);
+            }
 
-        unreachable!()
+            if !self.config.restart_policy.should_restart(&result) {
+                health.set_status(&daemon, DaemonStatus::Stopped);
+                self.shutdown(
+                    &order,
+                    &mut running,
+                    &mut supervisor,
+                    &mut health,
+                )
+                .await;
+                // Daemon which has exited cleanly was asked to shut down, so
+                // we shut down the whole node
+                return result;
+            }
+
+            let supervised = state
+                .get_mut(&daemon)
+                .expect("Supervision state is created at daemon launch");
+            if supervised.started.elapsed() >= STABLE_PERIOD {
+                supervised.attempts = 0;
+            }
+            if supervised.attempts >= self.config.max_restarts {
+                health.set_status(&daemon, DaemonStatus::Failed);
+                self.shutdown(
+                    &order,
+                    &mut running,
+                    &mut supervisor,
+                    &mut health,
+                )
+                .await;
+                return Err(DaemonError::RestartLimit(daemon));
+            }
+
+            let delay = self.config.restart_policy.delay(supervised.attempts);
+            supervised.attempts += 1;
+            health.set_status(&daemon, DaemonStatus::Restarting);
+            let sender = sender.clone();
+            task::spawn(async move {
+                time::delay_for(delay).await;
+                let _ = sender.send(Supervision::Restart(daemon));
+            });
+        }
     }
 }
 
 #[cfg(any(feature = "node"))]
 pub async fn main_with_config(config: Config) -> Result<(), BootstrapError> {
     let runtime = Runtime::init(config).await?;
-    runtime.run_or_panic("RGBd runtime").await;
-
-    unreachable!()
+    runtime.run_until_shutdown("RGBd runtime").await;
+    Ok(())
 }
@@ -39,8 +39,20 @@ pub trait TryService: Sized {
         panic!(msg)
     }
 
+    /// Runs the service until it gets shut down. Unlike [`run_or_panic()`],
+    /// treats successful return from [`try_run_loop()`] as a graceful
+    /// shutdown; failures still result in a panic.
+    async fn run_until_shutdown(self, service_name: &str) {
+        if let Err(err) = self.try_run_loop().await {
+            let msg = handle_failure(service_name, Err::<(), _>(err));
+            panic!(msg)
+        }
+        info!("{} has shut down", service_name);
+    }
+
     /// Main failable run loop implementation. Must produce an error of type
-    /// [`TryService::ErrorType`] or never return.
+    /// [`TryService::ErrorType`] or never return, unless the service supports
+    /// graceful shutdown, in which case it returns `Ok` once it is complete.
     async fn try_run_loop(self) -> Result<(), Self::ErrorType>;
 }
 
@@ -11,15 +11,24 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+use core::convert::TryFrom;
 use core::fmt::Display;
 use core::str::FromStr;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use lnpbp::bp;
 use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
 use lnpbp::lnp::{LocalNode, PartialNodeAddr};
 
+use super::resolvers::ResolverUrl;
+use crate::auth::ServerAuth;
 use crate::constants::*;
+use crate::error::ConfigError;
+#[cfg(feature = "shell")]
+use crate::util::config::LayeredOpts;
+use crate::util::file::utf8_path;
+use crate::util::WalletId;
 
 #[derive(Clap)]
 #[clap(
@@ -37,6 +46,11 @@ pub struct Opts {
     #[clap(short, long, default_value = RGB_DATA_DIR, env = "RGB_DATA_DIR")]
     pub data_dir: String,
 
+    /// Path to the configuration file; if not given, `rgb.toml` from the data
+    /// directory is used when present
+    #[clap(long, env = "RGB_CONFIG")]
+    pub config: Option<String>,
+
     /// Connection string to stash (exact format depends on used storage
     /// engine)
     #[clap(short, long, default_value = STASHD_STASH, env = "RGB_STASHD_STASH")]
@@ -46,6 +60,15 @@ pub struct Opts {
     #[clap(short, long, default_value = STASHD_INDEX, env = "RGB_STASHD_INDEX")]
     pub index: String,
 
+    /// Directory for caching transactions resolved during consignment
+    /// validation
+    #[clap(
+        long,
+        default_value = STASHD_TX_CACHE,
+        env = "RGB_STASHD_TX_CACHE"
+    )]
+    pub tx_cache: String,
+
     /// LNP socket address string for P2P API
     #[clap(long = "bind", env = "RGB_STASHD_BIND")]
     pub p2p_endpoint: Option<String>,
@@ -70,6 +93,11 @@ pub struct Opts {
     #[clap(short, long, default_value = RGB_NETWORK, env = "RGB_NETWORK")]
     pub network: bp::Chain,
 
+    /// Wallet served by the daemon; each wallet has its own stash, cache and
+    /// set of daemons
+    #[clap(long, default_value = RGB_WALLET, env = "RGB_WALLET")]
+    pub wallet: WalletId,
+
     /// Electrum server to use to fecth Bitcoin transactions
     #[clap(
         long = "electrum",
@@ -77,6 +105,64 @@ pub struct Opts {
         env = "RGB_ELECTRUM_SERVER"
     )]
     pub electrum_server: String,
+
+    /// URLs of the data sources for resolving transactions during
+    /// consignment validation, in the order they are queried: Electrum server
+    /// (`tcp://`, `ssl://` or `electrum://`), Bitcoin Core JSON-RPC
+    /// (`bitcoind://<user>:<password>@<host>:<port>`) or a directory with raw
+    /// transactions for the air-gapped use (`file:///<path>`). Omitted ports
+    /// default to the ones used on the network. Defaults to the `--electrum`
+    /// server
+    #[clap(
+        long = "resolver",
+        env = "RGB_STASHD_RESOLVERS",
+        hide_env_values = true,
+        use_delimiter = true
+    )]
+    pub resolvers: Vec<String>,
+
+    /// Number of the data sources which must return the same transaction
+    /// and fee for it to be resolved; with the default value of 1 sources
+    /// after the first one are used only as fallbacks
+    #[clap(long, default_value = "1", env = "RGB_STASHD_RESOLVER_QUORUM")]
+    pub resolver_quorum: usize,
+
+    /// Minimal number of witness transaction confirmations for accepted
+    /// consignments; until it is reached, they are reported as awaiting
+    /// confirmation. Clients may override it per request.
+    #[clap(
+        long = "confirmations",
+        default_value = "0",
+        env = "RGB_STASHD_CONFIRMATIONS"
+    )]
+    pub min_confirmations: u32,
+
+    /// Number of confirmations after which witness transactions of the
+    /// merged consignments are considered final and are no longer checked
+    /// for reorgs and double-spends
+    #[clap(long, default_value = "6", env = "RGB_STASHD_FINALITY_DEPTH")]
+    pub finality_depth: u32,
+
+    /// Z85-encoded public key of the node used to encrypt RPC and PUB/SUB
+    /// connections with CurveZMQ; encryption is disabled if not given
+    #[clap(long, env = "RGB_CURVE_PUBLIC")]
+    pub curve_public: Option<String>,
+
+    /// Z85-encoded secret key of the node matching `--curve-public`
+    #[clap(long, env = "RGB_CURVE_SECRET", hide_env_values = true)]
+    pub curve_secret: Option<String>,
+
+    /// Z85-encoded public keys of the clients allowed to connect, optionally
+    /// followed by the client role (`<key>:read-only`, `<key>:transfer` or
+    /// `<key>:admin`; admin by default) and the wallet the client is limited
+    /// to (`<key>:transfer@alice`). Required if `--curve-public` is given
+    #[clap(long, env = "RGB_CURVE_CLIENTS", use_delimiter = true)]
+    pub curve_clients: Vec<String>,
+
+    /// TCP socket address for exporting metrics in Prometheus text format
+    /// over HTTP; metrics are not exported if not given
+    #[clap(long = "metrics", env = "RGB_STASHD_METRICS")]
+    pub metrics_endpoint: Option<String>,
 }
 
 // We need config structure since not all of the parameters can be specified
@@ -90,28 +176,99 @@ pub struct Config {
     pub data_dir: PathBuf,
     pub stash: String,
     pub index: String,
+    pub tx_cache: PathBuf,
     pub p2p_endpoint: Option<PartialNodeAddr>,
     pub rpc_endpoint: ZmqSocketAddr,
     pub pub_endpoint: ZmqSocketAddr,
     pub network: bp::Chain,
+    pub wallet: WalletId,
     pub electrum_server: String,
+    pub resolvers: Vec<ResolverUrl>,
+    pub resolver_quorum: usize,
+    pub min_confirmations: u32,
+    pub finality_depth: u32,
+    pub auth: ServerAuth,
+    pub metrics_endpoint: Option<SocketAddr>,
+}
+
+#[cfg(feature = "shell")]
+impl LayeredOpts for Opts {
+    const FILE_PARAMS: &'static [(&'static str, &'static str)] = &[
+        ("data-dir", "data-dir"),
+        ("network", "network"),
+        ("wallet", "wallet"),
+        ("electrum-server", "electrum-server"),
+        ("curve-public", "curve-public"),
+        ("curve-secret", "curve-secret"),
+        ("curve-clients", "curve-clients"),
+        ("stashd.stash", "stash"),
+        ("stashd.index", "index"),
+        ("stashd.tx-cache", "tx-cache"),
+        ("stashd.resolvers", "resolvers"),
+        ("stashd.resolver-quorum", "resolver-quorum"),
+        ("stashd.confirmations", "min-confirmations"),
+        ("stashd.finality-depth", "finality-depth"),
+        ("stashd.bind", "p2p-endpoint"),
+        ("stashd.rpc", "rpc-endpoint"),
+        ("stashd.pub", "pub-endpoint"),
+        ("stashd.metrics", "metrics-endpoint"),
+    ];
+
+    fn config_file(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    fn data_dir(&self) -> &str {
+        &self.data_dir
+    }
 }
 
-impl From<Opts> for Config {
-    fn from(opts: Opts) -> Self {
+impl TryFrom<Opts> for Config {
+    type Error = ConfigError;
+
+    fn try_from(opts: Opts) -> Result<Self, Self::Error> {
         let mut me = Self {
             verbose: opts.verbose,
             network: opts.network,
+            wallet: opts.wallet,
             ..Config::default()
         };
-        me.data_dir = me.parse_param(opts.data_dir);
-        me.stash = me.parse_param(opts.stash);
-        me.index = me.parse_param(opts.index);
-        me.rpc_endpoint = me.parse_param(opts.rpc_endpoint);
-        me.pub_endpoint = me.parse_param(opts.pub_endpoint);
-        me.p2p_endpoint = opts.p2p_endpoint.map(|ep| me.parse_param(ep));
-        me.electrum_server = me.parse_param(opts.electrum_server);
-        me
+        me.data_dir = me.parse_param("data-dir", opts.data_dir)?;
+        me.stash = me.parse_param("stash", opts.stash)?;
+        me.index = me.parse_param("index", opts.index)?;
+        me.tx_cache = me.parse_param("tx-cache", opts.tx_cache)?;
+        me.rpc_endpoint = me.parse_param("rpc", opts.rpc_endpoint)?;
+        me.pub_endpoint = me.parse_param("pub", opts.pub_endpoint)?;
+        me.p2p_endpoint = opts
+            .p2p_endpoint
+            .map(|ep| me.parse_param("bind", ep))
+            .transpose()?;
+        me.electrum_server =
+            me.parse_param("electrum", opts.electrum_server)?;
+        me.resolvers = opts
+            .resolvers
+            .into_iter()
+            .map(|url| me.parse_resolver("resolver", url))
+            .collect::<Result<_, _>>()?;
+        if me.resolvers.is_empty() {
+            me.resolvers = vec![
+                me.parse_resolver("electrum", me.electrum_server.clone())?
+            ];
+        }
+        me.resolver_quorum = opts.resolver_quorum;
+        me.min_confirmations = opts.min_confirmations;
+        me.finality_depth = opts.finality_depth;
+        me.metrics_endpoint = opts
+            .metrics_endpoint
+            .map(|ep| me.parse_param("metrics", ep))
+            .transpose()?;
+        me.auth = ServerAuth::from_opts(
+            opts.curve_public,
+            opts.curve_secret,
+            opts.curve_clients,
+            &me.wallet,
+        )?;
+        Ok(me)
     }
 }
 
@@ -125,6 +282,9 @@ impl Default for Config {
                 .expect("Error in RGB_DATA_DIR constant value"),
             stash: STASHD_STASH.to_string(),
             index: STASHD_INDEX.to_string(),
+            tx_cache: STASHD_TX_CACHE
+                .parse()
+                .expect("Error in STASHD_TX_CACHE constant value"),
             p2p_endpoint: None,
             rpc_endpoint: STASHD_RPC_ENDPOINT
                 .parse()
@@ -135,27 +295,61 @@ impl Default for Config {
             network: RGB_NETWORK
                 .parse()
                 .expect("Error in RGB_NETWORK constant value"),
+            wallet: WalletId::default(),
             electrum_server: DEFAULT_ELECTRUM_ENDPOINT
                 .parse()
                 .expect("Error in DEFAULT_ELECTRUM_ENDPOINT constant value"),
+            resolvers: vec![ResolverUrl::Electrum(
+                DEFAULT_ELECTRUM_ENDPOINT.to_string(),
+            )],
+            resolver_quorum: 1,
+            min_confirmations: 0,
+            finality_depth: 6,
+            auth: ServerAuth::default(),
+            metrics_endpoint: None,
         }
     }
 }
 
 impl Config {
-    pub fn parse_param<T>(&self, param: String) -> T
+    /// Parses value of the parameter with a given name, replacing
+    /// placeholders with the actual values
+    pub fn parse_param<T>(
+        &self,
+        name: &str,
+        param: String,
+    ) -> Result<T, ConfigError>
     where
         T: FromStr,
         T::Err: Display,
     {
         param
-            .replace("{id}", "default")
+            .replace("{id}", self.wallet.as_str())
             .replace("{network}", &self.network.to_string())
-            .replace("{data_dir}", self.data_dir.to_str().unwrap())
+            .replace("{data_dir}", utf8_path("data-dir", &self.data_dir)?)
             .replace("{node_id}", &self.node_auth.node_id().to_string())
             .parse()
-            .unwrap_or_else(|err| {
-                panic!("Error parsing parameter `{}`: {}", param, err)
+            .map_err(|err: T::Err| ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: param.clone(),
+                details: err.to_string(),
             })
     }
+
+    /// Parses resolver URL given with the named parameter, taking the
+    /// default port of the data source for the configured network
+    pub fn parse_resolver(
+        &self,
+        name: &str,
+        param: String,
+    ) -> Result<ResolverUrl, ConfigError> {
+        let url: String = self.parse_param(name, param.clone())?;
+        ResolverUrl::parse(&url, &self.network).map_err(|err| {
+            ConfigError::InvalidParam {
+                param: name.to_string(),
+                value: param,
+                details: err.to_string(),
+            }
+        })
+    }
 }
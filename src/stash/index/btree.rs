@@ -20,7 +20,7 @@ use lnpbp::rgb::{Anchor, AnchorId, NodeId};
 use lnpbp::strict_encoding::{StrictDecode, StrictEncode};
 
 use super::Index;
-use crate::error::{BootstrapError, ServiceErrorDomain};
+use crate::error::{BootstrapError, ServiceErrorDomain, StorageError};
 
 type BTreeIndexData = BTreeMap<Vec<u8>, Vec<u8>>;
 
@@ -36,7 +36,7 @@ pub enum BTreeIndexError {
 
 impl From<BTreeIndexError> for ServiceErrorDomain {
     fn from(err: BTreeIndexError) -> Self {
-        ServiceErrorDomain::Storage(err.to_string())
+        ServiceErrorDomain::Storage(StorageError::Index(err.to_string()))
     }
 }
 
@@ -80,6 +80,15 @@ impl BTreeIndex {
         }
     }
 
+    /// Number of entries in the index
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
     pub fn store(&self) -> Result<(), BTreeIndexError> {
         debug!("Saving RGB index to file {:?} ...", &self.config.index_file);
         let _ = fs::remove_file(&self.config.index_file);
@@ -14,11 +14,12 @@
 mod config;
 mod runtime;
 mod stash;
+mod witness;
 
 pub(self) mod index;
 pub(self) mod storage;
 
-pub mod electrum;
+pub mod resolvers;
 
 pub use config::{Config, Opts};
 pub use runtime::{main_with_config, Runtime};
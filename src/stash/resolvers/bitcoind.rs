@@ -0,0 +1,390 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use core::iter;
+use core::time::Duration;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+
+use lnpbp::bitcoin::consensus::deserialize;
+use lnpbp::bitcoin::hashes::hex::FromHex;
+use lnpbp::bitcoin::{OutPoint, Transaction, Txid};
+use lnpbp::rgb::validation::{TxResolver, TxResolverError};
+
+use super::{prevout_value, tx_fee};
+use crate::util::chain::{
+    ChainStatus, ChainStatusError, Connectivity, OutputStatus, TxStatus,
+};
+
+/// Timeout for sending the request to Bitcoin Core and receiving the reply
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error code returned by Bitcoin Core for unknown transactions
+const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+
+/// Error code returned by Bitcoin Core for RPC methods it does not support
+const RPC_METHOD_NOT_FOUND: i64 = -32601;
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum BitcoindError {
+    /// I/O error: {_0}
+    #[from]
+    Io(io::Error),
+
+    /// Bitcoin Core has rejected RPC credentials
+    Unauthorized,
+
+    /// Malformed reply from Bitcoin Core: {_0}
+    Malformed(String),
+
+    /// Bitcoin Core RPC error {code}: {message}
+    Rpc { code: i64, message: String },
+}
+
+fn map_bitcoind_err(other: BitcoindError) -> TxResolverError {
+    log::error!("Bitcoin Core error: {}", other);
+
+    TxResolverError
+}
+
+/// Resolver using Bitcoin Core JSON-RPC. Mined transactions are found only if
+/// the node is run with `txindex=1`; otherwise just mempool transactions are
+/// resolved, with the outputs they spend looked up in the UTXO set.
+pub struct BitcoinCoreTxResolver {
+    /// Host and port of the RPC server
+    addr: String,
+
+    /// Encoded credentials for the basic HTTP authentication, if any
+    auth: Option<String>,
+
+    /// Outcome of the last request to the server
+    state: Mutex<Connectivity>,
+}
+
+impl BitcoinCoreTxResolver {
+    pub fn new(addr: &str, user: Option<&str>, password: Option<&str>) -> Self {
+        let auth = user.map(|user| {
+            base64::encode(format!("{}:{}", user, password.unwrap_or("")))
+        });
+        Self {
+            addr: addr.to_string(),
+            auth,
+            state: Mutex::new(Connectivity::Unknown),
+        }
+    }
+
+    /// Sends requests to Bitcoin Core as a single batch, returning results in
+    /// the order of the requests. Whether the server has been reached is
+    /// remembered to report connectivity.
+    fn call(
+        &self,
+        requests: &[(&str, Value)],
+    ) -> Result<Vec<Result<Value, BitcoindError>>, BitcoindError> {
+        let results = self.send(requests);
+        if let Ok(mut state) = self.state.lock() {
+            *state = match &results {
+                Ok(_) => Connectivity::Connected,
+                Err(err) => Connectivity::Unreachable(err.to_string()),
+            };
+        }
+        results
+    }
+
+    fn send(
+        &self,
+        requests: &[(&str, Value)],
+    ) -> Result<Vec<Result<Value, BitcoindError>>, BitcoindError> {
+        let body = Value::Array(
+            requests
+                .iter()
+                .enumerate()
+                .map(|(id, (method, params))| {
+                    json!({
+                        "jsonrpc": "1.0",
+                        "id": id,
+                        "method": method,
+                        "params": params,
+                    })
+                })
+                .collect(),
+        )
+        .to_string();
+
+        // HTTP/1.0 makes the server close connection after the reply, so we
+        // do not have to deal with chunked encoding
+        let mut head = format!(
+            "POST / HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n",
+            self.addr,
+            body.len()
+        );
+        if let Some(auth) = &self.auth {
+            head += &format!("Authorization: Basic {}\r\n", auth);
+        }
+        head += "\r\n";
+
+        let mut stream = TcpStream::connect(&self.addr)?;
+        stream.set_read_timeout(Some(RPC_TIMEOUT))?;
+        stream.set_write_timeout(Some(RPC_TIMEOUT))?;
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        let mut reply = vec![];
+        stream.read_to_end(&mut reply)?;
+
+        let reply = String::from_utf8(reply)
+            .map_err(|_| BitcoindError::Malformed(s!("non-UTF8 reply")))?;
+        let (head, body) = reply
+            .find("\r\n\r\n")
+            .map(|pos| (&reply[..pos], &reply[pos + 4..]))
+            .ok_or_else(|| BitcoindError::Malformed(s!("no HTTP headers")))?;
+        if head.split_whitespace().nth(1) == Some("401") {
+            Err(BitcoindError::Unauthorized)?
+        }
+        let replies: Vec<Value> = serde_json::from_str(body)
+            .map_err(|err| BitcoindError::Malformed(err.to_string()))?;
+
+        // Replies to batch requests may come in any order
+        let mut results: Vec<Option<Result<Value, BitcoindError>>> =
+            requests.iter().map(|_| None).collect();
+        for mut reply in replies {
+            let id = reply["id"].as_u64().ok_or_else(|| {
+                BitcoindError::Malformed(s!("reply without id"))
+            })? as usize;
+            let result = match reply["error"].take() {
+                Value::Null => Ok(reply["result"].take()),
+                error => Err(BitcoindError::Rpc {
+                    code: error["code"].as_i64().unwrap_or_default(),
+                    message: error["message"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                }),
+            };
+            if let Some(slot) = results.get_mut(id) {
+                *slot = Some(result);
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                result.ok_or_else(|| {
+                    BitcoindError::Malformed(s!("missing reply"))
+                })
+            })
+            .collect()
+    }
+
+    /// Fetches transactions with `getrawtransaction`; transactions unknown to
+    /// Bitcoin Core are omitted from the result
+    fn raw_transactions<'a>(
+        &self,
+        txids: impl Iterator<Item = &'a Txid>,
+    ) -> Result<HashMap<Txid, Transaction>, BitcoindError> {
+        let requests = txids
+            .map(|txid| ("getrawtransaction", json!([txid.to_string(), false])))
+            .collect::<Vec<_>>();
+        let mut txs = HashMap::new();
+        for result in self.call(&requests)? {
+            let hex = match result {
+                Ok(hex) => hex,
+                Err(BitcoindError::Rpc { code, .. })
+                    if code == RPC_INVALID_ADDRESS_OR_KEY =>
+                {
+                    continue
+                }
+                Err(err) => Err(err)?,
+            };
+            let tx: Transaction = hex
+                .as_str()
+                .and_then(|hex| Vec::<u8>::from_hex(hex).ok())
+                .and_then(|data| deserialize(&data).ok())
+                .ok_or_else(|| {
+                    BitcoindError::Malformed(s!("invalid transaction"))
+                })?;
+            txs.insert(tx.txid(), tx);
+        }
+        Ok(txs)
+    }
+
+    /// Fetches values of the unspent outputs with `gettxout`; outputs which
+    /// are spent or unknown are omitted from the result
+    fn utxo_values(
+        &self,
+        outpoints: &[OutPoint],
+    ) -> Result<HashMap<OutPoint, u64>, BitcoindError> {
+        let requests = outpoints
+            .iter()
+            .map(|outpoint| {
+                let params =
+                    json!([outpoint.txid.to_string(), outpoint.vout, false]);
+                ("gettxout", params)
+            })
+            .collect::<Vec<_>>();
+        let mut values = HashMap::new();
+        for (outpoint, result) in outpoints.iter().zip(self.call(&requests)?) {
+            // Bitcoin Core returns null for spent outputs
+            if let Some(btc) = result?["value"].as_f64() {
+                values.insert(*outpoint, (btc * 100_000_000.0).round() as u64);
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl ChainStatus for BitcoinCoreTxResolver {
+    /// Requires `txindex=1` for mined transactions, like the resolution does
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainStatusError> {
+        let mut results = self
+            .call(&[
+                ("getrawtransaction", json!([txid.to_string(), true])),
+                ("getblockcount", json!([])),
+            ])
+            .map_err(|err| ChainStatusError::Unavailable(err.to_string()))?;
+        let tip = results
+            .remove(1)
+            .map_err(|err| ChainStatusError::Unavailable(err.to_string()))?
+            .as_u64()
+            .ok_or_else(|| {
+                ChainStatusError::Inconsistency(s!("invalid block count"))
+            })? as u32;
+        let info = match results.remove(0) {
+            Ok(info) => info,
+            Err(BitcoindError::Rpc { code, .. })
+                if code == RPC_INVALID_ADDRESS_OR_KEY =>
+            {
+                return Ok(TxStatus::Unknown)
+            }
+            Err(err) => Err(ChainStatusError::Unavailable(err.to_string()))?,
+        };
+        // Mempool transactions have no confirmations field
+        match info["confirmations"].as_u64() {
+            None | Some(0) => Ok(TxStatus::Mempool),
+            Some(confirmations) => {
+                let confirmations = confirmations as u32;
+                Ok(TxStatus::Mined {
+                    height: (tip + 1).saturating_sub(confirmations),
+                    confirmations,
+                })
+            }
+        }
+    }
+
+    /// Finds transactions spending the outputs with `gettxspendingprevout`,
+    /// which requires Bitcoin Core 24 or later. Bitcoin Core does not index
+    /// spends by mined transactions, so such outputs are reported as unknown;
+    /// their spends may be seen while the spending transaction is in the
+    /// mempool.
+    fn output_status(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError> {
+        let txid = outpoint.txid.to_string();
+        let mut results = self
+            .call(&[
+                ("gettxout", json!([txid, outpoint.vout, true])),
+                (
+                    "gettxspendingprevout",
+                    json!([[{ "txid": txid, "vout": outpoint.vout }]]),
+                ),
+            ])
+            .map_err(|err| ChainStatusError::Unavailable(err.to_string()))?;
+        let spending = results.remove(1);
+        let utxo = results
+            .remove(0)
+            .map_err(|err| ChainStatusError::Unavailable(err.to_string()))?;
+        if !utxo.is_null() {
+            return Ok(OutputStatus::Unspent);
+        }
+        let spending = match spending {
+            Ok(spending) => spending,
+            Err(BitcoindError::Rpc { code, .. })
+                if code == RPC_METHOD_NOT_FOUND =>
+            {
+                return Ok(OutputStatus::Unknown)
+            }
+            Err(err) => Err(ChainStatusError::Unavailable(err.to_string()))?,
+        };
+        match spending[0]["spendingtxid"].as_str() {
+            Some(spender) => Txid::from_hex(spender)
+                .map(|spender| OutputStatus::Spent(Some(spender)))
+                .map_err(|_| {
+                    ChainStatusError::Inconsistency(format!(
+                        "invalid spending transaction id {}",
+                        spender
+                    ))
+                }),
+            None => Ok(OutputStatus::Unknown),
+        }
+    }
+
+    /// Reports whether Bitcoin Core RPC was reachable on the last request
+    fn connectivity(&self) -> Connectivity {
+        self.state
+            .lock()
+            .map(|state| state.clone())
+            .unwrap_or_else(|_| {
+                Connectivity::Unreachable(s!("state lock is poisoned"))
+            })
+    }
+}
+
+impl TxResolver for BitcoinCoreTxResolver {
+    fn resolve(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+        log::debug!("Resolving txid {}", txid);
+
+        let tx = match self
+            .raw_transactions(iter::once(txid))
+            .map_err(map_bitcoind_err)?
+            .remove(txid)
+        {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+
+        let prev_txids = tx
+            .input
+            .iter()
+            .map(|input| input.previous_output.txid)
+            .collect::<BTreeSet<_>>();
+        let prev_txs = self
+            .raw_transactions(prev_txids.iter())
+            .map_err(map_bitcoind_err)?;
+        let missing = tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .filter(|outpoint| prevout_value(&prev_txs, outpoint).is_none())
+            .collect::<Vec<_>>();
+        let utxo_values = if missing.is_empty() {
+            HashMap::new()
+        } else {
+            self.utxo_values(&missing).map_err(map_bitcoind_err)?
+        };
+        let fee = tx_fee(&tx, |outpoint| {
+            prevout_value(&prev_txs, outpoint)
+                .or_else(|| utxo_values.get(outpoint).copied())
+        })?;
+
+        log::debug!("Calculated fee: {}", fee);
+
+        Ok(Some((tx, fee)))
+    }
+}
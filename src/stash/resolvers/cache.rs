@@ -0,0 +1,126 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::{fs, io};
+
+use lnpbp::bitcoin::consensus::{serialize, Decodable};
+use lnpbp::bitcoin::{OutPoint, Transaction, Txid};
+use lnpbp::rgb::validation::{TxResolver, TxResolverError};
+
+use crate::util::chain::{
+    ChainStatus, ChainStatusError, Connectivity, OutputStatus, TxStatus,
+};
+
+/// Resolver keeping transactions resolved by the inner resolver together with
+/// their fees in memory and in the cache directory, one file per transaction.
+/// Transactions which were not found are not cached, since they may be
+/// published later.
+pub struct CachingTxResolver<R: TxResolver> {
+    inner: R,
+    dir: PathBuf,
+    resolved: Mutex<HashMap<Txid, (Transaction, u64)>>,
+}
+
+impl<R: TxResolver> CachingTxResolver<R> {
+    pub fn new(inner: R, dir: PathBuf) -> Result<Self, io::Error> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            inner,
+            dir,
+            resolved: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn filename(&self, txid: &Txid) -> PathBuf {
+        self.dir.join(format!("{}.tx", txid))
+    }
+
+    fn read(&self, txid: &Txid) -> Option<(Transaction, u64)> {
+        let data = fs::read(self.filename(txid)).ok()?;
+        let mut cursor = io::Cursor::new(data);
+        match (
+            Transaction::consensus_decode(&mut cursor),
+            u64::consensus_decode(&mut cursor),
+        ) {
+            (Ok(tx), Ok(fee)) if tx.txid() == *txid => Some((tx, fee)),
+            _ => {
+                // Damaged entry will be replaced with the resolved one
+                log::warn!("Ignoring damaged cache entry for txid {}", txid);
+                None
+            }
+        }
+    }
+
+    fn write(&self, tx: &Transaction, fee: u64) -> Result<(), io::Error> {
+        let mut data = serialize(tx);
+        data.extend(serialize(&fee));
+        fs::write(self.filename(&tx.txid()), data)
+    }
+}
+
+impl<R: TxResolver> TxResolver for CachingTxResolver<R> {
+    fn resolve(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+        let mut resolved = self.resolved.lock().map_err(|_| TxResolverError)?;
+        if let Some(entry) = resolved.get(txid) {
+            return Ok(Some(entry.clone()));
+        }
+        let entry = match self.read(txid) {
+            Some(entry) => entry,
+            None => match self.inner.resolve(txid)? {
+                Some((tx, fee)) => {
+                    if let Err(err) = self.write(&tx, fee) {
+                        // Caching failure must not break the validation
+                        log::warn!("Unable to cache txid {}: {}", txid, err);
+                    }
+                    (tx, fee)
+                }
+                None => return Ok(None),
+            },
+        };
+        resolved.insert(*txid, entry.clone());
+        Ok(Some(entry))
+    }
+}
+
+impl<R: TxResolver> TxResolver for &CachingTxResolver<R> {
+    fn resolve(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+        (*self).resolve(txid)
+    }
+}
+
+/// Transaction status changes over time, so it is never cached
+impl<R: TxResolver + ChainStatus> ChainStatus for CachingTxResolver<R> {
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainStatusError> {
+        self.inner.tx_status(txid)
+    }
+
+    fn output_status(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError> {
+        self.inner.output_status(outpoint)
+    }
+
+    fn connectivity(&self) -> Connectivity {
+        self.inner.connectivity()
+    }
+}
@@ -0,0 +1,496 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use lnpbp::bitcoin::{OutPoint, Transaction, Txid};
+use lnpbp::rgb::validation::{TxResolver, TxResolverError};
+
+use super::TxSource;
+use crate::error::ConfigError;
+use crate::util::chain::{
+    ChainStatus, ChainStatusError, Connectivity, OutputStatus, TxStatus,
+};
+
+/// Resolver querying several data sources in the given order.
+///
+/// With the quorum of one, sources after the first one are used as fallbacks:
+/// they are queried only if the previous ones failed or do not know the
+/// transaction. With a larger quorum, the transaction is resolved only when
+/// the given number of sources return the same transaction with the same fee;
+/// if some of the sources disagree, resolution fails.
+pub struct CompositeTxResolver {
+    sources: Vec<Box<dyn TxSource>>,
+    quorum: usize,
+}
+
+impl CompositeTxResolver {
+    /// Constructs resolver from the non-empty list of sources; quorum must
+    /// not exceed the number of sources
+    pub fn new(
+        sources: Vec<Box<dyn TxSource>>,
+        quorum: usize,
+    ) -> Result<Self, ConfigError> {
+        if sources.is_empty() {
+            Err(ConfigError::InvalidParam {
+                param: s!("resolvers"),
+                value: s!(""),
+                details: s!("at least one resolver is required"),
+            })?
+        }
+        if quorum < 1 || quorum > sources.len() {
+            Err(ConfigError::InvalidParam {
+                param: s!("resolver-quorum"),
+                value: quorum.to_string(),
+                details: format!(
+                    "quorum must be between 1 and the number of resolvers ({})",
+                    sources.len()
+                ),
+            })?
+        }
+        Ok(Self { sources, quorum })
+    }
+}
+
+impl TxResolver for CompositeTxResolver {
+    fn resolve(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+        // Distinct answers with the number of sources which have given them
+        let mut answers: Vec<((Transaction, u64), usize)> = vec![];
+        let mut failed = 0usize;
+        for (no, source) in self.sources.iter().enumerate() {
+            let answer = match source.resolve(txid) {
+                Ok(Some(answer)) => answer,
+                Ok(None) => continue,
+                Err(_) => {
+                    log::warn!("Source #{} has failed to resolve {}", no, txid);
+                    failed += 1;
+                    continue;
+                }
+            };
+            let agreed = match answers.iter_mut().find(|(a, _)| *a == answer) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count
+                }
+                None => {
+                    answers.push((answer.clone(), 1));
+                    1
+                }
+            };
+            if agreed >= self.quorum {
+                if answers.len() > 1 {
+                    log::error!("Sources disagree on transaction {}", txid);
+                    Err(TxResolverError)?
+                }
+                return Ok(Some(answer));
+            }
+        }
+
+        if answers.len() > 1 {
+            log::error!("Sources disagree on transaction {}", txid);
+            Err(TxResolverError)?
+        }
+        if failed > 0 {
+            // Failed sources might have known the transaction
+            Err(TxResolverError)?
+        }
+        if !answers.is_empty() {
+            log::warn!(
+                "Transaction {} is known to less than {} sources",
+                txid,
+                self.quorum
+            );
+        }
+        Ok(None)
+    }
+}
+
+impl ChainStatus for CompositeTxResolver {
+    /// Requires the quorum of sources knowing the transaction and reports the
+    /// most conservative of their statuses: conflicted if any reachable source
+    /// sees the conflict, otherwise the one with the least confirmations.
+    /// All sources are queried even after the quorum is reached, so the
+    /// conflict seen by a fallback source is not missed.
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainStatusError> {
+        let mut known: Vec<TxStatus> = vec![];
+        let mut errors: Vec<String> = vec![];
+        for source in &self.sources {
+            match source.tx_status(txid) {
+                Ok(TxStatus::Unknown) => continue,
+                Ok(status) => known.push(status),
+                Err(err) => errors.push(err.to_string()),
+            }
+        }
+
+        if known.contains(&TxStatus::Conflicted) {
+            return Ok(TxStatus::Conflicted);
+        }
+        if known.len() < self.quorum {
+            if !errors.is_empty() {
+                // Failed sources might have known the transaction
+                Err(ChainStatusError::Unavailable(errors.join("; ")))?
+            }
+            return Ok(TxStatus::Unknown);
+        }
+        Ok(known
+            .into_iter()
+            .min_by_key(TxStatus::confirmations)
+            .unwrap_or(TxStatus::Unknown))
+    }
+
+    /// Requires the quorum of sources to agree on whether the output is
+    /// spent; the spending transaction is taken from the first source which
+    /// is able to tell it
+    fn output_status(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError> {
+        let mut known: Vec<OutputStatus> = vec![];
+        let mut errors: Vec<String> = vec![];
+        for source in &self.sources {
+            match source.output_status(outpoint) {
+                Ok(OutputStatus::Unknown) => continue,
+                Ok(status) => known.push(status),
+                Err(err) => errors.push(err.to_string()),
+            }
+            if known.len() >= self.quorum {
+                break;
+            }
+        }
+
+        if known.len() < self.quorum {
+            if !errors.is_empty() {
+                Err(ChainStatusError::Unavailable(errors.join("; ")))?
+            }
+            return Ok(OutputStatus::Unknown);
+        }
+        let spent = known
+            .iter()
+            .filter(|status| matches!(status, OutputStatus::Spent(_)))
+            .count();
+        if spent == 0 {
+            return Ok(OutputStatus::Unspent);
+        }
+        if spent < known.len() {
+            Err(ChainStatusError::Inconsistency(format!(
+                "sources disagree on whether {} is spent",
+                outpoint
+            )))?
+        }
+        Ok(OutputStatus::Spent(known.into_iter().find_map(
+            |status| match status {
+                OutputStatus::Spent(txid) => txid,
+                _ => None,
+            },
+        )))
+    }
+
+    /// Reports connection as established if enough sources were reachable on
+    /// their last requests to achieve the quorum
+    fn connectivity(&self) -> Connectivity {
+        let states = self
+            .sources
+            .iter()
+            .map(|source| source.connectivity())
+            .collect::<Vec<_>>();
+        if states.iter().all(|state| *state == Connectivity::Offline) {
+            return Connectivity::Offline;
+        }
+        // Offline sources are always available, since they are local
+        let available = states
+            .iter()
+            .filter(|state| {
+                matches!(state, Connectivity::Connected | Connectivity::Offline)
+            })
+            .count();
+        if available >= self.quorum {
+            return Connectivity::Connected;
+        }
+        let unknown = states
+            .iter()
+            .filter(|state| **state == Connectivity::Unknown)
+            .count();
+        if available + unknown >= self.quorum {
+            return Connectivity::Unknown;
+        }
+        Connectivity::Unreachable(
+            states
+                .iter()
+                .filter_map(|state| match state {
+                    Connectivity::Unreachable(err) => Some(err.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lnpbp::bitcoin::hashes::Hash;
+
+    use super::*;
+
+    /// Data source giving the same answer for any transaction
+    enum Source {
+        /// Knows the transaction with the given fee and status; its outputs
+        /// have the given status
+        Knows(u64, TxStatus, OutputStatus),
+        /// Does not know the transaction
+        Unaware,
+        /// Is not reachable
+        Failing,
+    }
+
+    fn tx() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![],
+        }
+    }
+
+    impl TxResolver for Source {
+        fn resolve(
+            &self,
+            _: &Txid,
+        ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+            match self {
+                Source::Knows(fee, ..) => Ok(Some((tx(), *fee))),
+                Source::Unaware => Ok(None),
+                Source::Failing => Err(TxResolverError),
+            }
+        }
+    }
+
+    impl ChainStatus for Source {
+        fn tx_status(&self, _: &Txid) -> Result<TxStatus, ChainStatusError> {
+            match self {
+                Source::Knows(_, status, _) => Ok(*status),
+                Source::Unaware => Ok(TxStatus::Unknown),
+                Source::Failing => {
+                    Err(ChainStatusError::Unavailable(s!("unreachable")))
+                }
+            }
+        }
+
+        fn output_status(
+            &self,
+            _: &OutPoint,
+        ) -> Result<OutputStatus, ChainStatusError> {
+            match self {
+                Source::Knows(_, _, status) => Ok(*status),
+                Source::Unaware => Ok(OutputStatus::Unknown),
+                Source::Failing => {
+                    Err(ChainStatusError::Unavailable(s!("unreachable")))
+                }
+            }
+        }
+
+        fn connectivity(&self) -> Connectivity {
+            match self {
+                Source::Failing => Connectivity::Unreachable(s!("unreachable")),
+                _ => Connectivity::Connected,
+            }
+        }
+    }
+
+    fn mined(confirmations: u32) -> TxStatus {
+        TxStatus::Mined {
+            height: 100,
+            confirmations,
+        }
+    }
+
+    fn knows(fee: u64) -> Source {
+        Source::Knows(fee, mined(1), OutputStatus::Unspent)
+    }
+
+    fn resolver(sources: Vec<Source>, quorum: usize) -> CompositeTxResolver {
+        CompositeTxResolver::new(
+            sources
+                .into_iter()
+                .map(|source| Box::new(source) as Box<dyn TxSource>)
+                .collect(),
+            quorum,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_invariants() {
+        let sources = |count| {
+            (0..count)
+                .map(|_| Box::new(Source::Unaware) as Box<dyn TxSource>)
+                .collect::<Vec<_>>()
+        };
+        assert!(CompositeTxResolver::new(sources(0), 0).is_err());
+        assert!(CompositeTxResolver::new(sources(0), 1).is_err());
+        assert!(CompositeTxResolver::new(sources(2), 0).is_err());
+        assert!(CompositeTxResolver::new(sources(2), 3).is_err());
+        assert!(CompositeTxResolver::new(sources(2), 2).is_ok());
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let txid = Txid::hash(&[1]);
+        let resolve =
+            |sources, quorum| resolver(sources, quorum).resolve(&txid);
+
+        assert_eq!(
+            resolve(vec![Source::Failing, knows(10)], 1),
+            Ok(Some((tx(), 10)))
+        );
+        assert_eq!(
+            resolve(vec![Source::Unaware, knows(10), knows(20)], 1),
+            Ok(Some((tx(), 10)))
+        );
+        assert_eq!(
+            resolve(vec![Source::Unaware, Source::Unaware], 1),
+            Ok(None)
+        );
+        // Failed source might have known the transaction
+        assert_eq!(
+            resolve(vec![Source::Failing, Source::Unaware], 1),
+            Err(TxResolverError)
+        );
+    }
+
+    #[test]
+    fn test_quorum() {
+        let txid = Txid::hash(&[1]);
+        let resolve =
+            |sources, quorum| resolver(sources, quorum).resolve(&txid);
+
+        assert_eq!(
+            resolve(vec![knows(10), Source::Unaware, knows(10)], 2),
+            Ok(Some((tx(), 10)))
+        );
+        assert_eq!(
+            resolve(vec![knows(10), knows(10), knows(20)], 2),
+            Ok(Some((tx(), 10)))
+        );
+        assert_eq!(resolve(vec![knows(10), Source::Unaware], 2), Ok(None));
+        assert_eq!(
+            resolve(vec![knows(10), Source::Failing], 2),
+            Err(TxResolverError)
+        );
+    }
+
+    #[test]
+    fn test_disagreement() {
+        let txid = Txid::hash(&[1]);
+        let resolve =
+            |sources, quorum| resolver(sources, quorum).resolve(&txid);
+
+        assert_eq!(
+            resolve(vec![knows(10), knows(20), knows(10)], 2),
+            Err(TxResolverError)
+        );
+        assert_eq!(
+            resolve(vec![knows(10), knows(20)], 2),
+            Err(TxResolverError)
+        );
+        assert_eq!(
+            resolve(vec![knows(10), knows(20), Source::Unaware], 2),
+            Err(TxResolverError)
+        );
+    }
+
+    #[test]
+    fn test_tx_status() {
+        let txid = Txid::hash(&[1]);
+        let status =
+            |sources, quorum| resolver(sources, quorum).tx_status(&txid);
+        let source = |status| Source::Knows(10, status, OutputStatus::Unspent);
+
+        // The least confirmed status is reported
+        assert_eq!(
+            status(vec![source(mined(5)), source(mined(3))], 2),
+            Ok(mined(3))
+        );
+        assert_eq!(
+            status(vec![source(mined(5)), source(TxStatus::Mempool)], 2),
+            Ok(TxStatus::Mempool)
+        );
+        assert_eq!(
+            status(
+                vec![source(TxStatus::Mempool), source(TxStatus::Conflicted)],
+                2
+            ),
+            Ok(TxStatus::Conflicted)
+        );
+        // Conflict is reported by any source, even beyond the quorum
+        assert_eq!(
+            status(vec![source(mined(5)), source(TxStatus::Conflicted)], 1),
+            Ok(TxStatus::Conflicted)
+        );
+        assert_eq!(
+            status(vec![source(mined(5)), Source::Unaware], 2),
+            Ok(TxStatus::Unknown)
+        );
+        assert!(status(vec![source(mined(5)), Source::Failing], 2).is_err());
+        assert_eq!(
+            status(vec![Source::Failing, source(mined(5))], 1),
+            Ok(mined(5))
+        );
+    }
+
+    #[test]
+    fn test_output_status() {
+        let outpoint = OutPoint::new(Txid::hash(&[1]), 0);
+        let spender = Txid::hash(&[2]);
+        let status = |sources, quorum| {
+            resolver(sources, quorum).output_status(&outpoint)
+        };
+        let source = |status| Source::Knows(10, mined(1), status);
+
+        assert_eq!(
+            status(
+                vec![
+                    source(OutputStatus::Spent(None)),
+                    source(OutputStatus::Spent(Some(spender)))
+                ],
+                2
+            ),
+            Ok(OutputStatus::Spent(Some(spender)))
+        );
+        assert_eq!(
+            status(
+                vec![
+                    source(OutputStatus::Unspent),
+                    source(OutputStatus::Unspent)
+                ],
+                2
+            ),
+            Ok(OutputStatus::Unspent)
+        );
+        assert!(matches!(
+            status(
+                vec![
+                    source(OutputStatus::Unspent),
+                    source(OutputStatus::Spent(None))
+                ],
+                2
+            ),
+            Err(ChainStatusError::Inconsistency(_))
+        ));
+        assert_eq!(
+            status(vec![source(OutputStatus::Unspent), Source::Unaware], 2),
+            Ok(OutputStatus::Unknown)
+        );
+    }
+}
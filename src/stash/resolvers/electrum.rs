@@ -0,0 +1,304 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use core::time::Duration;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use electrum_client::{Client, ElectrumApi, Error};
+
+use lnpbp::bitcoin::{OutPoint, Transaction, Txid};
+use lnpbp::rgb::validation::{TxResolver, TxResolverError};
+
+use super::{prevout_value, tx_fee};
+use crate::util::chain::{
+    ChainStatus, ChainStatusError, Connectivity, OutputStatus, TxStatus,
+};
+
+/// Delay before reconnecting to the server after the first failure; it is
+/// doubled with each subsequent failure up to [`MAX_RECONNECT_DELAY`]
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
+fn map_electrum_err(other: String) -> TxResolverError {
+    log::error!("Electrum error: {}", other);
+
+    TxResolverError
+}
+
+/// Connection to the server, which is established on the first request and
+/// re-established after failures
+struct Connection {
+    client: Option<Client>,
+    failures: u32,
+    retry_at: Instant,
+    last_error: Option<String>,
+}
+
+impl Connection {
+    fn fail(&mut self, err: &Error) -> String {
+        let delay = RECONNECT_DELAY
+            .checked_mul(1 << self.failures.min(9))
+            .unwrap_or(MAX_RECONNECT_DELAY)
+            .min(MAX_RECONNECT_DELAY);
+        let err = format!("{:?}", err);
+        log::warn!(
+            "Electrum server is unavailable ({}); reconnecting in {} s",
+            err,
+            delay.as_secs()
+        );
+        self.client = None;
+        self.failures += 1;
+        self.retry_at = Instant::now() + delay;
+        self.last_error = Some(err.clone());
+        err
+    }
+}
+
+/// Resolver using Electrum server. The server is not contacted until the
+/// first request, so the daemon may start without chain access; dropped
+/// connections are re-established with exponential backoff.
+pub struct ElectrumTxResolver {
+    server: String,
+    connection: Mutex<Connection>,
+}
+
+impl ElectrumTxResolver {
+    pub fn new(server: &str) -> Self {
+        ElectrumTxResolver {
+            server: server.to_string(),
+            connection: Mutex::new(Connection {
+                client: None,
+                failures: 0,
+                retry_at: Instant::now(),
+                last_error: None,
+            }),
+        }
+    }
+
+    /// Runs request with the server connection, connecting to the server if
+    /// needed. Connection is dropped on the request failure, unless it is the
+    /// error reported by the server, and the request is retried once with a
+    /// new connection.
+    fn request<T>(
+        &self,
+        f: impl Fn(&Client) -> Result<T, Error>,
+    ) -> Result<T, String> {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| s!("connection lock is poisoned"))?;
+        let mut reconnected = false;
+        loop {
+            if connection.client.is_none() {
+                if Instant::now() < connection.retry_at {
+                    return Err(format!(
+                        "server is unavailable: {}",
+                        connection.last_error.clone().unwrap_or_default()
+                    ));
+                }
+                match Client::new(&self.server, None) {
+                    Ok(client) => {
+                        log::info!(
+                            "Connected to Electrum server {}",
+                            self.server
+                        );
+                        connection.client = Some(client);
+                        reconnected = true;
+                    }
+                    Err(err) => return Err(connection.fail(&err)),
+                }
+            }
+            let client = connection
+                .client
+                .as_ref()
+                .expect("connection is established above");
+            match f(client) {
+                Ok(result) => {
+                    connection.failures = 0;
+                    connection.last_error = None;
+                    return Ok(result);
+                }
+                Err(Error::Protocol(err)) => return Err(format!("{}", err)),
+                Err(err) if reconnected => return Err(connection.fail(&err)),
+                Err(err) => {
+                    // Connection may have been closed by the server while
+                    // idle, so we try a fresh one before giving up
+                    log::debug!("Electrum connection is lost: {:?}", err);
+                    connection.client = None;
+                    connection.retry_at = Instant::now();
+                }
+            }
+        }
+    }
+
+    /// Fetches transaction, returning `None` if it is unknown to the server
+    fn transaction(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<Transaction>, ChainStatusError> {
+        self.request(|client| match client.transaction_get(txid) {
+            Ok(tx) => Ok(Some(tx)),
+            // Server returns protocol error for the transactions it does not
+            // know about
+            Err(Error::Protocol(_)) => Ok(None),
+            Err(err) => Err(err),
+        })
+        .map_err(ChainStatusError::Unavailable)
+    }
+}
+
+impl ChainStatus for ElectrumTxResolver {
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainStatusError> {
+        let tx = match self.transaction(txid)? {
+            Some(tx) => tx,
+            None => return Ok(TxStatus::Unknown),
+        };
+
+        // Electrum does not provide a way to get transaction height directly,
+        // so we look the transaction up in the history of its first spendable
+        // output; provably unspendable scripts, like OP_RETURN, are not
+        // indexed by the servers
+        let script = &tx
+            .output
+            .iter()
+            .find(|output| !output.script_pubkey.is_provably_unspendable())
+            .ok_or_else(|| {
+                ChainStatusError::Unavailable(format!(
+                    "transaction {} has no spendable outputs to look it up",
+                    txid
+                ))
+            })?
+            .script_pubkey;
+        let height = self
+            .request(|client| client.script_get_history(script))
+            .map_err(ChainStatusError::Unavailable)?
+            .into_iter()
+            .find(|entry| entry.tx_hash == *txid)
+            .map(|entry| entry.height)
+            .ok_or_else(|| {
+                ChainStatusError::Unavailable(format!(
+                    "transaction {} is missing from its output script history",
+                    txid
+                ))
+            })?;
+        // Mempool transactions have zero height, or -1 if they spend other
+        // unconfirmed transactions
+        if height <= 0 {
+            return Ok(TxStatus::Mempool);
+        }
+
+        let tip = self
+            .request(|client| client.block_headers_subscribe())
+            .map_err(ChainStatusError::Unavailable)?
+            .height as u32;
+        let height = height as u32;
+        Ok(TxStatus::Mined {
+            height,
+            confirmations: tip.saturating_sub(height) + 1,
+        })
+    }
+
+    /// Looks for the spending transaction in the history of the output
+    /// script, which includes mempool transactions
+    fn output_status(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError> {
+        let tx = match self.transaction(&outpoint.txid)? {
+            Some(tx) => tx,
+            None => return Ok(OutputStatus::Unknown),
+        };
+        let script = &tx
+            .output
+            .get(outpoint.vout as usize)
+            .ok_or_else(|| {
+                ChainStatusError::Inconsistency(format!(
+                    "transaction {} has no output #{}",
+                    outpoint.txid, outpoint.vout
+                ))
+            })?
+            .script_pubkey;
+        let txids = self
+            .request(|client| client.script_get_history(script))
+            .map_err(ChainStatusError::Unavailable)?
+            .into_iter()
+            .map(|entry| entry.tx_hash)
+            .filter(|txid| *txid != outpoint.txid)
+            .collect::<BTreeSet<_>>();
+        if txids.is_empty() {
+            return Ok(OutputStatus::Unspent);
+        }
+        Ok(self
+            .request(|client| client.batch_transaction_get(&txids))
+            .map_err(ChainStatusError::Unavailable)?
+            .into_iter()
+            .find(|tx| {
+                tx.input
+                    .iter()
+                    .any(|input| input.previous_output == *outpoint)
+            })
+            .map(|tx| OutputStatus::Spent(Some(tx.txid())))
+            .unwrap_or(OutputStatus::Unspent))
+    }
+
+    /// Reports state of the server connection left by the last request
+    fn connectivity(&self) -> Connectivity {
+        let connection = match self.connection.lock() {
+            Ok(connection) => connection,
+            Err(_) => {
+                return Connectivity::Unreachable(s!(
+                    "connection lock is poisoned"
+                ))
+            }
+        };
+        match (&connection.client, &connection.last_error) {
+            (Some(_), _) => Connectivity::Connected,
+            (None, Some(err)) => Connectivity::Unreachable(err.clone()),
+            (None, None) => Connectivity::Unknown,
+        }
+    }
+}
+
+impl TxResolver for ElectrumTxResolver {
+    fn resolve(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+        log::debug!("Resolving txid {}", txid);
+
+        let tx = self
+            .request(|client| client.transaction_get(txid))
+            .map_err(map_electrum_err)?;
+
+        // All previous transactions are fetched with a single batch request
+        let prev_txids = tx
+            .input
+            .iter()
+            .map(|input| input.previous_output.txid)
+            .collect::<BTreeSet<_>>();
+        let prev_txs = self
+            .request(|client| client.batch_transaction_get(&prev_txids))
+            .map_err(map_electrum_err)?
+            .into_iter()
+            .map(|prev_tx| (prev_tx.txid(), prev_tx))
+            .collect::<HashMap<_, _>>();
+        let fee = tx_fee(&tx, |outpoint| prevout_value(&prev_txs, outpoint))?;
+
+        log::debug!("Calculated fee: {}", fee);
+
+        Ok(Some((tx, fee)))
+    }
+}
@@ -0,0 +1,113 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::{fs, io};
+
+use lnpbp::bitcoin::consensus::deserialize;
+use lnpbp::bitcoin::hashes::hex::FromHex;
+use lnpbp::bitcoin::{OutPoint, Transaction, Txid};
+use lnpbp::rgb::validation::{TxResolver, TxResolverError};
+
+use super::{prevout_value, tx_fee};
+use crate::util::chain::{
+    ChainStatus, ChainStatusError, Connectivity, OutputStatus, TxStatus,
+};
+
+/// Resolver for the air-gapped validation, which takes transactions from the
+/// files of a directory. Each file must contain a single raw transaction,
+/// either in binary or hex encoding; file names are not significant. To
+/// compute the fee, transactions spent by the resolved one must be present
+/// in the directory as well.
+pub struct OfflineTxResolver {
+    txs: HashMap<Txid, Transaction>,
+}
+
+impl OfflineTxResolver {
+    /// Reads all transactions from the directory. Fails on files which are
+    /// not valid transactions, so a mistake will not pass unnoticed.
+    pub fn load(dir: &Path) -> Result<Self, io::Error> {
+        let mut txs = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let tx = parse_tx(&fs::read(&path)?).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not a raw transaction", path.display()),
+                )
+            })?;
+            txs.insert(tx.txid(), tx);
+        }
+        log::debug!("Loaded {} offline transactions", txs.len());
+        Ok(Self { txs })
+    }
+}
+
+fn parse_tx(data: &[u8]) -> Option<Transaction> {
+    if let Ok(tx) = deserialize(data) {
+        return Some(tx);
+    }
+    let hex = std::str::from_utf8(data).ok()?.trim();
+    deserialize(&Vec::<u8>::from_hex(hex).ok()?).ok()
+}
+
+impl TxResolver for OfflineTxResolver {
+    fn resolve(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<(Transaction, u64)>, TxResolverError> {
+        let tx = match self.txs.get(txid) {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+        let fee = tx_fee(tx, |outpoint| prevout_value(&self.txs, outpoint))
+            .map_err(|err| {
+                log::error!("Transactions spent by {} are missing", txid);
+                err
+            })?;
+        Ok(Some((tx.clone(), fee)))
+    }
+}
+
+impl ChainStatus for OfflineTxResolver {
+    /// Raw transactions carry no information on whether they are mined
+    fn tx_status(&self, _txid: &Txid) -> Result<TxStatus, ChainStatusError> {
+        Ok(TxStatus::Unknown)
+    }
+
+    /// Output is reported as spent only if the spending transaction is in
+    /// the directory; otherwise there is no way to tell whether it is spent
+    fn output_status(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError> {
+        Ok(self
+            .txs
+            .values()
+            .find(|tx| {
+                tx.input
+                    .iter()
+                    .any(|input| input.previous_output == *outpoint)
+            })
+            .map(|tx| OutputStatus::Spent(Some(tx.txid())))
+            .unwrap_or(OutputStatus::Unknown))
+    }
+
+    fn connectivity(&self) -> Connectivity {
+        Connectivity::Offline
+    }
+}
@@ -11,7 +11,9 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+use std::collections::VecDeque;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use lnpbp::bitcoin::{Transaction, Txid};
 use lnpbp::lnp::presentation::Encode;
@@ -21,23 +23,45 @@ use lnpbp::lnp::{
     Unmarshall, Unmarshaller,
 };
 use lnpbp::rgb::{
-    validation, Anchor, Assignments, Consignment, ContractId, Genesis, Node,
-    NodeId, Schema, SchemaId, Stash, Validity,
+    validation, Anchor, Assignments, ContractId, Genesis, Node, NodeId, Schema,
+    SchemaId, Stash,
 };
 
-use super::electrum::ElectrumTxResolver;
 use super::index::{BTreeIndex, Index};
+use super::resolvers::{CachingTxResolver, CompositeTxResolver, ResolverUrl};
 #[cfg(not(store_hammersbald))] // Default store
 use super::storage::{DiskStorage, DiskStorageConfig, Store};
+use super::witness::WitnessTracker;
 use super::Config;
-use crate::api::stash::{ConsignRequest, MergeRequest, Request};
-use crate::api::{reply, Reply};
+use crate::api::event::{MergeEvent, ValidationEvent};
+use crate::api::hello::{self, Capabilities, Hello};
+use crate::api::stash::{
+    ConsignRequest, MergeRequest, Request, ValidateRequest,
+};
+use crate::api::validation::WitnessStatus;
+use crate::api::{reply, Event, Reply, ValidationStatus};
+use crate::auth::{self, Role};
+use crate::constants::RGB_PROTOCOL_VERSION;
 use crate::error::{
-    BootstrapError, RuntimeError, ServiceError, ServiceErrorDomain,
-    ServiceErrorSource,
+    BootstrapError, NetworkMismatch, RuntimeError, ServiceError,
+    ServiceErrorDomain, ServiceErrorSource, StorageError,
 };
 use crate::service::TryService;
 use crate::stash::index::BTreeIndexConfig;
+use crate::util::chain::{ChainStatus, TxStatus};
+use crate::util::metrics::{Exporter, Metrics};
+use crate::util::shutdown::{self, Shutdown};
+
+/// Interval for checking whether shutdown was requested while waiting for RPC
+/// requests, in milliseconds
+const SHUTDOWN_POLL_INTERVAL: i64 = 500;
+
+/// Interval for checking confirmations of the awaited witness transactions
+const WITNESS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of the recently merged consignments kept for the subscribers
+/// reading them with [`Request::ReadMerge`]
+const MERGES_KEPT: usize = 64;
 
 pub struct Runtime {
     /// Original configuration object
@@ -71,8 +95,25 @@ pub struct Runtime {
     /// Unmarshaller instance used for parsing RPC request
     unmarshaller: Unmarshaller<Request>,
 
-    /// Electrum client handle to fetch transactions
-    electrum: ElectrumTxResolver,
+    /// Resolver fetching transactions for consignment validation
+    resolver: CachingTxResolver<CompositeTxResolver>,
+
+    /// Witness transactions of merged consignments tracked for
+    /// confirmations and reorgs
+    witnesses: WitnessTracker,
+
+    /// Time of the last witness status check
+    witnesses_checked: Instant,
+
+    /// Recently merged consignments with their revealed seals, which are
+    /// not published with [`Event::ConsignmentMerged`]
+    merges: VecDeque<(MergeEvent, MergeRequest)>,
+
+    /// Tracks shutdown signals and requests
+    shutdown: Shutdown,
+
+    /// Statistics of processed requests
+    metrics: Metrics,
 }
 
 impl Runtime {
@@ -98,21 +139,46 @@ impl Runtime {
             index_file: PathBuf::from(config.index.clone()),
         })?;
 
-        let session_rpc = session::Raw::with_zmq_unencrypted(
-            ZmqType::Rep,
-            &config.rpc_endpoint,
-            None,
-            None,
+        let session_rpc =
+            config
+                .auth
+                .bind(ZmqType::Rep, &config.rpc_endpoint, "stashd")?;
+
+        let session_pub =
+            config
+                .auth
+                .bind(ZmqType::Pub, &config.pub_endpoint, "stashd")?;
+
+        let sources = config
+            .resolvers
+            .iter()
+            .map(ResolverUrl::connect)
+            .collect::<Result<_, _>>()?;
+        let resolver = CachingTxResolver::new(
+            CompositeTxResolver::new(sources, config.resolver_quorum)?,
+            config.tx_cache.clone(),
         )?;
 
-        let session_pub = session::Raw::with_zmq_unencrypted(
-            ZmqType::Pub,
-            &config.pub_endpoint,
-            None,
-            None,
-        )?;
+        let witnesses = WitnessTracker::load(
+            PathBuf::from(config.stash.clone()).join("witnesses.dat"),
+            config.finality_depth,
+        )
+        .map_err(|err| {
+            error!("Unable to load tracked witnesses: {}", err);
+            BootstrapError::StorageError
+        })?;
 
-        let electrum = ElectrumTxResolver::new(&config.electrum_server)?;
+        let shutdown = Shutdown::listen()?;
+
+        if let Some(addr) = config.metrics_endpoint {
+            Exporter {
+                daemon: "stashd",
+                auth: config.auth.client(),
+                endpoint: config.rpc_endpoint.clone(),
+                request: Request::Status().encode()?,
+            }
+            .spawn(addr)?;
+        }
 
         Ok(Self {
             config,
@@ -121,7 +187,12 @@ impl Runtime {
             indexer,
             storage,
             unmarshaller: Request::create_unmarshaller(),
-            electrum,
+            resolver,
+            witnesses,
+            witnesses_checked: Instant::now(),
+            merges: VecDeque::with_capacity(MERGES_KEPT),
+            shutdown,
+            metrics: Metrics::default(),
         })
     }
 }
@@ -131,23 +202,46 @@ impl TryService for Runtime {
     type ErrorType = RuntimeError;
 
     async fn try_run_loop(mut self) -> Result<(), Self::ErrorType> {
-        loop {
+        while !self.shutdown.is_requested() {
             match self.run().await {
-                Ok(_) => debug!("API request processing complete"),
+                Ok(_) => trace!("API request processing complete"),
                 Err(err) => {
                     error!("Error processing API request: {}", err);
                     Err(err)?;
                 }
             }
         }
+        self.finalize();
+        Ok(())
     }
 }
 
 impl Runtime {
     async fn run(&mut self) -> Result<(), RuntimeError> {
+        if self.witnesses_checked.elapsed() >= WITNESS_CHECK_INTERVAL {
+            self.check_witnesses();
+        }
+
         trace!("Awaiting for ZMQ RPC requests...");
-        let raw = self.session_rpc.recv_raw_message()?;
-        let reply = self.rpc_process(raw).await.unwrap_or_else(|err| err);
+        // Poll items are not `Send`, so they must be dropped before awaiting
+        let ready = {
+            let mut items =
+                [self.session_rpc.as_socket().as_poll_item(zmq::POLLIN)];
+            zmq::poll(&mut items, SHUTDOWN_POLL_INTERVAL).map_err(|err| {
+                RuntimeError::zmq_request(
+                    &self.config.rpc_endpoint.zmq_socket_string(),
+                    err,
+                )
+            })?;
+            items[0].is_readable()
+        };
+        if !ready {
+            return Ok(());
+        }
+
+        let (raw, client) = auth::recv_authenticated(&mut self.session_rpc)?;
+        let role = self.config.auth.role(client.as_ref());
+        let reply = self.rpc_process(raw, role).await.unwrap_or_else(|err| err);
         trace!("Preparing ZMQ RPC reply: {:?}", reply);
         let data = reply.encode()?;
         trace!(
@@ -158,13 +252,101 @@ impl Runtime {
         Ok(())
     }
 
-    async fn rpc_process(&mut self, raw: Vec<u8>) -> Result<Reply, Reply> {
+    /// Flushes the index to disk, closes API sockets and removes IPC socket
+    /// files. Failures are logged, so they do not prevent the rest of the
+    /// procedure.
+    fn finalize(self) {
+        info!("Shutting down stashd");
+        if let Err(err) = self.indexer.store() {
+            error!("Unable to save RGB index: {}", err);
+        }
+        if let Err(err) = self.witnesses.store() {
+            error!("Unable to save tracked witnesses: {}", err);
+        }
+        let Runtime {
+            config,
+            session_rpc,
+            session_pub,
+            ..
+        } = self;
+        drop(session_rpc);
+        drop(session_pub);
+        shutdown::remove_ipc_file(&config.rpc_endpoint);
+        shutdown::remove_ipc_file(&config.pub_endpoint);
+    }
+
+    /// Updates status of the tracked witness transactions and notifies
+    /// subscribers about the confirmed, reorged and double-spent ones
+    fn check_witnesses(&mut self) {
+        self.witnesses_checked = Instant::now();
+        if self.witnesses.is_empty() {
+            return;
+        }
+        trace!("Checking status of the tracked witnesses");
+        for event in self.witnesses.check(&self.resolver) {
+            self.publish(event);
+        }
+    }
+
+    /// Returns status of the witness transaction. Transactions which can't
+    /// be checked are reported with the unknown status, so they are never
+    /// taken as confirmed.
+    fn witness_status(&self, txid: &Txid) -> TxStatus {
+        self.resolver.tx_status(txid).unwrap_or_else(|err| {
+            warn!("Unable to get status of {}: {}", txid, err);
+            TxStatus::Unknown
+        })
+    }
+
+    /// Returns status of the witness transactions which are below the
+    /// minimal confirmations policy
+    fn awaiting_confirmation<'a>(
+        &self,
+        txids: impl Iterator<Item = &'a Txid>,
+        min_confirmations: u32,
+    ) -> Vec<WitnessStatus> {
+        txids
+            .map(|txid| WitnessStatus {
+                txid: *txid,
+                status: self.witness_status(txid),
+            })
+            .filter(|witness| !witness.status.is_confirmed(min_confirmations))
+            .collect()
+    }
+
+    /// Publishes event to the subscribers. Failure to publish an event must
+    /// not fail the request which has caused it, so we just log the error.
+    fn publish(&mut self, event: Event) {
+        trace!("Publishing event {}", event);
+        let result = event
+            .encode()
+            .map_err(RuntimeError::from)
+            .and_then(|data| Ok(self.session_pub.send_raw_message(&data)?));
+        if let Err(err) = result {
+            error!("Unable to publish event: {}", err);
+        }
+    }
+
+    async fn rpc_process(
+        &mut self,
+        raw: Vec<u8>,
+        role: Role,
+    ) -> Result<Reply, Reply> {
         trace!("Got {} bytes over ZMQ RPC: {:?}", raw.len(), raw);
         let message = &*self.unmarshaller.unmarshall(&raw).map_err(|err| {
             ServiceError::from_rpc(ServiceErrorSource::Stash, err)
         })?;
         debug!("Received ZMQ RPC request: {:?}", message);
-        Ok(match message {
+        role.check(message.required_role(), message.name())
+            .map_err(|err| ServiceError {
+                domain: err,
+                service: ServiceErrorSource::Stash,
+            })?;
+        let started = Instant::now();
+        let result = match message {
+            Request::Hello(hello) => self.rpc_hello(hello).await,
+            Request::Shutdown() => self.rpc_shutdown().await,
+            Request::Status() => self.rpc_status().await,
             Request::ListSchemata() => self.rpc_list_schemata().await,
             Request::ListGeneses() => self.rpc_list_geneses().await,
             Request::AddGenesis(genesis) => self.rpc_add_genesis(genesis).await,
@@ -176,19 +358,77 @@ impl Runtime {
                 self.rpc_read_schema(schema_id).await
             }
             Request::Consign(consign) => self.rpc_consign(consign).await,
-            Request::Validate(consign) => self.rpc_validate(consign).await,
+            Request::Validate(validate) => self.rpc_validate(validate).await,
             Request::Merge(merge) => self.rpc_merge(merge).await,
             Request::Forget(removal_list) => {
                 self.rpc_forget(removal_list).await
             }
-            _ => unimplemented!(),
-        }
-        .map_err(|err| ServiceError {
+            Request::ReadTransitions(node_ids) => {
+                self.rpc_read_transitions(node_ids).await
+            }
+            Request::ReadMerge(merge) => self.rpc_read_merge(merge).await,
+        };
+        self.metrics.record(
+            message.name(),
+            started.elapsed(),
+            result.as_ref().err().map(ToString::to_string),
+        );
+        Ok(result.map_err(|err| ServiceError {
             domain: err,
             service: ServiceErrorSource::Stash,
         })?)
     }
 
+    async fn rpc_hello(
+        &mut self,
+        hello: &Hello,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!(
+            "Got HELLO from {} using protocol version {}",
+            hello.agent, hello.protocol_version
+        );
+        if hello.protocol_version != RGB_PROTOCOL_VERSION {
+            warn!(
+                "Client {} uses protocol version {} instead of {}",
+                hello.agent, hello.protocol_version, RGB_PROTOCOL_VERSION
+            );
+        }
+        Ok(Reply::Capabilities(Capabilities {
+            protocol_version: RGB_PROTOCOL_VERSION,
+            agent: hello::agent("stashd"),
+            requests: Request::NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            schemata: self.storage.schema_ids()?,
+            network: self.config.network.clone(),
+            storage: if cfg!(store_hammersbald) {
+                s!("hammersbald")
+            } else {
+                s!("disk")
+            },
+            cache: s!("btree-index"),
+        }))
+    }
+
+    async fn rpc_shutdown(&mut self) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got SHUTDOWN");
+        self.shutdown.request();
+        Ok(Reply::Success)
+    }
+
+    async fn rpc_status(&mut self) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got STATUS");
+        let mut status = self.metrics.status(hello::agent("stashd"));
+        status.storage = bmap! {
+            s!("schemata") => self.storage.schema_ids()?.len() as u64,
+            s!("geneses") => self.storage.contract_ids()?.len() as u64
+        };
+        status.cache_size = self.indexer.len() as u64;
+        status.chain = Some(self.resolver.connectivity());
+        Ok(Reply::Status(status))
+    }
+
     async fn rpc_list_schemata(&mut self) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got LIST_SCHEMATA");
         let ids = self.storage.schema_ids()?;
@@ -206,7 +446,12 @@ impl Runtime {
         schema: &Schema,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got ADD_SCHEMA {}", schema);
+        let schema_id = schema.schema_id();
+        let known = self.storage.has_schema(&schema_id)?;
         self.storage.add_schema(schema)?;
+        if !known {
+            self.publish(Event::SchemaAdded(schema_id));
+        }
         Ok(Reply::Success)
     }
 
@@ -215,6 +460,7 @@ impl Runtime {
         genesis: &Genesis,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got ADD_GENESIS {}", genesis);
+        NetworkMismatch::check(&self.config.network, genesis.chain())?;
         self.storage.add_genesis(genesis)?;
         Ok(Reply::Success)
     }
@@ -237,6 +483,18 @@ impl Runtime {
         Ok(Reply::Schema(schema))
     }
 
+    async fn rpc_read_transitions(
+        &mut self,
+        node_ids: &Vec<NodeId>,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got READ_TRANSITIONS for {} nodes", node_ids.len());
+        let transitions = node_ids
+            .iter()
+            .map(|node_id| self.storage.transition(node_id))
+            .collect::<Result<_, _>>()?;
+        Ok(Reply::Transitions(transitions))
+    }
+
     async fn rpc_consign(
         &mut self,
         request: &ConsignRequest,
@@ -271,36 +529,42 @@ impl Runtime {
 
     async fn rpc_validate(
         &mut self,
-        consignment: &Consignment,
+        validate: &ValidateRequest,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got VALIDATE CONSIGNMENT");
+        let consignment = &validate.consignment;
+        let min_confirmations = validate
+            .min_confirmations
+            .unwrap_or(self.config.min_confirmations);
+        NetworkMismatch::check(
+            &self.config.network,
+            consignment.genesis.chain(),
+        )?;
 
         let schema = self
             .storage()
             .schema(&consignment.genesis.schema_id())
-            .map_err(|err| ServiceErrorDomain::Storage(err.to_string()))?;
+            .map_err(Into::<ServiceErrorDomain>::into)?;
 
         // [VALIDATION]: Validate genesis node against the scheme
-        let validation_status = consignment.validate(&schema, &self.electrum);
-
-        self.storage.add_genesis(&consignment.genesis)?;
-
-        match validation_status.validity() {
-            Validity::Valid => Ok(Reply::Success),
-            Validity::UnresolvedTransactions => {
-                Ok(Reply::Failure(reply::Failure {
-                    code: 1,
-                    info: format!("{:?}", validation_status.unresolved_txids),
-                }))
-            }
-            Validity::Invalid => Ok(Reply::Failure(reply::Failure {
-                code: 2,
-                info: format!("{:?}", validation_status.failures),
-            })),
-        }
-        // TODO: Return this type of reply when StrictEncoding will be
-        //       implemented for validation::Status
-        //Ok(Reply::ValidationStatus(validation_status))
+        let validation_status = consignment.validate(&schema, &self.resolver);
+
+        let mut status = ValidationStatus::from(validation_status);
+        // Unresolved transactions are reported already, so they are not
+        // listed among the awaited ones
+        let txids = consignment
+            .txids()
+            .into_iter()
+            .filter(|txid| !status.unresolved_txids.contains(txid))
+            .collect::<Vec<_>>();
+        status.awaiting_confirmation =
+            self.awaiting_confirmation(txids.iter(), min_confirmations);
+        self.publish(Event::ValidationFinished(ValidationEvent {
+            contract_id: consignment.genesis.contract_id(),
+            status: status.clone(),
+        }));
+
+        Ok(Reply::ValidationStatus(status))
     }
 
     async fn rpc_merge(
@@ -308,6 +572,10 @@ impl Runtime {
         merge: &MergeRequest,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got MERGE CONSIGNMENT");
+        NetworkMismatch::check(
+            &self.config.network,
+            merge.consignment.genesis.chain(),
+        )?;
 
         let known_seals = &merge.reveal_outpoints;
 
@@ -339,6 +607,10 @@ impl Runtime {
                 }
             };
 
+        // Validation does not modify the stash, so the genesis is stored only
+        // when the consignment gets merged
+        self.storage.add_genesis(&merge.consignment.genesis)?;
+
         for (anchor, transition) in &merge.consignment.state_transitions {
             let mut transition = transition.clone();
             transition
@@ -350,6 +622,18 @@ impl Runtime {
             self.storage.add_transition(&transition)?;
         }
 
+        let contract_id = merge.consignment.genesis.contract_id();
+        let min_confirmations = merge
+            .min_confirmations
+            .unwrap_or(self.config.min_confirmations);
+        // Witnesses are tracked even if confirmed already, since they still
+        // may be reorged out
+        for txid in merge.consignment.txids() {
+            let status = self.witness_status(&txid);
+            self.witnesses
+                .add(txid, contract_id, min_confirmations, status);
+        }
+
         for extension in &merge.consignment.state_extensions {
             let mut extension = extension.clone();
             extension
@@ -359,12 +643,52 @@ impl Runtime {
             self.storage.add_extension(&extension)?;
         }
 
+        let event = MergeEvent {
+            contract_id,
+            node_ids: merge
+                .consignment
+                .state_transitions
+                .iter()
+                .map(|(_, transition)| transition.node_id())
+                .chain(
+                    merge
+                        .consignment
+                        .state_extensions
+                        .iter()
+                        .map(Node::node_id),
+                )
+                .collect(),
+        };
+        if self.merges.len() >= MERGES_KEPT {
+            self.merges.pop_front();
+        }
+        self.merges.push_back((event.clone(), merge.clone()));
+        self.publish(Event::ConsignmentMerged(event));
+
         Ok(Reply::Success)
     }
 
+    async fn rpc_read_merge(
+        &mut self,
+        event: &MergeEvent,
+    ) -> Result<Reply, ServiceErrorDomain> {
+        debug!("Got READ MERGE {}", event);
+        self.merges
+            .iter()
+            .rev()
+            .find(|(merged, _)| merged == event)
+            .map(|(_, merge)| Reply::Merge(merge.clone()))
+            .ok_or_else(|| {
+                ServiceErrorDomain::Storage(StorageError::NotFound(format!(
+                    "consignment merge {}",
+                    event
+                )))
+            })
+    }
+
     async fn rpc_forget(
         &mut self,
-        _removal_list: &Vec<(NodeId, u16)>,
+        removal_list: &Vec<(NodeId, u16)>,
     ) -> Result<Reply, ServiceErrorDomain> {
         debug!("Got FORGET");
 
@@ -373,6 +697,8 @@ impl Runtime {
         //       not have any other _known_ outpoints, remove them — and iterate
         //       over their direct ancestor in the same manner
 
+        self.publish(Event::ForgetRequested(removal_list.clone()));
+
         Ok(Reply::Success)
     }
 }
@@ -390,7 +716,6 @@ impl validation::TxResolver for DummyTxResolver {
 
 pub async fn main_with_config(config: Config) -> Result<(), BootstrapError> {
     let runtime = Runtime::init(config)?;
-    runtime.run_or_panic("Stashd runtime").await;
-
-    unreachable!()
+    runtime.run_until_shutdown("Stashd runtime").await;
+    Ok(())
 }
@@ -18,7 +18,7 @@ use lnpbp::hex::ToHex;
 use lnpbp::rgb::prelude::*;
 
 use super::Store;
-use crate::error::{BootstrapError, ServiceErrorDomain};
+use crate::error::{BootstrapError, ServiceErrorDomain, StorageError};
 use crate::util::file::*;
 
 #[derive(Debug, Display, Error, From)]
@@ -38,9 +38,23 @@ pub enum DiskStorageError {
     BrokenFilenames,
 }
 
+impl From<DiskStorageError> for StorageError {
+    fn from(err: DiskStorageError) -> Self {
+        match err {
+            DiskStorageError::Io(err)
+                if err.kind() == io::ErrorKind::NotFound =>
+            {
+                StorageError::NotFound(err.to_string())
+            }
+            DiskStorageError::Io(err) => StorageError::Io(err.to_string()),
+            err => StorageError::DataIntegrity(err.to_string()),
+        }
+    }
+}
+
 impl From<DiskStorageError> for ServiceErrorDomain {
     fn from(err: DiskStorageError) -> Self {
-        ServiceErrorDomain::Storage(err.to_string())
+        ServiceErrorDomain::Storage(err.into())
     }
 }
 
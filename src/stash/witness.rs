@@ -0,0 +1,320 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use lnpbp::bitcoin::Txid;
+use lnpbp::rgb::ContractId;
+use lnpbp::strict_encoding::{self, StrictDecode, StrictEncode};
+
+use crate::api::event::WitnessEvent;
+use crate::api::Event;
+use crate::util::chain::{ChainStatus, TxStatus};
+
+/// Witness transaction of the merged consignments which is tracked until it
+/// gets deep enough in the chain not to be reorged out
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display(Debug)]
+struct TrackedWitness {
+    contract_ids: BTreeSet<ContractId>,
+    min_confirmations: u32,
+    status: TxStatus,
+    /// Whether the transaction has reached the required number of
+    /// confirmations since it was added or reorged out for the last time
+    confirmed: bool,
+}
+
+impl TrackedWitness {
+    /// Checks whether the transaction with the given status satisfies the
+    /// confirmations policy. Unlike validation, the zero policy is not
+    /// satisfied by the transactions missing from the mempool, since the
+    /// tracked ones were known before.
+    fn is_confirmed(&self, status: TxStatus) -> bool {
+        status != TxStatus::Unknown
+            && status.is_confirmed(self.min_confirmations)
+    }
+}
+
+/// Persistent list of witness transactions of the merged consignments. Each
+/// check updates their status from the chain, reporting the transactions
+/// which have reached the required depth, were reorged out of the chain (or
+/// dropped from the mempool) or double-spent. Transactions are tracked until
+/// they reach the finality depth or get double-spent.
+#[derive(Debug)]
+pub struct WitnessTracker {
+    file: PathBuf,
+    finality_depth: u32,
+    witnesses: BTreeMap<Txid, TrackedWitness>,
+}
+
+impl WitnessTracker {
+    pub fn load(
+        file: PathBuf,
+        finality_depth: u32,
+    ) -> Result<Self, strict_encoding::Error> {
+        let witnesses = match fs::File::open(&file) {
+            Ok(data) => {
+                debug!("Loading tracked witnesses from file {:?} ...", file);
+                StrictDecode::strict_decode(data)?
+            }
+            Err(_) => bmap! {},
+        };
+        Ok(Self {
+            file,
+            finality_depth,
+            witnesses,
+        })
+    }
+
+    pub fn store(&self) -> Result<(), strict_encoding::Error> {
+        debug!("Saving tracked witnesses to file {:?} ...", self.file);
+        let file = fs::File::create(&self.file)?;
+        self.witnesses.strict_encode(file)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.witnesses.is_empty()
+    }
+
+    /// Starts tracking the transaction, unless it is final already. If the
+    /// transaction is tracked already, the stricter policy is kept.
+    pub fn add(
+        &mut self,
+        txid: Txid,
+        contract_id: ContractId,
+        min_confirmations: u32,
+        status: TxStatus,
+    ) {
+        if status.is_confirmed(min_confirmations)
+            && status.confirmations() >= self.finality_depth
+        {
+            return;
+        }
+        let witness =
+            self.witnesses
+                .entry(txid)
+                .or_insert_with(|| TrackedWitness {
+                    contract_ids: bset! {},
+                    min_confirmations,
+                    status,
+                    confirmed: false,
+                });
+        witness.contract_ids.insert(contract_id);
+        witness.min_confirmations =
+            witness.min_confirmations.max(min_confirmations);
+        witness.status = status;
+        witness.confirmed = witness.is_confirmed(status);
+    }
+
+    /// Updates status of the tracked transactions, returning events for the
+    /// ones which have reached the required depth, were reorged out or
+    /// double-spent. Transactions whose status can't be fetched are kept
+    /// for the next check.
+    pub fn check(&mut self, chain: &impl ChainStatus) -> Vec<Event> {
+        let mut events = vec![];
+        let mut finished = vec![];
+        for (txid, witness) in self.witnesses.iter_mut() {
+            let status = match chain.tx_status(txid) {
+                Ok(status) => status,
+                Err(err) => {
+                    warn!("Unable to check witness {}: {}", txid, err);
+                    continue;
+                }
+            };
+            let previous = witness.status;
+            witness.status = status;
+            let event = WitnessEvent {
+                txid: *txid,
+                contract_ids: witness.contract_ids.clone(),
+                status,
+            };
+            match (previous, status) {
+                (_, TxStatus::Conflicted) => {
+                    warn!("Witness transaction {} was double-spent", txid);
+                    events.push(Event::WitnessConflicted(event));
+                    finished.push(*txid);
+                    continue;
+                }
+                (TxStatus::Mined { .. }, TxStatus::Mempool)
+                | (TxStatus::Mined { .. }, TxStatus::Unknown) => {
+                    warn!("Witness transaction {} was reorged out", txid);
+                    witness.confirmed = false;
+                    events.push(Event::WitnessReorged(event));
+                    continue;
+                }
+                (TxStatus::Mempool, TxStatus::Unknown) => {
+                    warn!("Witness transaction {} left the mempool", txid);
+                    witness.confirmed = false;
+                    events.push(Event::WitnessReorged(event));
+                    continue;
+                }
+                _ => {}
+            }
+            if !witness.confirmed && witness.is_confirmed(status) {
+                witness.confirmed = true;
+                events.push(Event::WitnessConfirmed(event));
+            }
+            if witness.confirmed
+                && status.confirmations() >= self.finality_depth
+            {
+                finished.push(*txid);
+            }
+        }
+        for txid in finished {
+            self.witnesses.remove(&txid);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lnpbp::bitcoin::hashes::Hash;
+    use lnpbp::bitcoin::OutPoint;
+
+    use super::*;
+    use crate::util::chain::{ChainStatusError, Connectivity, OutputStatus};
+
+    /// Chain with a single transaction, which status may be unavailable
+    struct Chain(Option<TxStatus>);
+
+    impl ChainStatus for Chain {
+        fn tx_status(&self, _: &Txid) -> Result<TxStatus, ChainStatusError> {
+            self.0
+                .ok_or_else(|| ChainStatusError::Unavailable(s!("offline")))
+        }
+
+        fn output_status(
+            &self,
+            _: &OutPoint,
+        ) -> Result<OutputStatus, ChainStatusError> {
+            Ok(OutputStatus::Unknown)
+        }
+
+        fn connectivity(&self) -> Connectivity {
+            Connectivity::Connected
+        }
+    }
+
+    fn mined(confirmations: u32) -> TxStatus {
+        TxStatus::Mined {
+            height: 100,
+            confirmations,
+        }
+    }
+
+    fn tracker() -> WitnessTracker {
+        WitnessTracker::load(PathBuf::from("/nonexistent/witnesses.dat"), 6)
+            .unwrap()
+    }
+
+    /// Checks the tracker against the chain with the given transaction
+    /// status, returning names of the reported events
+    fn check(
+        tracker: &mut WitnessTracker,
+        status: Option<TxStatus>,
+    ) -> Vec<&'static str> {
+        tracker
+            .check(&Chain(status))
+            .into_iter()
+            .map(|event| match event {
+                Event::WitnessConfirmed(_) => "confirmed",
+                Event::WitnessReorged(_) => "reorged",
+                Event::WitnessConflicted(_) => "conflicted",
+                _ => "unexpected",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_confirmation() {
+        let mut tracker = tracker();
+        let txid = Txid::hash(&[1]);
+        tracker.add(txid, ContractId::hash(&[1]), 2, TxStatus::Mempool);
+
+        assert!(check(&mut tracker, Some(TxStatus::Mempool)).is_empty());
+        assert!(check(&mut tracker, Some(mined(1))).is_empty());
+        assert_eq!(check(&mut tracker, Some(mined(2))), vec!["confirmed"]);
+        assert!(check(&mut tracker, Some(mined(3))).is_empty());
+        // Status can't be fetched, so the witness is kept
+        assert!(check(&mut tracker, None).is_empty());
+        assert!(!tracker.is_empty());
+        // Witness is not tracked after it reaches the finality depth
+        assert!(check(&mut tracker, Some(mined(6))).is_empty());
+        assert!(tracker.is_empty());
+
+        // Final transactions are not tracked at all
+        tracker.add(txid, ContractId::hash(&[1]), 2, mined(6));
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn test_reorg() {
+        let mut tracker = tracker();
+        let txid = Txid::hash(&[1]);
+        tracker.add(txid, ContractId::hash(&[1]), 1, mined(1));
+
+        assert_eq!(
+            check(&mut tracker, Some(TxStatus::Mempool)),
+            vec!["reorged"]
+        );
+        assert_eq!(check(&mut tracker, Some(mined(1))), vec!["confirmed"]);
+        assert_eq!(
+            check(&mut tracker, Some(TxStatus::Unknown)),
+            vec!["reorged"]
+        );
+        assert!(check(&mut tracker, Some(TxStatus::Unknown)).is_empty());
+        assert_eq!(check(&mut tracker, Some(mined(2))), vec!["confirmed"]);
+    }
+
+    #[test]
+    fn test_mempool_drop() {
+        let mut tracker = tracker();
+        let txid = Txid::hash(&[1]);
+        // Mempool transactions satisfy zero confirmations policy
+        tracker.add(txid, ContractId::hash(&[1]), 0, TxStatus::Mempool);
+
+        assert!(check(&mut tracker, Some(TxStatus::Mempool)).is_empty());
+        assert_eq!(
+            check(&mut tracker, Some(TxStatus::Unknown)),
+            vec!["reorged"]
+        );
+        assert!(check(&mut tracker, Some(TxStatus::Unknown)).is_empty());
+        assert_eq!(
+            check(&mut tracker, Some(TxStatus::Mempool)),
+            vec!["confirmed"]
+        );
+        assert!(!tracker.is_empty());
+    }
+
+    #[test]
+    fn test_conflict() {
+        let mut tracker = tracker();
+        let txid = Txid::hash(&[1]);
+        tracker.add(txid, ContractId::hash(&[1]), 1, TxStatus::Mempool);
+        tracker.add(txid, ContractId::hash(&[2]), 3, TxStatus::Mempool);
+
+        let events = tracker.check(&Chain(Some(TxStatus::Conflicted)));
+        match &events[..] {
+            [Event::WitnessConflicted(event)] => {
+                assert_eq!(event.txid, txid);
+                assert_eq!(event.contract_ids.len(), 2);
+            }
+            _ => panic!("unexpected events {:?}", events),
+        }
+        assert!(tracker.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use serde::{Deserialize, Serialize};
+
+use lnpbp::bitcoin::{OutPoint, Txid};
+
+/// Status of a bitcoin transaction as seen by some chain data source
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    StrictEncode,
+    StrictDecode,
+)]
+#[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate")
+)]
+pub enum TxStatus {
+    /// Transaction is known neither to the mempool nor to the blockchain
+    Unknown,
+
+    /// Transaction is in the mempool and waits to be mined
+    Mempool,
+
+    /// Transaction is mined in a block at the given height
+    Mined { height: u32, confirmations: u32 },
+
+    /// Transaction can't be mined since some of its inputs were spent by
+    /// another transaction
+    Conflicted,
+}
+
+impl TxStatus {
+    /// Number of transaction confirmations; zero for transactions which are
+    /// not mined
+    pub fn confirmations(&self) -> u32 {
+        match self {
+            TxStatus::Mined { confirmations, .. } => *confirmations,
+            _ => 0,
+        }
+    }
+
+    /// Checks whether transaction satisfies the minimal confirmations
+    /// policy. Zero policy accepts any transaction unless it is conflicted.
+    pub fn is_confirmed(&self, min_confirmations: u32) -> bool {
+        match self {
+            TxStatus::Conflicted => false,
+            _ if min_confirmations == 0 => true,
+            _ => self.confirmations() >= min_confirmations,
+        }
+    }
+}
+
+/// Status of a transaction output as seen by some chain data source
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display)]
+#[display(Debug)]
+pub enum OutputStatus {
+    /// Neither the output nor its spending transaction are known
+    Unknown,
+
+    /// Output exists and is not spent, including by mempool transactions
+    Unspent,
+
+    /// Output is spent by the given transaction, which may be not mined yet;
+    /// some data sources are unable to tell the spending transaction
+    Spent(Option<Txid>),
+}
+
+/// State of the connection to the chain data source
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display(doc_comments)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize,),
+    serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+pub enum Connectivity {
+    /// connected
+    Connected,
+
+    /// offline
+    Offline,
+
+    /// unreachable: {_0}
+    Unreachable(String),
+
+    /// not requested yet
+    Unknown,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ChainStatusError {
+    /// Chain data source is not available: {_0}
+    Unavailable(String),
+
+    /// Chain data source has returned inconsistent data: {_0}
+    Inconsistency(String),
+}
+
+/// Source of information about transaction mining status. Used to track
+/// whether assets assigned to some transaction outputs may be spent already.
+pub trait ChainStatus {
+    /// Returns current status of the transaction with the given id
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainStatusError>;
+
+    /// Returns whether the transaction output is spent and by which
+    /// transaction
+    fn output_status(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError>;
+
+    /// Reports whether the data source was reachable on the last request;
+    /// the source itself is not contacted
+    fn connectivity(&self) -> Connectivity;
+}
+
+/// Chain status source for the offline use: it knows nothing about any
+/// transaction, so all tracked allocations are kept pending
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Default)]
+#[display(Debug)]
+pub struct OfflineChainStatus;
+
+impl ChainStatus for OfflineChainStatus {
+    fn tx_status(&self, _: &Txid) -> Result<TxStatus, ChainStatusError> {
+        Ok(TxStatus::Unknown)
+    }
+
+    fn output_status(
+        &self,
+        _: &OutPoint,
+    ) -> Result<OutputStatus, ChainStatusError> {
+        Ok(OutputStatus::Unknown)
+    }
+
+    fn connectivity(&self) -> Connectivity {
+        Connectivity::Offline
+    }
+}
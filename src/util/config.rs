@@ -0,0 +1,433 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Configuration file shared by all RGB node daemons and rgb-cli.
+//!
+//! The file is in TOML format: parameters common for all of the daemons are
+//! put at the top level, while daemon-specific ones go to `[rgbd]`,
+//! `[stashd]`, `[fungibled]`, `[gateway]` and `[cli]` sections:
+//!
+//! ```toml
+//! data-dir = "/var/lib/rgb"
+//! network = "testnet"
+//!
+//! [stashd]
+//! rpc = "lnpz:/var/lib/rgb/testnet/stashd-default.rpc"
+//!
+//! [fungibled]
+//! confirmations = 3
+//! ```
+//!
+//! Each parameter corresponds to an environment variable read by command-line
+//! options of the daemons (`stashd.rpc` is `RGB_STASHD_RPC`), and values are
+//! layered as defaults < configuration file < environment < command line.
+//! Since endpoints of a daemon are used by the other daemons as well, its
+//! section is read by all of them.
+//!
+//! `network` may be a list of networks, in which case rgbd launches daemons
+//! for each of them; other daemons and rgb-cli then require network to be
+//! given with `--network` argument. The same applies to `rgbd.wallets`, each
+//! of which gets its own stash and cache; other daemons and rgb-cli select
+//! the wallet with the top-level `wallet` parameter or `--wallet` argument.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+
+use clap::Clap;
+use settings::{File, FileFormat, Value};
+
+use crate::constants::RGB_CONFIG_FILE;
+use crate::error::ConfigError;
+
+/// Configuration file parameters and environment variables they are mapped
+/// to
+const PARAMS: &[(&str, &str)] = &[
+    ("data-dir", "RGB_DATA_DIR"),
+    ("network", "RGB_NETWORK"),
+    ("wallet", "RGB_WALLET"),
+    ("electrum-server", "RGB_ELECTRUM_SERVER"),
+    ("node-key", "RGB_NODE_KEY"),
+    ("curve-public", "RGB_CURVE_PUBLIC"),
+    ("curve-secret", "RGB_CURVE_SECRET"),
+    ("curve-clients", "RGB_CURVE_CLIENTS"),
+    ("rgbd.bin-dir", "RGB_BIN_DIR"),
+    ("rgbd.contracts", "RGB_CONTRACTS"),
+    ("rgbd.wallets", "RGB_WALLETS"),
+    ("rgbd.restart", "RGB_RESTART"),
+    ("rgbd.max-restarts", "RGB_MAX_RESTARTS"),
+    ("rgbd.startup-timeout", "RGB_STARTUP_TIMEOUT"),
+    ("rgbd.health-file", "RGB_HEALTH_FILE"),
+    ("rgbd.rpc", "RGB_RGBD_RPC"),
+    ("rgbd.metrics", "RGB_RGBD_METRICS"),
+    ("stashd.stash", "RGB_STASHD_STASH"),
+    ("stashd.index", "RGB_STASHD_INDEX"),
+    ("stashd.tx-cache", "RGB_STASHD_TX_CACHE"),
+    ("stashd.resolvers", "RGB_STASHD_RESOLVERS"),
+    ("stashd.resolver-quorum", "RGB_STASHD_RESOLVER_QUORUM"),
+    ("stashd.confirmations", "RGB_STASHD_CONFIRMATIONS"),
+    ("stashd.finality-depth", "RGB_STASHD_FINALITY_DEPTH"),
+    ("stashd.bind", "RGB_STASHD_BIND"),
+    ("stashd.rpc", "RGB_STASHD_RPC"),
+    ("stashd.pub", "RGB_STASHD_PUB"),
+    ("stashd.metrics", "RGB_STASHD_METRICS"),
+    ("fungibled.cache", "RGB_FUNGIBLED_CACHE"),
+    ("fungibled.format", "RGB_FUNGIBLED_FORMAT"),
+    ("fungibled.resolvers", "RGB_FUNGIBLED_RESOLVERS"),
+    ("fungibled.resolver-quorum", "RGB_FUNGIBLED_RESOLVER_QUORUM"),
+    ("fungibled.confirmations", "RGB_FUNGIBLED_CONFIRMATIONS"),
+    ("fungibled.watch-interval", "RGB_FUNGIBLED_WATCH_INTERVAL"),
+    ("fungibled.rpc", "RGB_FUNGIBLED_RPC"),
+    ("fungibled.pub", "RGB_FUNGIBLED_PUB"),
+    ("fungibled.metrics", "RGB_FUNGIBLED_METRICS"),
+    ("gateway.bind", "RGB_GATEWAY_BIND"),
+    ("gateway.curve-public", "RGB_GATEWAY_CURVE_PUBLIC"),
+    ("gateway.curve-secret", "RGB_GATEWAY_CURVE_SECRET"),
+    ("cli.curve-public", "RGB_CLI_CURVE_PUBLIC"),
+    ("cli.curve-secret", "RGB_CLI_CURVE_SECRET"),
+];
+
+/// Command-line options which may be complemented with parameters from the
+/// configuration file
+pub trait LayeredOpts: Clap {
+    /// Configuration file parameters used by the daemon, each with the name
+    /// of the argument taking its value (field name in kebab case)
+    const FILE_PARAMS: &'static [(&'static str, &'static str)];
+
+    /// Configuration file path given by the user, if any
+    fn config_file(&self) -> Option<&str>;
+
+    /// Data directory, containing configuration file by default
+    fn data_dir(&self) -> &str;
+
+    /// Parses command-line arguments and environment, using parameters from
+    /// the configuration file for the values missing from both of them.
+    /// Configuration file from the data directory is optional, while the one
+    /// given explicitly must exist.
+    fn parse_layered() -> Result<Self, ConfigError> {
+        // Exits on argument errors and help requests before the configuration
+        // file is read
+        Self::parse();
+        Self::try_parse_layered_from(env::args_os())
+    }
+
+    /// Same as [`LayeredOpts::parse_layered`], but parses the given
+    /// arguments and reports errors in them instead of exiting
+    fn try_parse_layered_from<I, T>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(T::into).collect();
+        let opts = Self::try_parse_from(&args)
+            .map_err(|err| ConfigError::Args(err.to_string()))?;
+        let file = match opts.config_file() {
+            Some(path) => ConfigFile::load(Path::new(path))?,
+            None => {
+                let path =
+                    RGB_CONFIG_FILE.replace("{data_dir}", opts.data_dir());
+                if Path::new(&path).exists() {
+                    ConfigFile::load(Path::new(&path))?
+                } else {
+                    return Ok(opts);
+                }
+            }
+        };
+        let args =
+            file.layer::<Self>(args, |var| env::var_os(var).is_some())?;
+        Self::try_parse_from(args)
+            .map_err(|err| ConfigError::Args(err.to_string()))
+    }
+}
+
+/// Parameters of the configuration file under dot-separated names, with list
+/// items joined by commas
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ConfigFile(BTreeMap<String, String>);
+
+impl ConfigFile {
+    /// Reads configuration file, failing on parameters not known to any of
+    /// the daemons
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::read(File::from(path).format(FileFormat::Toml), &path.display())
+    }
+
+    /// Parses configuration file contents in TOML format
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Self::read(File::from_str(toml, FileFormat::Toml), &"<string>")
+    }
+
+    fn read<S>(
+        source: S,
+        name: &dyn std::fmt::Display,
+    ) -> Result<Self, ConfigError>
+    where
+        S: settings::Source + Send + Sync + 'static,
+    {
+        let file_error = |err: settings::ConfigError| {
+            ConfigError::File(name.to_string(), err.to_string())
+        };
+
+        let mut settings = settings::Config::default();
+        settings.merge(source).map_err(file_error)?;
+        let table: HashMap<String, Value> =
+            settings.try_into().map_err(file_error)?;
+
+        let mut params = BTreeMap::new();
+        flatten("", table, &mut params)?;
+        if let Some(param) = params
+            .keys()
+            .find(|param| !PARAMS.iter().any(|(name, _)| name == param))
+        {
+            return Err(ConfigError::UnknownParam(param.clone()));
+        }
+        Ok(ConfigFile(params))
+    }
+
+    /// Returns value of the parameter, if it is present in the file
+    pub fn get(&self, param: &str) -> Option<&str> {
+        self.0.get(param).map(String::as_str)
+    }
+
+    /// Adds parameters from the file to the command-line arguments `args`
+    /// for the options `O` which are given neither in these arguments nor in
+    /// the environment; `is_env_set` tells whether environment variable with
+    /// a given name is set.
+    pub fn layer<O>(
+        &self,
+        args: Vec<OsString>,
+        is_env_set: impl Fn(&str) -> bool,
+    ) -> Result<Vec<OsString>, ConfigError>
+    where
+        O: LayeredOpts,
+    {
+        let app = O::into_app();
+        let matches = app
+            .clone()
+            .try_get_matches_from(&args)
+            .map_err(|err| ConfigError::Args(err.to_string()))?;
+
+        let mut layered = vec![];
+        for (param, name) in O::FILE_PARAMS {
+            let value = match self.get(param) {
+                Some(value) => value,
+                None => continue,
+            };
+            if matches.occurrences_of(name) > 0 {
+                continue;
+            }
+            let var = PARAMS
+                .iter()
+                .find(|(known, _)| known == param)
+                .map(|(_, var)| *var)
+                .expect("FILE_PARAMS must contain only known parameters");
+            if is_env_set(var) {
+                continue;
+            }
+            let long = app
+                .get_arguments()
+                .find(|arg| arg.get_name() == *name)
+                .and_then(|arg| arg.get_long())
+                .expect("FILE_PARAMS must refer to long options");
+            layered.push(OsString::from(format!("--{}={}", long, value)));
+        }
+
+        // Options are put right after the binary name, so they precede the
+        // subcommands
+        let mut args = args.into_iter();
+        Ok(args.next().into_iter().chain(layered).chain(args).collect())
+    }
+}
+
+/// Collects top-level parameters and parameters from the daemon sections
+/// under dot-separated names, joining list items with commas
+fn flatten(
+    section: &str,
+    table: HashMap<String, Value>,
+    params: &mut BTreeMap<String, String>,
+) -> Result<(), ConfigError> {
+    for (key, value) in table {
+        let key = key.replace('_', "-");
+        let param = if section.is_empty() {
+            key
+        } else {
+            format!("{}.{}", section, key)
+        };
+        if let Ok(table) = value.clone().into_table() {
+            if !section.is_empty() {
+                return Err(ConfigError::UnsupportedValue(param));
+            }
+            flatten(&param, table, params)?;
+            continue;
+        }
+        let value = match value.clone().into_array() {
+            Ok(items) => items
+                .into_iter()
+                .map(Value::into_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ConfigError::UnsupportedValue(param.clone()))?
+                .join(","),
+            Err(_) => value
+                .into_str()
+                .map_err(|_| ConfigError::UnsupportedValue(param.clone()))?,
+        };
+        params.insert(param, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clap, Debug)]
+    struct Opts {
+        #[clap(long, default_value = "/var/lib/rgb")]
+        data_dir: String,
+
+        #[clap(long = "rpc", default_value = "lnpz:/default.rpc")]
+        rpc_endpoint: String,
+
+        #[clap(long, use_delimiter = true)]
+        curve_clients: Vec<String>,
+
+        #[clap(long, default_value = "1")]
+        confirmations: u32,
+    }
+
+    impl LayeredOpts for Opts {
+        const FILE_PARAMS: &'static [(&'static str, &'static str)] = &[
+            ("data-dir", "data-dir"),
+            ("stashd.rpc", "rpc-endpoint"),
+            ("curve-clients", "curve-clients"),
+            ("fungibled.confirmations", "confirmations"),
+        ];
+
+        fn config_file(&self) -> Option<&str> {
+            None
+        }
+
+        fn data_dir(&self) -> &str {
+            &self.data_dir
+        }
+    }
+
+    const TOML: &str = r#"
+        data-dir = "/data"
+        network = "testnet"
+        curve-clients = ["a", "b"]
+
+        [stashd]
+        rpc = "lnpz:/file.rpc"
+
+        [fungibled]
+        confirmations = 6
+    "#;
+
+    fn layered(args: &[&str], env: &[&str]) -> Opts {
+        let file = ConfigFile::from_toml(TOML).unwrap();
+        let args = args.iter().map(OsString::from).collect();
+        let args = file.layer::<Opts>(args, |var| env.contains(&var)).unwrap();
+        Opts::try_parse_from(args).unwrap()
+    }
+
+    #[test]
+    fn test_config_file() {
+        let file = ConfigFile::from_toml(TOML).unwrap();
+        assert_eq!(file.get("data-dir"), Some("/data"));
+        assert_eq!(file.get("curve-clients"), Some("a,b"));
+        assert_eq!(file.get("fungibled.confirmations"), Some("6"));
+        assert_eq!(file.get("stashd.stash"), None);
+
+        assert_eq!(
+            ConfigFile::from_toml("[stashd]\nunknown = 1"),
+            Err(ConfigError::UnknownParam(s!("stashd.unknown")))
+        );
+        assert_eq!(
+            ConfigFile::from_toml("[stashd.nested]\nrpc = 1"),
+            Err(ConfigError::UnsupportedValue(s!("stashd.nested")))
+        );
+        assert!(ConfigFile::from_toml("data-dir = ").is_err());
+    }
+
+    #[test]
+    fn test_layering() {
+        let opts = layered(&["stashd"], &[]);
+        assert_eq!(opts.data_dir, "/data");
+        assert_eq!(opts.rpc_endpoint, "lnpz:/file.rpc");
+        assert_eq!(opts.curve_clients, vec!["a", "b"]);
+        assert_eq!(opts.confirmations, 6);
+
+        // Command line takes precedence over the file
+        let opts = layered(
+            &["stashd", "--rpc", "lnpz:/arg.rpc", "--curve-clients=c"],
+            &[],
+        );
+        assert_eq!(opts.data_dir, "/data");
+        assert_eq!(opts.rpc_endpoint, "lnpz:/arg.rpc");
+        assert_eq!(opts.curve_clients, vec!["c"]);
+
+        // So does environment
+        let opts = layered(&["stashd"], &["RGB_STASHD_RPC", "RGB_DATA_DIR"]);
+        assert_eq!(opts.data_dir, "/var/lib/rgb");
+        assert_eq!(opts.rpc_endpoint, "lnpz:/default.rpc");
+        assert_eq!(opts.confirmations, 6);
+
+        // Defaults are used for the parameters missing from the file
+        let file = ConfigFile::from_toml("network = \"testnet\"").unwrap();
+        let args = file
+            .layer::<Opts>(vec![OsString::from("stashd")], |_| false)
+            .unwrap();
+        assert_eq!(args, vec![OsString::from("stashd")]);
+    }
+
+    #[test]
+    fn test_daemon_params() {
+        // Each daemon must refer only to the known parameters and to its own
+        // long options
+        fn check<O: LayeredOpts>(args: &[&str]) {
+            for (param, _) in O::FILE_PARAMS {
+                assert!(
+                    PARAMS.iter().any(|(name, _)| name == param),
+                    "{} reads unknown parameter {}",
+                    args[0],
+                    param
+                );
+            }
+            let len = args.len();
+            let file = ConfigFile(
+                O::FILE_PARAMS
+                    .iter()
+                    .map(|(param, _)| (param.to_string(), s!("value")))
+                    .collect(),
+            );
+            let args = file
+                .layer::<O>(args.iter().map(OsString::from).collect(), |_| {
+                    false
+                })
+                .unwrap();
+            assert_eq!(args.len(), O::FILE_PARAMS.len() + len, "{:?}", args);
+        }
+        #[cfg(any(feature = "node", feature = "client"))]
+        check::<crate::rgbd::Opts>(&["rgbd"]);
+        #[cfg(feature = "node")]
+        check::<crate::stash::Opts>(&["stashd"]);
+        #[cfg(any(feature = "node", feature = "client"))]
+        check::<crate::contracts::fungible::Opts>(&["fungibled"]);
+        #[cfg(feature = "node")]
+        check::<crate::gateway::Opts>(&["rgb-gateway"]);
+        #[cfg(feature = "cli")]
+        check::<crate::cli::Opts>(&["rgb-cli", "watch"]);
+    }
+}
@@ -0,0 +1,76 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Serde helpers for the data which have no human-readable representation
+//! in LNP/BP library, like consignments and PSBTs. Use them with
+//! `#[serde(with = "...")]` field attribute.
+
+/// Serializes data as Base64-encoded strict encoding
+pub mod strict_base64 {
+    use lnpbp::strict_encoding::{
+        self, strict_decode, strict_encode, StrictDecode, StrictEncode,
+    };
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S>(data: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: StrictEncode,
+        T::Error: std::error::Error + From<strict_encoding::Error>,
+        S: Serializer,
+    {
+        let data = strict_encode(data).map_err(S::Error::custom)?;
+        serializer.serialize_str(&base64::encode(&data))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: StrictDecode,
+        T::Error: std::error::Error + From<strict_encoding::Error>,
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let data = base64::decode(&s).map_err(D::Error::custom)?;
+        strict_decode(&data).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes PSBT as Base64-encoded string, as defined by BIP-174
+pub mod psbt_base64 {
+    use lnpbp::bitcoin::consensus::encode;
+    use lnpbp::bitcoin::util::psbt::PartiallySignedTransaction;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(
+        psbt: &PartiallySignedTransaction,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&base64::encode(&encode::serialize(psbt)))
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<PartiallySignedTransaction, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let data = base64::decode(&s).map_err(D::Error::custom)?;
+        encode::deserialize(&data).map_err(D::Error::custom)
+    }
+}
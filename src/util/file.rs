@@ -1,12 +1,13 @@
 use core::convert::TryFrom;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 use lnpbp::rgb::prelude::*;
 use lnpbp::strict_encoding::{Error, StrictDecode, StrictEncode};
 
 use super::MagicNumber;
+use crate::error::ConfigError;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display)]
 #[display(Debug)]
@@ -61,6 +62,19 @@ pub fn read_dir_filenames(
     Ok(list)
 }
 
+/// Returns path given with the named parameter as a string, so it can be put
+/// in place of the placeholders in other parameters
+pub fn utf8_path<'a>(
+    param: &str,
+    path: &'a Path,
+) -> Result<&'a str, ConfigError> {
+    path.to_str().ok_or_else(|| ConfigError::InvalidParam {
+        param: param.to_string(),
+        value: path.display().to_string(),
+        details: s!("path must be a valid UTF-8 string"),
+    })
+}
+
 pub trait ReadWrite
 where
     Self: Sized,
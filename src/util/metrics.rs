@@ -0,0 +1,175 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Collection of daemon metrics and their export in Prometheus text format
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lnpbp::lnp::transport::zmqsocket::{ZmqSocketAddr, ZmqType};
+use lnpbp::lnp::{CreateUnmarshaller, Session, Unmarshall};
+
+use crate::api::status::{RequestStats, Status};
+use crate::api::Reply;
+use crate::auth::ClientAuth;
+use crate::error::RuntimeError;
+
+/// Time to wait for the daemon to reply to `status` request, in milliseconds
+const EXPORTER_TIMEOUT: i32 = 5000;
+
+/// Collects statistics of the requests processed by a daemon
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct Metrics {
+    started: Instant,
+    requests: BTreeMap<String, RequestStats>,
+    last_error: Option<String>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: BTreeMap::new(),
+            last_error: None,
+        }
+    }
+}
+
+impl Metrics {
+    /// Accounts for the processed request, remembering the error if the
+    /// request has failed
+    pub fn record(
+        &mut self,
+        request: &str,
+        latency: Duration,
+        error: Option<String>,
+    ) {
+        self.requests
+            .entry(request.to_string())
+            .or_default()
+            .record(latency, error.is_none());
+        if error.is_some() {
+            self.last_error = error;
+        }
+    }
+
+    /// Composes daemon status from the collected metrics. Daemon-specific
+    /// information (storage, cache and connectivity) is left empty and must
+    /// be filled in by the daemon itself.
+    pub fn status(&self, agent: String) -> Status {
+        Status {
+            agent,
+            uptime: self.started.elapsed().as_secs(),
+            requests: self.requests.clone(),
+            last_error: self.last_error.clone(),
+            ..Status::default()
+        }
+    }
+}
+
+/// Serves daemon metrics in Prometheus text format over HTTP. Metrics are
+/// obtained with `status` request sent to the daemon RPC API, so exporter
+/// shares no state with the daemon and runs on its own thread.
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display(Debug)]
+pub struct Exporter {
+    /// Daemon name used as a label for all metrics
+    pub daemon: &'static str,
+    /// Authentication settings for connecting daemon RPC API
+    pub auth: ClientAuth,
+    /// Daemon RPC API endpoint
+    pub endpoint: ZmqSocketAddr,
+    /// Encoded `status` request of the daemon API
+    pub request: Vec<u8>,
+}
+
+impl Exporter {
+    /// Binds HTTP socket and starts serving metrics on a separate thread
+    pub fn spawn(self, addr: SocketAddr) -> Result<(), io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        info!(
+            "Exporting {} metrics at http://{}/metrics",
+            self.daemon, addr
+        );
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| self.serve(stream));
+                if let Err(err) = result {
+                    debug!("Error serving metrics request: {}", err);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn serve(&self, mut stream: TcpStream) -> Result<(), io::Error> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Headers must be consumed, otherwise closing the socket with unread
+        // data resets the connection before the client reads the response
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => match self.fetch() {
+                Ok(status) => ("200 OK", status.to_prometheus(self.daemon)),
+                Err(err) => {
+                    warn!("Unable to get {} status: {}", self.daemon, err);
+                    ("503 Service Unavailable", format!("{}\n", err))
+                }
+            },
+            _ => ("404 Not Found", s!("Not found\n")),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    fn fetch(&self) -> Result<Status, RuntimeError> {
+        // REQ socket is unusable after a missed reply, so each scrape uses
+        // a fresh session
+        let mut session = self.auth.connect(ZmqType::Req, &self.endpoint)?;
+        let socket = session.as_socket();
+        socket
+            .set_linger(0)
+            .and_then(|_| socket.set_rcvtimeo(EXPORTER_TIMEOUT))
+            .map_err(|err| {
+                RuntimeError::zmq_request(&self.endpoint.to_string(), err)
+            })?;
+        session.send_raw_message(&self.request)?;
+        let raw = session.recv_raw_message()?;
+        match &*Reply::create_unmarshaller().unmarshall(&raw)? {
+            Reply::Status(status) => Ok(status.clone()),
+            Reply::Failure(failure) => {
+                Err(RuntimeError::Internal(failure.to_string()))
+            }
+            _ => Err(RuntimeError::Internal(s!(
+                "unexpected reply to status request"
+            ))),
+        }
+    }
+}
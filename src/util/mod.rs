@@ -13,9 +13,20 @@
 
 #[macro_use]
 mod macros;
+pub mod chain;
+#[cfg(feature = "shell")]
+pub mod config;
+#[cfg(feature = "serde")]
+pub mod encoding;
 pub mod file;
 mod magic_numbers;
+#[cfg(feature = "node")]
+pub mod metrics;
 mod seal_spec;
+#[cfg(feature = "node")]
+pub mod shutdown;
+mod wallet_id;
 
 pub use magic_numbers::MagicNumber;
 pub use seal_spec::SealSpec;
+pub use wallet_id::{WalletId, WalletIdError};
@@ -0,0 +1,83 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Helpers for graceful shutdown of the daemons
+
+use futures::FutureExt;
+use lnpbp::lnp::transport::zmqsocket::ZmqSocketAddr;
+use std::{fs, io};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// Tracks whether the daemon was asked to shut down, either with SIGTERM or
+/// SIGINT signal or with an RPC request
+#[derive(Debug)]
+pub struct Shutdown {
+    terminate: Signal,
+    interrupt: Signal,
+    requested: bool,
+}
+
+impl Shutdown {
+    /// Starts listening for shutdown signals. Must be called from within
+    /// tokio runtime.
+    pub fn listen() -> Result<Self, io::Error> {
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+            requested: false,
+        })
+    }
+
+    /// Requests shutdown, which will happen once the current request is
+    /// processed
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Waits for any of the shutdown signals
+    pub async fn signalled(&mut self) {
+        tokio::select! {
+            _ = self.terminate.recv() => {}
+            _ = self.interrupt.recv() => {}
+        }
+        info!("Shutdown signal received");
+        self.requested = true;
+    }
+
+    /// Checks whether shutdown was requested or any of the shutdown signals
+    /// was received, without blocking
+    pub fn is_requested(&mut self) -> bool {
+        if !self.requested
+            && (self.terminate.recv().now_or_never().is_some()
+                || self.interrupt.recv().now_or_never().is_some())
+        {
+            info!("Shutdown signal received");
+            self.requested = true;
+        }
+        self.requested
+    }
+}
+
+/// Removes socket file left by ZMQ IPC endpoint, if any. Failures are only
+/// logged, since they must not prevent the rest of the shutdown procedure.
+pub fn remove_ipc_file(endpoint: &ZmqSocketAddr) {
+    if let ZmqSocketAddr::Ipc(path) = endpoint {
+        debug!("Removing IPC socket file {}", path);
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                warn!("Unable to remove IPC socket file {}: {}", path, err)
+            }
+            _ => {}
+        }
+    }
+}
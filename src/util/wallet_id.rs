@@ -0,0 +1,83 @@
+// RGB standard library
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use core::str::FromStr;
+
+use crate::constants::RGB_WALLET;
+
+/// Identifier of a wallet: independent namespace of stash and asset cache
+/// data, served by its own set of daemons. It is used in file paths and
+/// socket names as `{id}` placeholder, so it may contain only ASCII letters,
+/// digits, `-` and `_`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+#[display("{_0}")]
+pub struct WalletId(String);
+
+impl WalletId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for WalletId {
+    fn default() -> Self {
+        WalletId::from_str(RGB_WALLET)
+            .expect("Error in RGB_WALLET constant value")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum WalletIdError {
+    /// Wallet identifier must not be empty
+    Empty,
+
+    /// Wallet identifier may contain only ASCII letters, digits, `-` and `_`
+    InvalidChar,
+}
+
+impl FromStr for WalletId {
+    type Err = WalletIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err(WalletIdError::Empty)?
+        }
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            Err(WalletIdError::InvalidChar)?
+        }
+        Ok(WalletId(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wallet_id() {
+        for id in &["default", "alice", "Bob-2", "cold_storage", "0", "-_"] {
+            assert_eq!(WalletId::from_str(id).unwrap().as_str(), *id);
+        }
+        assert_eq!(WalletId::default().to_string(), RGB_WALLET);
+
+        assert_eq!(WalletId::from_str(""), Err(WalletIdError::Empty));
+        for id in &["a b", "../etc", "a/b", "a.b", "{id}", "wallet\n", "ключ"]
+        {
+            assert_eq!(WalletId::from_str(id), Err(WalletIdError::InvalidChar));
+        }
+    }
+}
@@ -13,4 +13,8 @@ cargo test test_sqlite_asset_cache
 
 cargo test test_sqlite_mappings
 
+cargo test test_sqlite_history
+
 cargo test test_filecache_mappings
+
+cargo test test_filecache_history